@@ -0,0 +1,931 @@
+// --- Frontend AST (very small subset) ---
+use std::fmt;
+use std::path::PathBuf;
+
+#[derive(Debug, Clone)]
+pub struct Import {
+    pub names: Vec<String>,
+    pub module: String,
+}
+
+#[derive(Debug, Clone)]
+pub struct Program {
+    pub imports: Vec<Import>,
+    pub structs: Vec<StructDecl>,
+    pub enums: Vec<EnumDecl>,
+    pub aliases: Vec<TypeAlias>,
+    pub interfaces: Vec<InterfaceDecl>,
+    pub functions: Vec<Function>,
+}
+
+/// `type Id = int`, `type Status = "ok" | "err"`
+#[derive(Debug, Clone)]
+pub struct TypeAlias {
+    pub name: String,
+    pub ty: TypeAnnotation,
+    pub line: usize,
+}
+
+/// `struct Point { x: int, y: int }` / `struct Box<T> { value: T }`
+#[derive(Debug, Clone)]
+pub struct StructDecl {
+    pub name: String,
+    /// `T` in `struct Box<T>`; empty for a plain struct.
+    pub type_params: Vec<String>,
+    /// The type arguments of an instance the checker generated from a generic
+    /// struct (`Box<int>`); empty otherwise.
+    pub type_args: Vec<TypeAnnotation>,
+    pub fields: Vec<Field>,
+    pub line: usize,
+}
+
+#[derive(Debug, Clone)]
+pub struct Field {
+    pub name: String,
+    pub ty: TypeAnnotation,
+    pub line: usize,
+}
+
+/// `enum Shape { Circle(int), Rect(int, int), Empty }`
+#[derive(Debug, Clone)]
+pub struct EnumDecl {
+    pub name: String,
+    pub variants: Vec<Variant>,
+    pub line: usize,
+}
+
+#[derive(Debug, Clone)]
+pub struct Variant {
+    pub name: String,
+    /// Payload types; empty for a unit variant.
+    pub fields: Vec<TypeAnnotation>,
+    pub line: usize,
+}
+
+/// `interface Writer { write(s: string): void }`: satisfied by every struct
+/// that has methods with these names and signatures, without declaring it.
+#[derive(Debug, Clone)]
+pub struct InterfaceDecl {
+    pub name: String,
+    pub methods: Vec<InterfaceMethod>,
+    pub line: usize,
+}
+
+/// A method an interface requires; it takes `self` implicitly.
+#[derive(Debug, Clone)]
+pub struct InterfaceMethod {
+    pub name: String,
+    pub params: Vec<Param>,
+    pub return_type: Option<TypeAnnotation>,
+    pub line: usize,
+}
+
+#[derive(Debug, Clone)]
+pub struct Function {
+    pub name: String,
+    /// The struct a method belongs to (`Point` for `fn Point.dist(self)` or a
+    /// `fn` inside `impl Point { .. }`); `None` for a free function.
+    pub owner: Option<String>,
+    /// How a method takes `self`; `None` for a free function and for a static
+    /// method (`fn Point.origin(): Point`).
+    pub receiver: Option<Receiver>,
+    /// `T` in `fn first<T>(xs: T[]): T?`; empty for a plain function.
+    pub type_params: Vec<String>,
+    /// The type arguments of an instance the checker generated from a generic
+    /// function (`first<int>`); empty otherwise.
+    pub type_args: Vec<TypeAnnotation>,
+    pub params: Vec<Param>,
+    pub return_type: Option<TypeAnnotation>,
+    pub body: Vec<Spanned<Stmt>>,
+    pub is_async: bool,
+    pub line: usize,
+    /// The file an imported function was loaded from, so runtime checks name
+    /// it; `None` for a function of the entry file.
+    pub source: Option<PathBuf>,
+}
+
+impl Function {
+    /// `Point.dist` for a method, the plain name otherwise.
+    pub fn full_name(&self) -> String {
+        match &self.owner {
+            Some(owner) => format!("{}.{}", owner, self.name),
+            None => self.name.clone(),
+        }
+    }
+}
+
+/// The `self` parameter of a method.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Receiver {
+    /// `self`: a read-only view of the value the method is called on.
+    Shared,
+    /// `mut self`: the method may assign the fields of the caller's value.
+    Mut,
+}
+
+#[derive(Debug, Clone)]
+pub struct Param {
+    pub name: String,
+    /// `None` for a closure parameter written without a type until the
+    /// checker takes it from the expected function type.
+    pub ty: TypeAnnotation,
+    pub line: usize,
+    /// Set by the checker when a closure captures the parameter.
+    pub captured: bool,
+    /// `{ x, y }: Point` / `(a, b): (int, string)`: a destructured parameter,
+    /// passed under the hidden `name` and unpacked by the checker.
+    pub pattern: Option<BindingPattern>,
+}
+
+impl Param {
+    /// The parameter as written: its name or the pattern destructuring it.
+    pub fn written_name(&self) -> String {
+        match &self.pattern {
+            Some(pattern) => pattern.to_string(),
+            None => self.name.clone(),
+        }
+    }
+}
+
+/// The left-hand side of a destructuring `let` / `const`, a destructured
+/// parameter or the variable of a `for .. of` loop.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum BindingPattern {
+    /// `x`; `_` binds nothing.
+    Name(String),
+    /// `(a, b)`: the elements of a tuple (or an array) by position.
+    Tuple(Vec<BindingPattern>),
+    /// `[a, b]`: the same, written like a TypeScript array pattern.
+    Array(Vec<BindingPattern>),
+    /// `{ x, y: other }`: fields of a struct or an object.
+    Record(Vec<(String, BindingPattern)>),
+}
+
+impl fmt::Display for BindingPattern {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let list = |items: &[BindingPattern]| {
+            items
+                .iter()
+                .map(|item| item.to_string())
+                .collect::<Vec<_>>()
+                .join(", ")
+        };
+        match self {
+            BindingPattern::Name(name) => f.write_str(name),
+            BindingPattern::Tuple(items) => write!(f, "({})", list(items)),
+            BindingPattern::Array(items) => write!(f, "[{}]", list(items)),
+            BindingPattern::Record(fields) => {
+                let fields = fields
+                    .iter()
+                    .map(|(field, pattern)| match pattern {
+                        BindingPattern::Name(name) if name == field => field.clone(),
+                        other => format!("{}: {}", field, other),
+                    })
+                    .collect::<Vec<_>>()
+                    .join(", ");
+                write!(f, "{{ {} }}", fields)
+            }
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum TypeAnnotation {
+    /// 32-bit signed integer; the type of integer literals unless the context
+    /// expects another numeric type.
+    Int,
+    /// `i64`: 64-bit signed integer.
+    I64,
+    /// `u64`: 64-bit unsigned integer.
+    U64,
+    /// `u8`: 8-bit unsigned integer (a byte).
+    U8,
+    /// `float` / `f64`: 64-bit IEEE 754 floating point.
+    Float,
+    String,
+    Bool,
+    Void,
+    /// A user-declared struct, enum or type alias; resolved by the checker.
+    Named(String),
+    /// String literal type (`"ok"`); a string restricted to that one value.
+    Literal(String),
+    /// `T?`: a `T` or `none`.
+    Nullable(Box<TypeAnnotation>),
+    /// Type of the `none` literal; only assignable to nullable types.
+    None,
+    /// `int | string` / `"ok" | "err"`. The checker flattens, deduplicates and
+    /// sorts the members; unions of literals and of other types never mix.
+    Union(Vec<TypeAnnotation>),
+    /// Built-in `Result<T, E>`: a `T` value on success, an `E` error otherwise.
+    /// `ok(v)` / `err(e)` leave the unknown side as `None` until the checker
+    /// fills it in from the expected type.
+    Result(Box<TypeAnnotation>, Box<TypeAnnotation>),
+    /// `T[]` / `Array<T>`: a growable array, shared by reference like in
+    /// TypeScript. The empty literal `[]` has element type `None` until the
+    /// checker fills it in from the expected type.
+    Array(Box<TypeAnnotation>),
+    /// `Map<K, V>` with `int` or `string` keys; shared by reference. `new Map()`
+    /// leaves both sides `None` until the checker fills them in.
+    Map(Box<TypeAnnotation>, Box<TypeAnnotation>),
+    /// `Set<T>` with `int` or `string` elements; shared by reference.
+    Set(Box<TypeAnnotation>),
+    /// `{ name: string, age: int }`: an anonymous record type, compared
+    /// structurally. The checker sorts the fields by name, so the order they
+    /// are written in does not matter.
+    Object(Vec<(String, TypeAnnotation)>),
+    /// `Box<int>`: an instance of a generic struct.
+    Generic(String, Vec<TypeAnnotation>),
+    /// A type parameter (`T`) inside the generic declaration that introduces
+    /// it; only equal to itself.
+    Param(String),
+    /// A parameter typed with an interface (`w: Writer`). Such a function is
+    /// instantiated once per struct type it is called with, like a generic one.
+    Interface(String),
+    /// `(int, string)`: a tuple of two or more values, accessed by position
+    /// (`t.0`) and copied like a struct.
+    Tuple(Vec<TypeAnnotation>),
+    /// `(int, string) => bool`: a function value, i.e. a top-level function
+    /// or a closure.
+    Function(Vec<TypeAnnotation>, Box<TypeAnnotation>),
+}
+
+impl TypeAnnotation {
+    /// Strings and string literal types (including unions of literals), which
+    /// all share the `vts_string` representation.
+    pub fn is_string(&self) -> bool {
+        match self {
+            TypeAnnotation::String | TypeAnnotation::Literal(_) => true,
+            TypeAnnotation::Union(members) => members
+                .iter()
+                .all(|m| matches!(m, TypeAnnotation::Literal(_))),
+            _ => false,
+        }
+    }
+
+    /// `int`, `i64`, `u64` and `u8`.
+    pub fn is_integer(&self) -> bool {
+        matches!(
+            self,
+            TypeAnnotation::Int | TypeAnnotation::I64 | TypeAnnotation::U64 | TypeAnnotation::U8
+        )
+    }
+
+    /// The integer types and `float`.
+    pub fn is_numeric(&self) -> bool {
+        self.is_integer() || *self == TypeAnnotation::Float
+    }
+}
+
+/// A type argument the checker has not determined yet (`ok(1)`'s error type,
+/// `new Map()`'s key and value) shows as `_`.
+fn open_side(ty: &TypeAnnotation) -> String {
+    match ty {
+        TypeAnnotation::None => "_".to_string(),
+        other => other.to_string(),
+    }
+}
+
+impl fmt::Display for TypeAnnotation {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            TypeAnnotation::Int => f.write_str("int"),
+            TypeAnnotation::I64 => f.write_str("i64"),
+            TypeAnnotation::U64 => f.write_str("u64"),
+            TypeAnnotation::U8 => f.write_str("u8"),
+            TypeAnnotation::Float => f.write_str("float"),
+            TypeAnnotation::String => f.write_str("string"),
+            TypeAnnotation::Bool => f.write_str("bool"),
+            TypeAnnotation::Void => f.write_str("void"),
+            TypeAnnotation::Named(name) => f.write_str(name),
+            TypeAnnotation::Literal(text) => f.write_str(&quote_str(text)),
+            TypeAnnotation::Union(members) => {
+                for (index, member) in members.iter().enumerate() {
+                    if index > 0 {
+                        f.write_str(" | ")?;
+                    }
+                    match member {
+                        TypeAnnotation::Function(..) => write!(f, "({})", member)?,
+                        _ => write!(f, "{}", member)?,
+                    }
+                }
+                Ok(())
+            }
+            TypeAnnotation::Nullable(inner)
+                if matches!(
+                    **inner,
+                    TypeAnnotation::Union(_) | TypeAnnotation::Function(..)
+                ) =>
+            {
+                write!(f, "({})?", inner)
+            }
+            TypeAnnotation::Nullable(inner) => write!(f, "{}?", inner),
+            TypeAnnotation::None => f.write_str("none"),
+            TypeAnnotation::Result(value, error) => {
+                write!(f, "Result<{}, {}>", open_side(value), open_side(error))
+            }
+            TypeAnnotation::Array(elem)
+                if matches!(
+                    **elem,
+                    TypeAnnotation::Union(_) | TypeAnnotation::Function(..)
+                ) =>
+            {
+                write!(f, "({})[]", elem)
+            }
+            TypeAnnotation::Array(elem) if **elem == TypeAnnotation::None => f.write_str("_[]"),
+            TypeAnnotation::Array(elem) => write!(f, "{}[]", elem),
+            TypeAnnotation::Map(key, value) => {
+                write!(f, "Map<{}, {}>", open_side(key), open_side(value))
+            }
+            TypeAnnotation::Set(elem) => write!(f, "Set<{}>", open_side(elem)),
+            TypeAnnotation::Generic(name, args) => {
+                write!(f, "{}<", name)?;
+                for (index, arg) in args.iter().enumerate() {
+                    if index > 0 {
+                        f.write_str(", ")?;
+                    }
+                    write!(f, "{}", arg)?;
+                }
+                f.write_str(">")
+            }
+            TypeAnnotation::Param(name) | TypeAnnotation::Interface(name) => f.write_str(name),
+            TypeAnnotation::Function(params, ret) => {
+                f.write_str("(")?;
+                for (index, param) in params.iter().enumerate() {
+                    if index > 0 {
+                        f.write_str(", ")?;
+                    }
+                    write!(f, "{}", param)?;
+                }
+                write!(f, ") => {}", ret)
+            }
+            TypeAnnotation::Tuple(elems) => {
+                f.write_str("(")?;
+                for (index, elem) in elems.iter().enumerate() {
+                    if index > 0 {
+                        f.write_str(", ")?;
+                    }
+                    write!(f, "{}", elem)?;
+                }
+                f.write_str(")")
+            }
+            TypeAnnotation::Object(fields) if fields.is_empty() => f.write_str("{}"),
+            TypeAnnotation::Object(fields) => {
+                f.write_str("{ ")?;
+                for (index, (name, ty)) in fields.iter().enumerate() {
+                    if index > 0 {
+                        f.write_str(", ")?;
+                    }
+                    write!(f, "{}: {}", name, ty)?;
+                }
+                f.write_str(" }")
+            }
+        }
+    }
+}
+
+/// The receiver of the call in `value?.method(args)`: the present value. It
+/// cannot be written in source, so it never clashes with a variable.
+pub const OPTIONAL_RECEIVER: &str = "?.";
+
+/// `"..."`: a string value as VoltTS source, escaping quotes, backslashes and
+/// control characters so that it lexes back to the same value.
+pub fn quote_str(text: &str) -> String {
+    let mut out = String::from("\"");
+    for c in text.chars() {
+        match c {
+            '"' => out.push_str("\\\""),
+            '\\' => out.push_str("\\\\"),
+            '\n' => out.push_str("\\n"),
+            '\t' => out.push_str("\\t"),
+            '\r' => out.push_str("\\r"),
+            '\0' => out.push_str("\\0"),
+            c if c.is_control() => out.push_str(&format!("\\u{{{:x}}}", c as u32)),
+            c => out.push(c),
+        }
+    }
+    out.push('"');
+    out
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Spanned<T> {
+    pub value: T,
+    pub line: usize,
+}
+
+#[derive(Debug, Clone, Copy)]
+pub enum LogLevel {
+    Info,
+    Warn,
+    Error,
+}
+
+pub type Block = Vec<Spanned<Stmt>>;
+
+#[derive(Debug, Clone)]
+pub enum Stmt {
+    Print(Expr),
+    Return(Option<Expr>),
+    Log {
+        level: LogLevel,
+        message: Expr,
+    },
+    SleepMs(Expr),
+    Expr(Expr),
+    /// `let name: T = value` / `const name = value`. `ty` is filled in by the
+    /// checker when the annotation is omitted.
+    Let {
+        name: String,
+        mutable: bool,
+        ty: Option<TypeAnnotation>,
+        value: Expr,
+        /// Set by the checker when a closure captures the (mutable) variable;
+        /// it then lives in a heap cell shared with the closure.
+        captured: bool,
+    },
+    /// `const (a, b) = pair` / `let { x, y } = p`; the checker replaces it by
+    /// a hidden `const` holding the value and one `Let` per bound name.
+    Destructure {
+        pattern: BindingPattern,
+        mutable: bool,
+        ty: Option<TypeAnnotation>,
+        value: Expr,
+    },
+    /// `target = value` or a compound `target op= value`.
+    Assign {
+        target: Expr,
+        op: Option<BinaryOp>,
+        value: Expr,
+    },
+    Await(Box<Stmt>),
+    /// `break` / `break label`
+    Break(Option<String>),
+    /// `match` in statement position: arms run blocks.
+    Match {
+        scrutinee: Expr,
+        arms: Vec<MatchArm<Block>>,
+    },
+    /// `continue` / `continue label`
+    Continue(Option<String>),
+    If {
+        condition: Expr,
+        then_branch: Block,
+        else_branch: Block,
+    },
+    While {
+        label: Option<String>,
+        condition: Expr,
+        body: Block,
+    },
+    ForRange {
+        label: Option<String>,
+        var: String,
+        range: Box<Range>,
+        body: Block,
+    },
+    /// `for item of values { .. }` / `for (key, value) of map { .. }`; the
+    /// checker leaves only `Name` variables.
+    ForOf {
+        label: Option<String>,
+        var: BindingPattern,
+        iterable: Expr,
+        body: Block,
+    },
+}
+
+/// `start..end`, `start..=end`, optionally `(..).step(n)` and/or `(..).rev()`.
+#[derive(Debug, Clone)]
+pub struct Range {
+    pub start: Expr,
+    pub end: Expr,
+    pub inclusive: bool,
+    pub step: Option<Expr>,
+    /// Visit the same values from last to first.
+    pub reverse: bool,
+}
+
+#[derive(Debug, Clone)]
+pub struct MatchArm<T> {
+    pub pattern: Pattern,
+    pub body: T,
+    pub line: usize,
+}
+
+#[derive(Debug, Clone)]
+pub enum Pattern {
+    /// `_`
+    Wildcard,
+    /// `"ok"`, for unions of string literals
+    Literal(String),
+    /// `Circle(r)`, `Shape.Rect(w, _)`, `Empty`; also `int(n)` / `Point(p)` for
+    /// the members of a union
+    Variant {
+        enum_name: Option<String>,
+        name: String,
+        /// Names bound to the payload; `_` ignores a field.
+        bindings: Vec<String>,
+    },
+}
+
+#[derive(Debug, Clone)]
+pub struct Expr {
+    pub kind: ExprKind,
+    pub line: usize,
+    /// Resolved by `type_check`; `None` straight out of the parser.
+    pub ty: Option<TypeAnnotation>,
+}
+
+impl Expr {
+    pub fn new(kind: ExprKind, line: usize) -> Self {
+        Self {
+            kind,
+            line,
+            ty: None,
+        }
+    }
+
+    /// Type assigned by the checker. Only valid on a type-checked program.
+    pub fn ty(&self) -> &TypeAnnotation {
+        self.ty
+            .as_ref()
+            .expect("expression type is resolved by type_check")
+    }
+}
+
+/// Calls `visit` on every expression in `block`, nested blocks and
+/// subexpressions included (parents before children).
+pub fn walk_block(block: &Block, visit: &mut dyn FnMut(&Expr)) {
+    for stmt in block {
+        walk_stmt(&stmt.value, visit);
+    }
+}
+
+fn walk_stmt(stmt: &Stmt, visit: &mut dyn FnMut(&Expr)) {
+    match stmt {
+        Stmt::Print(expr)
+        | Stmt::SleepMs(expr)
+        | Stmt::Expr(expr)
+        | Stmt::Log { message: expr, .. }
+        | Stmt::Let { value: expr, .. }
+        | Stmt::Destructure { value: expr, .. }
+        | Stmt::Return(Some(expr)) => walk_expr(expr, visit),
+        Stmt::Assign { target, value, .. } => {
+            walk_expr(target, visit);
+            walk_expr(value, visit);
+        }
+        Stmt::Await(inner) => walk_stmt(inner, visit),
+        Stmt::Match { scrutinee, arms } => {
+            walk_expr(scrutinee, visit);
+            for arm in arms {
+                walk_block(&arm.body, visit);
+            }
+        }
+        Stmt::If {
+            condition,
+            then_branch,
+            else_branch,
+        } => {
+            walk_expr(condition, visit);
+            walk_block(then_branch, visit);
+            walk_block(else_branch, visit);
+        }
+        Stmt::While {
+            condition, body, ..
+        } => {
+            walk_expr(condition, visit);
+            walk_block(body, visit);
+        }
+        Stmt::ForRange { range, body, .. } => {
+            walk_expr(&range.start, visit);
+            walk_expr(&range.end, visit);
+            if let Some(step) = &range.step {
+                walk_expr(step, visit);
+            }
+            walk_block(body, visit);
+        }
+        Stmt::ForOf { iterable, body, .. } => {
+            walk_expr(iterable, visit);
+            walk_block(body, visit);
+        }
+        Stmt::Return(None) | Stmt::Break(_) | Stmt::Continue(_) => {}
+    }
+}
+
+fn walk_expr(expr: &Expr, visit: &mut dyn FnMut(&Expr)) {
+    visit(expr);
+    match &expr.kind {
+        ExprKind::Int(_)
+        | ExprKind::Float(_)
+        | ExprKind::Str(_)
+        | ExprKind::Bool(_)
+        | ExprKind::None
+        | ExprKind::TimeNow
+        | ExprKind::NewCollection(_)
+        | ExprKind::FnRef(_)
+        | ExprKind::Ident(_) => {}
+        ExprKind::Call { args, .. }
+        | ExprKind::Variant { args, .. }
+        | ExprKind::ArrayLit(args)
+        | ExprKind::TupleLit(args) => {
+            for arg in args {
+                walk_expr(arg, visit);
+            }
+        }
+        ExprKind::OptionalCall { expr, call } => {
+            walk_expr(expr, visit);
+            walk_expr(call, visit);
+        }
+        ExprKind::MethodCall { receiver, args, .. }
+        | ExprKind::CallValue {
+            callee: receiver,
+            args,
+        } => {
+            walk_expr(receiver, visit);
+            for arg in args {
+                walk_expr(arg, visit);
+            }
+        }
+        ExprKind::Unary { expr, .. }
+        | ExprKind::Field { expr, .. }
+        | ExprKind::OptionalField { expr, .. }
+        | ExprKind::Is { expr, .. }
+        | ExprKind::ResultOk(Some(expr))
+        | ExprKind::ResultErr(expr)
+        | ExprKind::Try(expr)
+        | ExprKind::Await(expr)
+        | ExprKind::FsReadFile { path: expr }
+        | ExprKind::Convert { expr, .. }
+        | ExprKind::Coerce(expr) => walk_expr(expr, visit),
+        ExprKind::ResultOk(None) => {}
+        ExprKind::FsWriteFile { path, contents } => {
+            walk_expr(path, visit);
+            walk_expr(contents, visit);
+        }
+        ExprKind::Binary { lhs, rhs, .. }
+        | ExprKind::Index {
+            expr: lhs,
+            index: rhs,
+        } => {
+            walk_expr(lhs, visit);
+            walk_expr(rhs, visit);
+        }
+        ExprKind::StructLit { fields, .. } | ExprKind::ObjectLit(fields) => {
+            for (_, value) in fields {
+                walk_expr(value, visit);
+            }
+        }
+        ExprKind::Template(parts) => {
+            for part in parts {
+                if let TemplatePart::Expr(value) = part {
+                    walk_expr(value, visit);
+                }
+            }
+        }
+        ExprKind::Match { scrutinee, arms } => {
+            walk_expr(scrutinee, visit);
+            for arm in arms {
+                walk_expr(&arm.body, visit);
+            }
+        }
+        ExprKind::Closure(closure) => match &closure.body {
+            ClosureBody::Expr(body) => walk_expr(body, visit),
+            ClosureBody::Block(body) => walk_block(body, visit),
+        },
+    }
+}
+
+#[derive(Debug, Clone)]
+pub enum ExprKind {
+    /// Integer literal; `-` in front of it is a `Unary` negation.
+    Int(u64),
+    /// `1.5`, `2e10`
+    Float(f64),
+    Str(String),
+    /// `` `hello ${name}` ``: text and interpolated values, concatenated as strings.
+    Template(Vec<TemplatePart>),
+    Bool(bool),
+    /// `none`
+    None,
+    Ident(String),
+    Call {
+        callee: String,
+        args: Vec<Expr>,
+        /// Type arguments the checker inferred for a generic callee.
+        type_args: Vec<TypeAnnotation>,
+    },
+    /// `f(x)` where `f` is a function value: a variable, a field, another
+    /// call's result. The checker rewrites a `Call` of a variable to this.
+    CallValue {
+        callee: Box<Expr>,
+        args: Vec<Expr>,
+    },
+    /// `x => x * 2`, `(a: int, b: int): int => { .. }`
+    Closure(Box<Closure>),
+    /// A top-level function used as a value (`xs.map(double)`), produced by
+    /// the checker.
+    FnRef(String),
+    Unary {
+        op: UnaryOp,
+        expr: Box<Expr>,
+    },
+    Binary {
+        op: BinaryOp,
+        lhs: Box<Expr>,
+        rhs: Box<Expr>,
+    },
+    /// `value.name`; `name` is the position for a tuple element (`pair.0`)
+    Field {
+        expr: Box<Expr>,
+        name: String,
+    },
+    /// `value?.name`: `none` when `value` is, otherwise the field (made nullable).
+    OptionalField {
+        expr: Box<Expr>,
+        name: String,
+    },
+    /// `value?.method(args)`: `none` when `value` is, otherwise the method's
+    /// result (made nullable). `call` is the `MethodCall` on the present
+    /// value, which it names `OPTIONAL_RECEIVER`.
+    OptionalCall {
+        expr: Box<Expr>,
+        call: Box<Expr>,
+    },
+    /// `[1, 2, 3]`
+    ArrayLit(Vec<Expr>),
+    /// `(1, "a")`: a tuple of two or more values.
+    TupleLit(Vec<Expr>),
+    /// `new Map<K, V>()` / `new Set<T>()`; omitted type arguments are `None`.
+    NewCollection(TypeAnnotation),
+    /// `value[index]`: a string's byte or an array element.
+    Index {
+        expr: Box<Expr>,
+        index: Box<Expr>,
+    },
+    /// `Point { x: 1, y: 2 }`
+    StructLit {
+        name: String,
+        fields: Vec<(String, Expr)>,
+    },
+    /// `{ name: "a", age: 3 }`: an anonymous record of type
+    /// `{ age: int, name: string }`.
+    ObjectLit(Vec<(String, Expr)>),
+    /// `receiver.method(args)`: the built-in methods of strings (`s.slice(1, 3)`),
+    /// arrays (`xs.push(1)`, `xs.pop()`, `xs.map(f)`), maps and sets (`m.get(k)`,
+    /// `s.add(v)`) and ints/bools (`n.toString()`);
+    /// `Shape.Circle(5)` is rewritten to `Variant` by the checker, and so is a
+    /// call of a function-typed field (`b.onClick()`) to `CallValue`.
+    MethodCall {
+        receiver: Box<Expr>,
+        method: String,
+        args: Vec<Expr>,
+    },
+    /// Enum construction (`Shape.Circle(5)`, `Shape.Empty`), produced by the checker.
+    Variant {
+        enum_name: String,
+        variant: String,
+        args: Vec<Expr>,
+    },
+    /// `match` in expression position: every arm yields a value.
+    Match {
+        scrutinee: Box<Expr>,
+        arms: Vec<MatchArm<Expr>>,
+    },
+    /// `x is int`: tests which member of a union `x` currently holds.
+    Is {
+        expr: Box<Expr>,
+        ty: TypeAnnotation,
+    },
+    /// `ok(value)` (`ok()` for `Result<void, E>`), produced by the checker.
+    ResultOk(Option<Box<Expr>>),
+    /// `err(error)`, produced by the checker.
+    ResultErr(Box<Expr>),
+    /// `result?`: the success value, or an early return of the error.
+    Try(Box<Expr>),
+    /// `await value`; runs synchronously for now, so it is the value itself.
+    Await(Box<Expr>),
+    /// `time.now()`: the current time in milliseconds.
+    TimeNow,
+    /// `fs.readFile(path)`: `Result<string, string>`.
+    FsReadFile {
+        path: Box<Expr>,
+    },
+    /// `fs.writeFile(path, contents)`: `Result<void, string>`.
+    FsWriteFile {
+        path: Box<Expr>,
+        contents: Box<Expr>,
+    },
+    /// `float(n)` / `u8(x)`: an explicit numeric conversion, produced by the checker.
+    Convert {
+        expr: Box<Expr>,
+        to: TypeAnnotation,
+    },
+    /// Implicit conversion inserted by the checker; the target type is this node's `ty`.
+    Coerce(Box<Expr>),
+}
+
+#[derive(Debug, Clone)]
+pub struct Closure {
+    pub params: Vec<Param>,
+    /// Filled in by the checker when omitted: from the expected function
+    /// type, or from the body's value.
+    pub return_type: Option<TypeAnnotation>,
+    pub body: ClosureBody,
+    /// The variables of the enclosing code the body uses, filled in by the checker.
+    pub captures: Vec<Capture>,
+}
+
+#[derive(Debug, Clone)]
+pub enum ClosureBody {
+    /// `=> value`
+    Expr(Expr),
+    /// `=> { .. }`
+    Block(Block),
+}
+
+/// A variable a closure uses from the code around it. `let` variables and
+/// parameters are shared with the closure through their heap cell (as in
+/// JavaScript, the closure sees later assignments and may assign them
+/// itself); other bindings cannot change and are copied.
+#[derive(Debug, Clone)]
+pub struct Capture {
+    pub name: String,
+    /// The declared type, also when the variable is narrowed where the closure is.
+    pub ty: TypeAnnotation,
+    pub by_ref: bool,
+}
+
+#[derive(Debug, Clone)]
+pub enum TemplatePart {
+    /// Literal text (escapes kept verbatim).
+    Text(String),
+    /// `${expr}`
+    Expr(Expr),
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum UnaryOp {
+    Neg,
+    Not,
+}
+
+impl UnaryOp {
+    pub fn as_str(self) -> &'static str {
+        match self {
+            UnaryOp::Neg => "-",
+            UnaryOp::Not => "!",
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum BinaryOp {
+    Add,
+    Sub,
+    Mul,
+    Div,
+    Rem,
+    Eq,
+    Ne,
+    Lt,
+    Le,
+    Gt,
+    Ge,
+    And,
+    Or,
+    /// `a ?? b`: `a` unless it is `none`.
+    Coalesce,
+}
+
+impl BinaryOp {
+    pub fn as_str(self) -> &'static str {
+        match self {
+            BinaryOp::Add => "+",
+            BinaryOp::Sub => "-",
+            BinaryOp::Mul => "*",
+            BinaryOp::Div => "/",
+            BinaryOp::Rem => "%",
+            BinaryOp::Eq => "==",
+            BinaryOp::Ne => "!=",
+            BinaryOp::Lt => "<",
+            BinaryOp::Le => "<=",
+            BinaryOp::Gt => ">",
+            BinaryOp::Ge => ">=",
+            BinaryOp::And => "&&",
+            BinaryOp::Or => "||",
+            BinaryOp::Coalesce => "??",
+        }
+    }
+
+    /// Binding power used by the parser and the formatter (higher binds tighter).
+    pub fn precedence(self) -> u8 {
+        match self {
+            BinaryOp::Coalesce => 1,
+            BinaryOp::Or => 2,
+            BinaryOp::And => 3,
+            BinaryOp::Eq | BinaryOp::Ne => 4,
+            BinaryOp::Lt | BinaryOp::Le | BinaryOp::Gt | BinaryOp::Ge => 5,
+            BinaryOp::Add | BinaryOp::Sub => 6,
+            BinaryOp::Mul | BinaryOp::Div | BinaryOp::Rem => 7,
+        }
+    }
+}
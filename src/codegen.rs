@@ -0,0 +1,2399 @@
+// --- C code generation ---
+use std::path::Path;
+
+use crate::ast::{
+    BinaryOp, BindingPattern, Block, Closure, ClosureBody, EnumDecl, Expr, ExprKind, Function,
+    LogLevel, MatchArm, OPTIONAL_RECEIVER, Pattern, Program, Range, Stmt, StructDecl, TemplatePart,
+    TypeAnnotation, UnaryOp, walk_block,
+};
+
+pub fn codegen_c(program: &Program, source_path: &Path) -> String {
+    let mut out = String::new();
+    out.push_str("// VoltTS v0.1 generated C (prototype)\n");
+    out.push_str(&format!("// Source: {}\n", source_path.display()));
+    out.push_str("#define _XOPEN_SOURCE 700\n");
+    out.push_str("#include <stdbool.h>\n");
+    out.push_str("#include <stdint.h>\n");
+    out.push_str("#include <stdio.h>\n");
+    out.push_str("#include <stdlib.h>\n");
+    out.push_str("#include <string.h>\n");
+    out.push_str("#include <sys/time.h>\n");
+    out.push_str("#include <sys/stat.h>\n");
+    out.push_str("#include <unistd.h>\n\n");
+
+    out.push_str("// forward declaration for usleep on some libc variants\n");
+    out.push_str("int usleep(unsigned int);\n\n");
+
+    out.push_str("#if defined(__GNUC__) || defined(__clang__)\n#define VTS_UNUSED __attribute__((unused))\n#else\n#define VTS_UNUSED\n#endif\n\n");
+
+    emit_runtime(&mut out);
+    emit_types(
+        &mut out,
+        &program.structs,
+        &program.enums,
+        &collect_generated_types(program),
+    );
+
+    out.push_str("// --- user prototypes ---\n");
+    for func in &program.functions {
+        out.push_str(&format!("{};\n", function_header(func)));
+    }
+    out.push('\n');
+    emit_fn_refs(&mut out, program);
+
+    for func in &program.functions {
+        let mut emitter = FnEmitter::new(func, source_path, &program.structs, &program.enums);
+        emitter.emit_function(func);
+        // closures are lifted into functions of their own, defined first
+        out.push_str(&emitter.lifted);
+        out.push_str(&emitter.out);
+    }
+
+    out
+}
+
+fn emit_runtime(out: &mut String) {
+    out.push_str("// --- standard runtime (prototype) ---\n");
+    out.push_str("// strings carry their byte length and stay NUL-terminated for C calls;\n");
+    out.push_str("// results of concatenation, slicing and conversions are heap allocated\n");
+    out.push_str("typedef struct vts_string { size_t len; const char *data; } vts_string;\n");
+    out.push_str("#define VTS_STR(lit) ((vts_string){ sizeof(lit) - 1, lit })\n");
+    out.push_str("static VTS_UNUSED void vts_panic(const char *msg) { fflush(stdout); fprintf(stderr, \"[panic] %s\\n\", msg); exit(1); }\n");
+    out.push_str("static VTS_UNUSED char *vts_alloc(size_t size) { char *buf = (char *)malloc(size); if (!buf) { fprintf(stderr, \"out of memory\\n\"); exit(1); } return buf; }\n");
+    out.push_str("static VTS_UNUSED vts_string vts_str_from_c(const char *s) { return (vts_string){ strlen(s), s }; }\n");
+    out.push_str("static VTS_UNUSED vts_string vts_str_copy(const char *data, size_t len) { char *buf = vts_alloc(len + 1); memcpy(buf, data, len); buf[len] = 0; return (vts_string){ len, buf }; }\n");
+    out.push_str("static VTS_UNUSED vts_string vts_str_concat(vts_string a, vts_string b) { char *buf = vts_alloc(a.len + b.len + 1); memcpy(buf, a.data, a.len); memcpy(buf + a.len, b.data, b.len); buf[a.len + b.len] = 0; return (vts_string){ a.len + b.len, buf }; }\n");
+    out.push_str("static VTS_UNUSED int vts_str_cmp(vts_string a, vts_string b) { size_t n = a.len < b.len ? a.len : b.len; int c = memcmp(a.data, b.data, n); if (c != 0) return c; return a.len < b.len ? -1 : a.len > b.len; }\n");
+    out.push_str("static VTS_UNUSED bool vts_str_eq(vts_string a, vts_string b) { return a.len == b.len && memcmp(a.data, b.data, a.len) == 0; }\n");
+    out.push_str("static VTS_UNUSED vts_string vts_str_at(vts_string s, int i, const char *where) { if (i < 0 || (size_t)i >= s.len) { fflush(stdout); fprintf(stderr, \"[panic] %s: インデックス %d は範囲外です (長さ %zu)\\n\", where, i, s.len); exit(1); } return vts_str_copy(s.data + i, 1); }\n");
+    out.push_str("// `slice` follows JavaScript: negative indices count from the end, out-of-range ones are clamped\n");
+    out.push_str("static VTS_UNUSED size_t vts_str_index(vts_string s, int i) { long long n = (long long)s.len; long long k = i < 0 ? n + i : i; return (size_t)(k < 0 ? 0 : k > n ? n : k); }\n");
+    out.push_str("static VTS_UNUSED vts_string vts_str_slice(vts_string s, int start, int end) { size_t a = vts_str_index(s, start), b = vts_str_index(s, end); return b > a ? vts_str_copy(s.data + a, b - a) : VTS_STR(\"\"); }\n");
+    out.push_str("static VTS_UNUSED vts_string vts_int_to_str(long long v) { char tmp[32]; int len = snprintf(tmp, sizeof tmp, \"%lld\", v); return vts_str_copy(tmp, (size_t)len); }\n");
+    out.push_str("static VTS_UNUSED vts_string vts_uint_to_str(unsigned long long v) { char tmp[32]; int len = snprintf(tmp, sizeof tmp, \"%llu\", v); return vts_str_copy(tmp, (size_t)len); }\n");
+    out.push_str("// floats print like JavaScript numbers: the shortest digits that read back\n");
+    out.push_str("// as the same value, in exponent notation below 1e-6 and from 1e21 on\n");
+    out.push_str("static VTS_UNUSED vts_string vts_float_to_str(double v) { if (v != v) return VTS_STR(\"NaN\"); if (v - v != 0) return v > 0 ? VTS_STR(\"Infinity\") : VTS_STR(\"-Infinity\"); if (v == 0) return VTS_STR(\"0\"); char tmp[64]; int digits = 0; for (; digits < 17; digits++) { snprintf(tmp, sizeof tmp, \"%.*e\", digits, v); if (strtod(tmp, NULL) == v) break; } char *exp = strchr(tmp, 'e'); int e = atoi(exp + 1); int len; if (e >= -6 && e < 21) { len = snprintf(tmp, sizeof tmp, \"%.*f\", digits - e > 0 ? digits - e : 0, v); } else { len = (int)(exp - tmp); len += snprintf(exp, sizeof tmp - (size_t)len, \"e%+d\", e); } return vts_str_copy(tmp, (size_t)len); }\n");
+    out.push_str("static VTS_UNUSED vts_string vts_bool_to_str(bool v) { return v ? VTS_STR(\"true\") : VTS_STR(\"false\"); }\n\n");
+
+    out.push_str("// arrays are heap-allocated growable vectors shared by reference; elements\n");
+    out.push_str("// are stored inline and accessed through `(T *)` casts of the slot pointers\n");
+    out.push_str(
+        "typedef struct vts_array { int len; int cap; size_t elem_size; char *data; } vts_array;\n",
+    );
+    out.push_str("static VTS_UNUSED vts_array *vts_array_new(size_t elem_size, int cap) { vts_array *a = (vts_array *)vts_alloc(sizeof(vts_array)); a->len = 0; a->cap = cap; a->elem_size = elem_size; a->data = cap > 0 ? vts_alloc(elem_size * (size_t)cap) : NULL; return a; }\n");
+    out.push_str("static VTS_UNUSED void *vts_array_push(vts_array *a) { if (a->len == a->cap) { int cap = a->cap > 0 ? a->cap * 2 : 4; char *data = (char *)realloc(a->data, a->elem_size * (size_t)cap); if (!data) { fprintf(stderr, \"out of memory\\n\"); exit(1); } a->data = data; a->cap = cap; } return a->data + a->elem_size * (size_t)a->len++; }\n");
+    out.push_str("static VTS_UNUSED void *vts_array_pop(vts_array *a) { return a->len > 0 ? a->data + a->elem_size * (size_t)--a->len : NULL; }\n");
+    out.push_str("static VTS_UNUSED void *vts_array_at(vts_array *a, int i, const char *where) { if (i < 0 || i >= a->len) { fflush(stdout); fprintf(stderr, \"[panic] %s: インデックス %d は範囲外です (長さ %d)\\n\", where, i, a->len); exit(1); } return a->data + a->elem_size * (size_t)i; }\n\n");
+
+    out.push_str("// Map / Set are insertion-ordered hash tables shared by reference: entries\n");
+    out.push_str("// are appended to parallel key / value arrays and chained from power-of-two\n");
+    out.push_str("// buckets; a delete leaves a hole that the next resize compacts. A Set is a\n");
+    out.push_str("// map without values. Keys are `int` or `vts_string`\n");
+    out.push_str("typedef struct vts_map { int len; int used; int cap; bool str_keys; size_t key_size; size_t value_size; int *buckets; int *next; unsigned *hashes; char *live; char *keys; char *values; } vts_map;\n");
+    out.push_str("static VTS_UNUSED unsigned vts_map_hash(const vts_map *m, const void *key) { if (m->str_keys) { const vts_string *s = (const vts_string *)key; unsigned h = 2166136261u; for (size_t i = 0; i < s->len; i++) { h ^= (unsigned char)s->data[i]; h *= 16777619u; } return h; } unsigned h = (unsigned)*(const int *)key * 2654435761u; return h ^ (h >> 16); }\n");
+    out.push_str("static VTS_UNUSED bool vts_map_key_eq(const vts_map *m, const void *a, const void *b) { return m->str_keys ? vts_str_eq(*(const vts_string *)a, *(const vts_string *)b) : *(const int *)a == *(const int *)b; }\n");
+    out.push_str("static VTS_UNUSED vts_map *vts_map_new(bool str_keys, size_t key_size, size_t value_size) { vts_map *m = (vts_map *)vts_alloc(sizeof(vts_map)); memset(m, 0, sizeof(vts_map)); m->str_keys = str_keys; m->key_size = key_size; m->value_size = value_size; return m; }\n");
+    out.push_str("static VTS_UNUSED int vts_map_find(const vts_map *m, const void *key) { if (m->cap == 0) return -1; unsigned h = vts_map_hash(m, key); for (int i = m->buckets[h & (unsigned)(m->cap - 1)]; i >= 0; i = m->next[i]) { if (m->hashes[i] == h && vts_map_key_eq(m, m->keys + m->key_size * (size_t)i, key)) return i; } return -1; }\n");
+    out.push_str("static VTS_UNUSED void vts_map_grow(vts_map *m) { int cap = 8; while (cap < m->len * 2) cap *= 2; size_t n = (size_t)cap; int *buckets = (int *)vts_alloc(sizeof(int) * n); int *next = (int *)vts_alloc(sizeof(int) * n); unsigned *hashes = (unsigned *)vts_alloc(sizeof(unsigned) * n); char *live = vts_alloc(n); char *keys = vts_alloc(m->key_size * n); char *values = m->value_size > 0 ? vts_alloc(m->value_size * n) : NULL; for (int i = 0; i < cap; i++) buckets[i] = -1; int len = 0; for (int i = 0; i < m->used; i++) { if (!m->live[i]) continue; memcpy(keys + m->key_size * (size_t)len, m->keys + m->key_size * (size_t)i, m->key_size); if (values) memcpy(values + m->value_size * (size_t)len, m->values + m->value_size * (size_t)i, m->value_size); hashes[len] = m->hashes[i]; live[len] = 1; unsigned b = hashes[len] & (unsigned)(cap - 1); next[len] = buckets[b]; buckets[b] = len; len++; } free(m->buckets); free(m->next); free(m->hashes); free(m->live); free(m->keys); free(m->values); m->buckets = buckets; m->next = next; m->hashes = hashes; m->live = live; m->keys = keys; m->values = values; m->used = len; m->cap = cap; }\n");
+    out.push_str("static VTS_UNUSED void vts_map_set(vts_map *m, const void *key, const void *value) { int i = vts_map_find(m, key); if (i < 0) { if (m->used == m->cap) vts_map_grow(m); i = m->used++; unsigned h = vts_map_hash(m, key); m->hashes[i] = h; m->live[i] = 1; memcpy(m->keys + m->key_size * (size_t)i, key, m->key_size); unsigned b = h & (unsigned)(m->cap - 1); m->next[i] = m->buckets[b]; m->buckets[b] = i; m->len++; } if (m->value_size > 0) memcpy(m->values + m->value_size * (size_t)i, value, m->value_size); }\n");
+    out.push_str("static VTS_UNUSED bool vts_map_delete(vts_map *m, const void *key) { int i = vts_map_find(m, key); if (i < 0) return false; int *link = &m->buckets[m->hashes[i] & (unsigned)(m->cap - 1)]; while (*link != i) link = &m->next[*link]; *link = m->next[i]; m->live[i] = 0; m->len--; return true; }\n");
+    out.push_str("static VTS_UNUSED vts_array *vts_map_entries(const vts_map *m, bool values) { size_t size = values ? m->value_size : m->key_size; const char *src = values ? m->values : m->keys; vts_array *a = vts_array_new(size, m->len); for (int i = 0; i < m->used; i++) { if (m->live[i]) memcpy(vts_array_push(a), src + size * (size_t)i, size); } return a; }\n\n");
+    out.push_str("static VTS_UNUSED void vts_print(vts_string s) { fwrite(s.data, 1, s.len, stdout); putchar('\\n'); }\n");
+    out.push_str(
+        "static VTS_UNUSED void vts_log_info(vts_string msg) { fputs(\"[info] \", stdout); vts_print(msg); }\n",
+    );
+    out.push_str(
+        "static VTS_UNUSED void vts_log_warn(vts_string msg) { fputs(\"[warn] \", stdout); vts_print(msg); }\n",
+    );
+    out.push_str(
+        "static VTS_UNUSED void vts_log_error(vts_string msg) { fputs(\"[error] \", stdout); vts_print(msg); }\n",
+    );
+    out.push_str("static VTS_UNUSED void vts_sleep_ms(unsigned long ms) { usleep(ms * 1000); }\n");
+    out.push_str(
+        "static VTS_UNUSED int64_t vts_time_now_ms(void) { struct timeval tv; gettimeofday(&tv, NULL); return (int64_t)tv.tv_sec * 1000 + tv.tv_usec / 1000; }\n\n",
+    );
+    // the contents keep the byte count `fread` returned, so NUL bytes survive
+    out.push_str("static VTS_UNUSED bool vts_fs_read_file(const char *path, vts_string *out) { FILE *f = fopen(path, \"rb\"); if (!f) return false; if (fseek(f, 0, SEEK_END) != 0) { fclose(f); return false; } long size = ftell(f); if (size < 0) { fclose(f); return false; } if (fseek(f, 0, SEEK_SET) != 0) { fclose(f); return false; } char *buf = (char *)malloc((size_t)size + 1); if (!buf) { fclose(f); return false; } size_t read = fread(buf, 1, (size_t)size, f); buf[read] = 0; fclose(f); *out = (vts_string){ read, buf }; return true; }\n");
+    out.push_str("static VTS_UNUSED int vts_fs_write_file(const char *path, vts_string contents) { const char *slash = strrchr(path, '/'); if (slash) { size_t len = (size_t)(slash - path); if (len > 0) { char *dir = (char *)malloc(len + 1); if (!dir) return -1; memcpy(dir, path, len); dir[len] = 0; struct stat st; if (stat(dir, &st) != 0) { mkdir(dir, 0755); } free(dir); } } FILE *f = fopen(path, \"wb\"); if (!f) return -1; size_t written = fwrite(contents.data, 1, contents.len, f); fclose(f); return written == contents.len ? 0 : -1; }\n\n");
+}
+
+/// A top-level function used as a value is called through a wrapper with the
+/// signature of a closure: `static int vts_fnref_twice(void *vts_env_raw, int p0)`.
+fn emit_fn_refs(out: &mut String, program: &Program) {
+    let mut names: Vec<String> = Vec::new();
+    for func in &program.functions {
+        walk_block(&func.body, &mut |expr| {
+            if let ExprKind::FnRef(name) = &expr.kind
+                && !names.contains(name)
+            {
+                names.push(name.clone());
+            }
+        });
+    }
+    if names.is_empty() {
+        return;
+    }
+    for name in &names {
+        let func = program
+            .functions
+            .iter()
+            .find(|func| &func.name == name)
+            .expect("checked function");
+        let ret = func.return_type.as_ref().unwrap_or(&TypeAnnotation::Void);
+        let mut params = vec!["void *vts_env_raw".to_string()];
+        let mut args = Vec::new();
+        for (index, param) in func.params.iter().enumerate() {
+            params.push(c_decl(&param.ty, &format!("p{}", index)));
+            args.push(format!("p{}", index));
+        }
+        let call = format!("{}({})", function_name(name, &[]), args.join(", "));
+        let body = match ret {
+            TypeAnnotation::Void => format!("{};", call),
+            _ => format!("return {};", call),
+        };
+        out.push_str(&format!(
+            "static {}({}) {{ (void)vts_env_raw; {} }}\n",
+            c_decl(ret, &format!("vts_fnref_{}", name)),
+            params.join(", "),
+            body
+        ));
+    }
+    out.push('\n');
+}
+
+/// Emits one `typedef struct` per struct and enum declaration, dependencies
+/// first (fields and payloads are stored by value, so their types have to be
+/// complete). Enums become tagged unions:
+/// `struct Shape { int tag; union { struct { int f0; } Circle; } as; }`, and so
+/// do `int | string` unions: `struct vts_union_int_string { int tag; union { int m0; .. } as; }`.
+/// `T?` becomes `struct vts_opt_T { bool has_value; T value; }` and `Result<T, E>`
+/// `struct vts_result_T_E { bool ok; T value; E error; }`. A function type is
+/// a closure: `struct vts_fn_int_to_int { void *env; int (*call)(void *, int); }`.
+fn emit_types(
+    out: &mut String,
+    structs: &[StructDecl],
+    enums: &[EnumDecl],
+    generated: &[TypeAnnotation],
+) {
+    struct Types<'a> {
+        structs: &'a [StructDecl],
+        enums: &'a [EnumDecl],
+        done: Vec<String>,
+    }
+
+    fn emit(name: &str, types: &mut Types<'_>, out: &mut String) {
+        if types.done.iter().any(|done| done == name) {
+            return;
+        }
+        types.done.push(name.to_string());
+        if let Some(decl) = types.structs.iter().find(|s| struct_name(s) == name) {
+            for field in &decl.fields {
+                emit_dependency(&field.ty, types, out);
+            }
+            out.push_str(&format!("typedef struct {} {{\n", c_type_name(name)));
+            for field in &decl.fields {
+                out.push_str(&format!(
+                    "    {};\n",
+                    c_decl(&field.ty, &c_ident(&field.name))
+                ));
+            }
+            if decl.fields.is_empty() {
+                // C99 does not allow empty structs
+                out.push_str("    char vts_empty;\n");
+            }
+            out.push_str(&format!("}} {};\n\n", c_type_name(name)));
+        } else if let Some(decl) = types.enums.iter().find(|e| e.name == name) {
+            for ty in decl.variants.iter().flat_map(|v| &v.fields) {
+                emit_dependency(ty, types, out);
+            }
+            let tags: Vec<String> = decl
+                .variants
+                .iter()
+                .map(|v| enum_tag(&decl.name, &v.name))
+                .collect();
+            out.push_str(&format!("enum {{ {} }};\n", tags.join(", ")));
+            out.push_str(&format!("typedef struct {} {{\n", c_type_name(&decl.name)));
+            out.push_str("    int tag;\n");
+            if decl.variants.iter().any(|v| !v.fields.is_empty()) {
+                out.push_str("    union {\n");
+                for variant in decl.variants.iter().filter(|v| !v.fields.is_empty()) {
+                    let fields: Vec<String> = variant
+                        .fields
+                        .iter()
+                        .enumerate()
+                        .map(|(index, ty)| format!("{};", c_decl(ty, &format!("f{}", index))))
+                        .collect();
+                    out.push_str(&format!(
+                        "        struct {{ {} }} {};\n",
+                        fields.join(" "),
+                        variant.name
+                    ));
+                }
+                out.push_str("    } as;\n");
+            }
+            out.push_str(&format!("}} {};\n\n", c_type_name(&decl.name)));
+        }
+    }
+
+    fn emit_union(ty: &TypeAnnotation, types: &mut Types<'_>, out: &mut String) {
+        let name = c_type(ty);
+        if types.done.contains(&name) {
+            return;
+        }
+        types.done.push(name.clone());
+        let TypeAnnotation::Union(members) = ty else {
+            unreachable!("tagged union");
+        };
+        for member in members {
+            emit_dependency(member, types, out);
+        }
+        out.push_str(&format!("typedef struct {} {{\n", name));
+        out.push_str("    int tag;\n");
+        out.push_str("    union {\n");
+        for (index, member) in members.iter().enumerate() {
+            out.push_str(&format!(
+                "        {};\n",
+                c_decl(member, &format!("m{}", index))
+            ));
+        }
+        out.push_str("    } as;\n");
+        out.push_str(&format!("}} {};\n\n", name));
+    }
+
+    fn emit_optional(ty: &TypeAnnotation, types: &mut Types<'_>, out: &mut String) {
+        let name = c_type(ty);
+        if types.done.contains(&name) {
+            return;
+        }
+        types.done.push(name.clone());
+        let TypeAnnotation::Nullable(inner) = ty else {
+            unreachable!("nullable");
+        };
+        emit_dependency(inner, types, out);
+        out.push_str(&format!(
+            "typedef struct {} {{ bool has_value; {}; }} {};\n\n",
+            name,
+            c_decl(inner, "value"),
+            name
+        ));
+    }
+
+    fn emit_result(ty: &TypeAnnotation, types: &mut Types<'_>, out: &mut String) {
+        let name = c_type(ty);
+        if types.done.contains(&name) {
+            return;
+        }
+        types.done.push(name.clone());
+        let TypeAnnotation::Result(value, error) = ty else {
+            unreachable!("result");
+        };
+        emit_dependency(value, types, out);
+        emit_dependency(error, types, out);
+        let value = match **value {
+            // `Result<void, E>` carries no value
+            TypeAnnotation::Void => String::new(),
+            _ => format!(" {};", c_decl(value, "value")),
+        };
+        out.push_str(&format!(
+            "typedef struct {} {{ bool ok;{} {}; }} {};\n\n",
+            name,
+            value,
+            c_decl(error, "error"),
+            name
+        ));
+    }
+
+    fn emit_object(ty: &TypeAnnotation, types: &mut Types<'_>, out: &mut String) {
+        let name = c_type(ty);
+        if types.done.contains(&name) {
+            return;
+        }
+        types.done.push(name.clone());
+        let TypeAnnotation::Object(fields) = ty else {
+            unreachable!("object");
+        };
+        for (_, field_ty) in fields {
+            emit_dependency(field_ty, types, out);
+        }
+        out.push_str(&format!("typedef struct {} {{\n", name));
+        for (field, field_ty) in fields {
+            out.push_str(&format!("    {};\n", c_decl(field_ty, &c_ident(field))));
+        }
+        if fields.is_empty() {
+            out.push_str("    char vts_empty;\n");
+        }
+        out.push_str(&format!("}} {};\n\n", name));
+    }
+
+    fn emit_tuple(ty: &TypeAnnotation, types: &mut Types<'_>, out: &mut String) {
+        let name = c_type(ty);
+        if types.done.contains(&name) {
+            return;
+        }
+        types.done.push(name.clone());
+        let TypeAnnotation::Tuple(elems) = ty else {
+            unreachable!("tuple");
+        };
+        for elem in elems {
+            emit_dependency(elem, types, out);
+        }
+        out.push_str(&format!("typedef struct {} {{\n", name));
+        for (index, elem) in elems.iter().enumerate() {
+            out.push_str(&format!("    {};\n", c_decl(elem, &format!("f{}", index))));
+        }
+        out.push_str(&format!("}} {};\n\n", name));
+    }
+
+    fn emit_function_type(ty: &TypeAnnotation, types: &mut Types<'_>, out: &mut String) {
+        let name = c_type(ty);
+        if types.done.contains(&name) {
+            return;
+        }
+        types.done.push(name.clone());
+        let TypeAnnotation::Function(params, ret) = ty else {
+            unreachable!("function type");
+        };
+        for param in params {
+            emit_dependency(param, types, out);
+        }
+        emit_dependency(ret, types, out);
+        let params: Vec<String> = std::iter::once("void *".to_string())
+            .chain(params.iter().map(c_type))
+            .collect();
+        out.push_str(&format!(
+            "typedef struct {} {{ void *env; {}; }} {};\n\n",
+            name,
+            c_decl(ret, &format!("(*call)({})", params.join(", "))),
+            name
+        ));
+    }
+
+    fn emit_dependency(ty: &TypeAnnotation, types: &mut Types<'_>, out: &mut String) {
+        match ty {
+            TypeAnnotation::Function(..) => emit_function_type(ty, types, out),
+            TypeAnnotation::Object(_) => emit_object(ty, types, out),
+            TypeAnnotation::Tuple(_) => emit_tuple(ty, types, out),
+            TypeAnnotation::Named(inner) => emit(inner, types, out),
+            TypeAnnotation::Generic(..) => emit(&mangle(ty), types, out),
+            TypeAnnotation::Union(_) if is_tagged(ty) => emit_union(ty, types, out),
+            TypeAnnotation::Nullable(_) => emit_optional(ty, types, out),
+            TypeAnnotation::Result(..) => emit_result(ty, types, out),
+            _ => {}
+        }
+    }
+
+    if structs.is_empty() && enums.is_empty() && generated.is_empty() {
+        return;
+    }
+    out.push_str("// --- user types ---\n");
+    let mut types = Types {
+        structs,
+        enums,
+        done: Vec::new(),
+    };
+    for name in structs
+        .iter()
+        .map(struct_name)
+        .chain(enums.iter().map(|e| e.name.clone()))
+    {
+        emit(&name, &mut types, out);
+    }
+    for ty in generated {
+        emit_dependency(ty, &mut types, out);
+    }
+}
+
+/// Tagged unions, nullable, Result, object, tuple and function types used anywhere in the program;
+/// their typedefs are emitted with the user types.
+fn collect_generated_types(program: &Program) -> Vec<TypeAnnotation> {
+    // array, Map and Set contents are collected on their own: behind the
+    // pointer they do not have to be declared before the types containing them
+    fn collect(ty: &TypeAnnotation, generated: &mut Vec<TypeAnnotation>) {
+        match ty {
+            TypeAnnotation::Array(elem) | TypeAnnotation::Set(elem) => {
+                return collect(elem, generated);
+            }
+            TypeAnnotation::Map(key, value) => {
+                collect(key, generated);
+                return collect(value, generated);
+            }
+            TypeAnnotation::Object(fields) => {
+                fields.iter().for_each(|(_, ty)| collect(ty, generated));
+            }
+            TypeAnnotation::Tuple(elems) => elems.iter().for_each(|ty| collect(ty, generated)),
+            TypeAnnotation::Function(params, ret) => {
+                params.iter().for_each(|ty| collect(ty, generated));
+                collect(ret, generated);
+            }
+            _ => {}
+        }
+        let is_generated = is_tagged(ty)
+            || matches!(
+                ty,
+                TypeAnnotation::Nullable(_)
+                    | TypeAnnotation::Result(..)
+                    | TypeAnnotation::Object(_)
+                    | TypeAnnotation::Tuple(_)
+                    | TypeAnnotation::Function(..)
+            );
+        if is_generated && !generated.contains(ty) {
+            generated.push(ty.clone());
+        }
+    }
+    let mut generated = Vec::new();
+    let mut add = |ty: &TypeAnnotation| collect(ty, &mut generated);
+    for decl in &program.structs {
+        decl.fields.iter().for_each(|field| add(&field.ty));
+    }
+    for decl in &program.enums {
+        decl.variants
+            .iter()
+            .flat_map(|v| &v.fields)
+            .for_each(&mut add);
+    }
+    for func in &program.functions {
+        func.params.iter().for_each(|param| add(&param.ty));
+        if let Some(ret) = &func.return_type {
+            add(ret);
+        }
+        walk_block(&func.body, &mut |expr| {
+            if let Some(ty) = &expr.ty {
+                add(ty);
+            }
+        });
+    }
+    generated
+}
+
+/// Tag constant of an enum variant: `VTS_TAG_Shape_Circle`.
+fn enum_tag(enum_name: &str, variant: &str) -> String {
+    format!("VTS_TAG_{}_{}", enum_name, variant)
+}
+
+/// Unions of non-literal types are tagged unions in C; unions of string
+/// literals are plain strings.
+fn is_tagged(ty: &TypeAnnotation) -> bool {
+    matches!(ty, TypeAnnotation::Union(_)) && !ty.is_string()
+}
+
+/// C name of a tagged union: `vts_union_int_string`.
+fn union_name(members: &[TypeAnnotation]) -> String {
+    let parts: Vec<String> = members.iter().map(mangle).collect();
+    format!("vts_union_{}", parts.join("_"))
+}
+
+/// Identifier-safe spelling of a type, used in generated type names.
+fn mangle(ty: &TypeAnnotation) -> String {
+    match ty {
+        ty if ty.is_string() => "string".to_string(),
+        TypeAnnotation::Union(members) => union_name(members),
+        TypeAnnotation::Nullable(inner) => format!("vts_opt_{}", mangle(inner)),
+        TypeAnnotation::Result(value, error) => {
+            format!("vts_result_{}_{}", mangle(value), mangle(error))
+        }
+        TypeAnnotation::Array(elem) => format!("vts_array_{}", mangle(elem)),
+        TypeAnnotation::Map(key, value) => format!("vts_map_{}_{}", mangle(key), mangle(value)),
+        TypeAnnotation::Set(elem) => format!("vts_set_{}", mangle(elem)),
+        TypeAnnotation::Generic(name, args) => instance_name(name, args),
+        TypeAnnotation::Object(fields) => {
+            let mut name = "vts_obj".to_string();
+            for (field, ty) in fields {
+                name.push_str(&format!("_{}_{}", field, mangle(ty)));
+            }
+            name
+        }
+        TypeAnnotation::Tuple(elems) => {
+            let parts: Vec<String> = elems.iter().map(mangle).collect();
+            format!("vts_tuple_{}", parts.join("_"))
+        }
+        TypeAnnotation::Function(params, ret) => {
+            let mut name = "vts_fn".to_string();
+            for param in params {
+                name.push_str(&format!("_{}", mangle(param)));
+            }
+            format!("{}_to_{}", name, mangle(ret))
+        }
+        other => other.to_string(),
+    }
+}
+
+fn c_type(ty: &TypeAnnotation) -> String {
+    match ty {
+        TypeAnnotation::Int => "int".to_string(),
+        TypeAnnotation::I64 => "int64_t".to_string(),
+        TypeAnnotation::U64 => "uint64_t".to_string(),
+        TypeAnnotation::U8 => "uint8_t".to_string(),
+        TypeAnnotation::Float => "double".to_string(),
+        TypeAnnotation::Bool => "bool".to_string(),
+        TypeAnnotation::Named(name) => c_type_name(name),
+        TypeAnnotation::Generic(..) => c_type_name(&mangle(ty)),
+        TypeAnnotation::Union(members) if is_tagged(ty) => union_name(members),
+        TypeAnnotation::Nullable(_)
+        | TypeAnnotation::Result(..)
+        | TypeAnnotation::Object(_)
+        | TypeAnnotation::Tuple(_)
+        | TypeAnnotation::Function(..) => mangle(ty),
+        TypeAnnotation::Array(_) => "vts_array *".to_string(),
+        TypeAnnotation::Map(..) | TypeAnnotation::Set(_) => "vts_map *".to_string(),
+        TypeAnnotation::String | TypeAnnotation::Literal(_) | TypeAnnotation::Union(_) => {
+            "vts_string".to_string()
+        }
+        TypeAnnotation::Void | TypeAnnotation::None => "void".to_string(),
+        TypeAnnotation::Param(_) | TypeAnnotation::Interface(_) => {
+            unreachable!("the checker instantiates generic code")
+        }
+    }
+}
+
+/// C name of an instance of a generic function or struct: `first__int`,
+/// `Pair__int__string`. Non-generic names stay as they are, except that a
+/// method `Point.dist` becomes `Point_dist`.
+fn instance_name(name: &str, type_args: &[TypeAnnotation]) -> String {
+    let mut out = name.replace('.', "_");
+    for arg in type_args {
+        out.push_str("__");
+        out.push_str(&mangle(arg));
+    }
+    out
+}
+
+fn struct_name(decl: &StructDecl) -> String {
+    instance_name(&decl.name, &decl.type_args)
+}
+
+/// A method taking `self` gets a pointer to the value first:
+/// `double vts_f_Point_dist(vts_t_Point *self)`.
+fn function_header(func: &Function) -> String {
+    let name = func.full_name();
+    if name == "main" {
+        return "int main(void)".to_string();
+    }
+    let ret = func.return_type.as_ref().unwrap_or(&TypeAnnotation::Void);
+    let receiver = match (&func.owner, func.receiver) {
+        (Some(owner), Some(_)) => Some(format!("{} *self", c_type_name(owner))),
+        _ => None,
+    };
+    let params = receiver
+        .into_iter()
+        .chain(
+            func.params
+                .iter()
+                .map(|param| c_decl(&param.ty, &c_ident(&param.name))),
+        )
+        .collect::<Vec<_>>();
+    let params = if params.is_empty() {
+        "void".to_string()
+    } else {
+        params.join(", ")
+    };
+    let name = function_name(&name, &func.type_args);
+    format!("{}({})", c_decl(ret, &name), params)
+}
+
+/// `int x` / `vts_string s`: a C declaration of `name` with the given type.
+fn c_decl(ty: &TypeAnnotation, name: &str) -> String {
+    let c_ty = c_type(ty);
+    let sep = if c_ty.ends_with('*') { "" } else { " " };
+    format!("{}{}{}", c_ty, sep, name)
+}
+
+/// C name of a VoltTS local, parameter or field: `vts_v_count`. The prefix
+/// keeps it clear of C keywords, libc names and macros (`EOF`, `unix`) and of
+/// the other generated names.
+fn c_ident(name: &str) -> String {
+    format!("vts_v_{}", name)
+}
+
+/// C name of a user struct or enum (or an instance of a generic struct):
+/// `vts_t_Point`, `vts_t_Box__int`.
+fn c_type_name(name: &str) -> String {
+    format!("vts_t_{}", name)
+}
+
+/// C name of a user function or method (or an instance of a generic one):
+/// `vts_f_add`, `vts_f_Point_dist`, `vts_f_first__int`. `main` stays the C
+/// entry point.
+fn function_name(name: &str, type_args: &[TypeAnnotation]) -> String {
+    if name == "main" {
+        return name.to_string();
+    }
+    format!("vts_f_{}", instance_name(name, type_args))
+}
+
+/// A C string literal holding the UTF-8 bytes of `text`. Anything but plain
+/// printable ASCII is written as a 3-digit octal escape (which cannot swallow
+/// the following character the way `\x` can), and `?` is escaped to rule out
+/// trigraphs.
+fn c_string_literal(text: &str) -> String {
+    let mut out = String::from("\"");
+    for byte in text.bytes() {
+        match byte {
+            b'"' | b'\\' | b'?' => {
+                out.push('\\');
+                out.push(byte as char);
+            }
+            b' '..=b'~' => out.push(byte as char),
+            _ => out.push_str(&format!("\\{:03o}", byte)),
+        }
+    }
+    out.push('"');
+    out
+}
+
+/// `VTS_STR("...")`: a VoltTS string literal as a `vts_string` value.
+fn vts_string_literal(text: &str) -> String {
+    format!("VTS_STR({})", c_string_literal(text))
+}
+
+/// C type of the elements of the array type `ty`.
+fn array_elem_type(ty: &TypeAnnotation) -> String {
+    match ty {
+        // the empty literal `[]` before the checker fixed its type
+        TypeAnnotation::Array(elem) if **elem == TypeAnnotation::None => "char".to_string(),
+        TypeAnnotation::Array(elem) => c_type(elem),
+        _ => unreachable!("checked array"),
+    }
+}
+
+/// C type of a Map key / Set element; `new Map()` left unused keeps its key
+/// type open.
+fn map_key_type(ty: &TypeAnnotation) -> String {
+    match ty {
+        TypeAnnotation::None => "int".to_string(),
+        ty => c_type(ty),
+    }
+}
+
+/// `value` of a number or bool converted to a `vts_string`.
+fn to_string_call(value: &str, ty: &TypeAnnotation) -> String {
+    match ty {
+        TypeAnnotation::Bool => format!("vts_bool_to_str({})", value),
+        TypeAnnotation::U64 => format!("vts_uint_to_str({})", value),
+        TypeAnnotation::Float => format!("vts_float_to_str({})", value),
+        _ => format!("vts_int_to_str({})", value),
+    }
+}
+
+/// C test that the `double` variable `v` truncates to a value of the integer
+/// type `ty`.
+fn float_range_check(v: &str, ty: &TypeAnnotation) -> String {
+    let (lower, upper) = match ty {
+        TypeAnnotation::Int => ("> -2147483649.0", "2147483648.0"),
+        TypeAnnotation::I64 => (">= -9223372036854775808.0", "9223372036854775808.0"),
+        TypeAnnotation::U64 => ("> -1.0", "18446744073709551616.0"),
+        TypeAnnotation::U8 => ("> -1.0", "256.0"),
+        _ => unreachable!("integer type"),
+    };
+    format!("{0} {1} && {0} < {2}", v, lower, upper)
+}
+
+/// An expression C can take the address of: a variable, or a field or array
+/// element of one.
+fn is_place(expr: &Expr) -> bool {
+    match &expr.kind {
+        ExprKind::Ident(_) => true,
+        ExprKind::Field { expr, .. } => is_place(expr),
+        ExprKind::Index { expr, .. } => matches!(expr.ty(), TypeAnnotation::Array(_)),
+        _ => false,
+    }
+}
+
+/// `x.len` for a string `length`, `x->len` for an array `length` or a Map /
+/// Set `size`, `x.field` otherwise.
+fn field_access(value: &str, ty: &TypeAnnotation, name: &str) -> String {
+    match ty {
+        TypeAnnotation::String => format!("((int){}.len)", value),
+        TypeAnnotation::Array(_) | TypeAnnotation::Map(..) | TypeAnnotation::Set(_) => {
+            format!("{}->len", value)
+        }
+        TypeAnnotation::Tuple(_) => format!("{}.f{}", value, name),
+        _ => format!("{}.{}", value, c_ident(name)),
+    }
+}
+
+/// C jump targets of an enclosing loop. They are only emitted when a `goto`
+/// uses them: for labeled jumps out of nested loops and for `break` inside a
+/// `match` (which lowers to a C `switch` that would swallow a plain `break`).
+struct LoopLabels {
+    label: Option<String>,
+    break_target: String,
+    continue_target: String,
+    break_used: bool,
+    continue_used: bool,
+}
+
+/// Constructs a plain C `break` would leave.
+enum Breakable {
+    Loop(LoopLabels),
+    Switch,
+}
+
+/// A VoltTS variable in scope and the C expression holding it. A variable a
+/// closure captures lives in a heap cell; `place` then points to the cell.
+struct Variable {
+    name: String,
+    place: String,
+    boxed: bool,
+}
+
+struct FnEmitter<'a> {
+    structs: &'a [StructDecl],
+    enums: &'a [EnumDecl],
+    /// Source file name used in runtime panic messages.
+    source: String,
+    out: String,
+    indent: usize,
+    temp_counter: usize,
+    is_main: bool,
+    ret: TypeAnnotation,
+    /// Enclosing loops and `switch`es, innermost last.
+    breakables: Vec<Breakable>,
+    /// C name of the function, which the names of its closures include.
+    fn_name: String,
+    /// Variables in scope, innermost last; a name not found here is its own
+    /// C identifier.
+    variables: Vec<Variable>,
+    /// The functions the closures in the body were lifted into.
+    lifted: String,
+}
+
+impl<'a> FnEmitter<'a> {
+    fn new(
+        func: &Function,
+        source_path: &Path,
+        structs: &'a [StructDecl],
+        enums: &'a [EnumDecl],
+    ) -> Self {
+        Self {
+            structs,
+            enums,
+            source: func
+                .source
+                .as_deref()
+                .unwrap_or(source_path)
+                .display()
+                .to_string(),
+            out: String::new(),
+            indent: 1,
+            temp_counter: 0,
+            is_main: func.full_name() == "main",
+            ret: func.return_type.clone().unwrap_or(TypeAnnotation::Void),
+            breakables: Vec::new(),
+            fn_name: instance_name(&func.full_name(), &func.type_args),
+            variables: Vec::new(),
+            lifted: String::new(),
+        }
+    }
+
+    fn bind(&mut self, name: &str, place: String, boxed: bool) {
+        self.variables.push(Variable {
+            name: name.to_string(),
+            place,
+            boxed,
+        });
+    }
+
+    /// Declares the local `name` holding `value`. A captured variable gets a
+    /// heap cell, which it shares with the closures.
+    fn declare(&mut self, name: &str, ty: &TypeAnnotation, value: &str, captured: bool) {
+        let place = c_ident(name);
+        if captured {
+            self.line(&format!(
+                "VTS_UNUSED {0} *{1} = ({0} *)vts_alloc(sizeof({0}));",
+                c_type(ty),
+                place
+            ));
+            self.line(&format!("*{} = {};", place, value));
+        } else {
+            self.line(&format!("VTS_UNUSED {} = {};", c_decl(ty, &place), value));
+        }
+        self.bind(name, place, captured);
+    }
+
+    fn variable(&self, name: &str) -> Option<&Variable> {
+        self.variables.iter().rev().find(|v| v.name == name)
+    }
+
+    /// The C expression reading (or assigning) the variable `name`.
+    fn read_variable(&self, name: &str) -> String {
+        match self.variable(name) {
+            Some(Variable {
+                place, boxed: true, ..
+            }) => format!("(*{})", place),
+            Some(variable) => variable.place.clone(),
+            None => c_ident(name),
+        }
+    }
+
+    /// Moves a captured parameter into a heap cell.
+    fn box_param(&mut self, name: &str, ty: &TypeAnnotation) {
+        let cell = format!("vts_cell_{}", name);
+        self.line(&format!(
+            "VTS_UNUSED {0} *{1} = ({0} *)vts_alloc(sizeof({0}));",
+            c_type(ty),
+            cell
+        ));
+        self.line(&format!("*{} = {};", cell, c_ident(name)));
+        self.bind(name, cell, true);
+    }
+
+    /// Lifts `closure` into a function of its own and returns the C value of
+    /// the closure: the function plus an environment holding the captured
+    /// variables (their cells for the shared ones).
+    fn closure(&mut self, closure: &Closure, ty: &TypeAnnotation) -> String {
+        let name = format!("{}_{}", self.temp("closure"), self.fn_name);
+        let env = format!("{}_env", name);
+        let ret = closure.return_type.clone().expect("checked closure");
+        let mut inner = FnEmitter {
+            structs: self.structs,
+            enums: self.enums,
+            source: self.source.clone(),
+            out: String::new(),
+            indent: 1,
+            temp_counter: self.temp_counter,
+            is_main: false,
+            ret: ret.clone(),
+            breakables: Vec::new(),
+            fn_name: self.fn_name.clone(),
+            variables: Vec::new(),
+            lifted: String::new(),
+        };
+        let mut params = vec!["void *vts_env_raw".to_string()];
+        params.extend(
+            closure
+                .params
+                .iter()
+                .map(|param| c_decl(&param.ty, &c_ident(&param.name))),
+        );
+        inner.out.push_str(&format!(
+            "static {}({}) {{\n",
+            c_decl(&ret, &name),
+            params.join(", ")
+        ));
+        if closure.captures.is_empty() {
+            inner.line("(void)vts_env_raw;");
+        } else {
+            inner.line(&format!("{0} *vts_env = ({0} *)vts_env_raw;", env));
+        }
+        for capture in &closure.captures {
+            inner.bind(
+                &capture.name,
+                format!("vts_env->{}", c_ident(&capture.name)),
+                capture.by_ref,
+            );
+        }
+        for param in &closure.params {
+            if param.captured {
+                inner.box_param(&param.name, &param.ty);
+            } else {
+                inner.bind(&param.name, c_ident(&param.name), false);
+            }
+        }
+        match &closure.body {
+            ClosureBody::Expr(body) => {
+                let value = inner.expr(body);
+                if ret == TypeAnnotation::Void {
+                    inner.line(&format!("(void)({});", value));
+                } else {
+                    inner.line(&format!("return {};", value));
+                }
+            }
+            ClosureBody::Block(body) => {
+                inner.emit_block(body);
+                let ends_with_return =
+                    matches!(body.last().map(|stmt| &stmt.value), Some(Stmt::Return(_)));
+                if !ends_with_return && !matches!(ret, TypeAnnotation::Void) {
+                    inner.line("abort();");
+                }
+            }
+        }
+        inner.out.push_str("}\n\n");
+        self.temp_counter = inner.temp_counter;
+
+        // the environment: a copy of each captured value, a pointer to each cell
+        let mut code = String::new();
+        if !closure.captures.is_empty() {
+            let fields: Vec<String> = closure
+                .captures
+                .iter()
+                .map(|capture| {
+                    let field = c_ident(&capture.name);
+                    if capture.by_ref {
+                        format!("{} *{};", c_type(&capture.ty), field)
+                    } else {
+                        format!("{};", c_decl(&capture.ty, &field))
+                    }
+                })
+                .collect();
+            self.lifted.push_str(&format!(
+                "typedef struct {} {{ {} }} {};\n",
+                env,
+                fields.join(" "),
+                env
+            ));
+            let tmp = self.temp("env");
+            code.push_str(&format!(
+                "({{ {0} *{1} = ({0} *)vts_alloc(sizeof({0})); ",
+                env, tmp
+            ));
+            for capture in &closure.captures {
+                let value = match self.variable(&capture.name) {
+                    Some(variable) if capture.by_ref => variable.place.clone(),
+                    _ => self.read_variable(&capture.name),
+                };
+                code.push_str(&format!(
+                    "{}->{} = {}; ",
+                    tmp,
+                    c_ident(&capture.name),
+                    value
+                ));
+            }
+            code.push_str(&format!("({}){{ {}, {} }}; }})", c_type(ty), tmp, name));
+        } else {
+            code = format!("(({}){{ NULL, {} }})", c_type(ty), name);
+        }
+        self.lifted.push_str(&inner.lifted);
+        self.lifted.push_str(&inner.out);
+        code
+    }
+
+    fn line(&mut self, code: &str) {
+        for _ in 0..self.indent {
+            self.out.push_str("    ");
+        }
+        self.out.push_str(code);
+        self.out.push('\n');
+    }
+
+    fn temp(&mut self, prefix: &str) -> String {
+        let name = format!("vts_{}{}", prefix, self.temp_counter);
+        self.temp_counter += 1;
+        name
+    }
+
+    fn emit_function(&mut self, func: &Function) {
+        self.out
+            .push_str(&format!("{} {{\n", function_header(func)));
+        if func.receiver.is_some() {
+            self.bind("self", "self".to_string(), true);
+        }
+        for param in func.params.iter().filter(|param| param.captured) {
+            self.box_param(&param.name, &param.ty);
+        }
+        self.emit_block(&func.body);
+        let ends_with_return = matches!(
+            func.body.last().map(|stmt| &stmt.value),
+            Some(Stmt::Return(_))
+        );
+        if !ends_with_return {
+            if self.is_main {
+                self.line("return 0;");
+            } else if !matches!(self.ret, TypeAnnotation::Void) {
+                // the checker guarantees a return exists; this only silences -Wreturn-type
+                self.line("abort();");
+            }
+        }
+        self.out.push_str("}\n\n");
+    }
+
+    fn emit_block(&mut self, block: &Block) {
+        let depth = self.variables.len();
+        for stmt in block {
+            self.emit_stmt(&stmt.value);
+        }
+        self.variables.truncate(depth);
+    }
+
+    fn emit_nested(&mut self, header: &str, block: &Block) {
+        self.line(&format!("{} {{", header));
+        self.indent += 1;
+        self.emit_block(block);
+        self.indent -= 1;
+        self.line("}");
+    }
+
+    /// `vts_panic("file.vts:12: message");`
+    fn panic_call(&self, line: usize, message: &str) -> String {
+        format!(
+            "vts_panic({});",
+            self.location_message(line, &format!(": {}", message))
+        )
+    }
+
+    /// `"file.vts:12"`: the source location handed to runtime checks.
+    fn location(&self, line: usize) -> String {
+        self.location_message(line, "")
+    }
+
+    fn location_message(&self, line: usize, suffix: &str) -> String {
+        c_string_literal(&format!("{}:{}{}", self.source, line, suffix))
+    }
+
+    /// Emits a loop. `prelude` is emitted first inside the body (e.g. the loop variable).
+    fn emit_loop(
+        &mut self,
+        header: &str,
+        label: Option<&str>,
+        prelude: Option<&str>,
+        body: &Block,
+    ) {
+        let suffix = label.map(|label| format!("{}_", label)).unwrap_or_default();
+        let labels = LoopLabels {
+            label: label.map(str::to_string),
+            break_target: self.temp(&format!("break_{}", suffix)),
+            continue_target: self.temp(&format!("continue_{}", suffix)),
+            break_used: false,
+            continue_used: false,
+        };
+        self.line(&format!("{} {{", header));
+        self.indent += 1;
+        self.breakables.push(Breakable::Loop(labels));
+        if let Some(prelude) = prelude {
+            self.line(prelude);
+        }
+        self.emit_block(body);
+        let Some(Breakable::Loop(labels)) = self.breakables.pop() else {
+            unreachable!("loop frame");
+        };
+        if labels.continue_used {
+            self.line(&format!("{}: VTS_UNUSED;", labels.continue_target));
+        }
+        self.indent -= 1;
+        self.line("}");
+        if labels.break_used {
+            self.line(&format!("{}: VTS_UNUSED;", labels.break_target));
+        }
+    }
+
+    fn emit_jump(&mut self, is_break: bool, label: Option<&str>) {
+        let keyword = if is_break { "break" } else { "continue" };
+        let target = self
+            .breakables
+            .iter()
+            .rposition(|frame| match frame {
+                Breakable::Loop(labels) => label.is_none() || labels.label.as_deref() == label,
+                Breakable::Switch => false,
+            })
+            .expect("loop labels are resolved by the checker");
+        // a plain jump reaches the innermost loop, unless a switch is in between for `break`
+        let plain = self.breakables[target + 1..]
+            .iter()
+            .all(|frame| match frame {
+                Breakable::Loop(_) => false,
+                Breakable::Switch => !is_break,
+            });
+        if plain {
+            self.line(&format!("{};", keyword));
+            return;
+        }
+        let Breakable::Loop(labels) = &mut self.breakables[target] else {
+            unreachable!("jump target is a loop");
+        };
+        let target = if is_break {
+            labels.break_used = true;
+            labels.break_target.clone()
+        } else {
+            labels.continue_used = true;
+            labels.continue_target.clone()
+        };
+        self.line(&format!("goto {};", target));
+    }
+
+    fn emit_stmt(&mut self, stmt: &Stmt) {
+        match stmt {
+            Stmt::Await(inner) => self.emit_stmt(inner),
+            Stmt::Print(expr) => {
+                let value = self.expr(expr);
+                let code = match expr.ty() {
+                    TypeAnnotation::Int => format!("printf(\"%d\\n\", {});", value),
+                    ty if ty.is_numeric() || *ty == TypeAnnotation::Bool => {
+                        format!("vts_print({});", to_string_call(&value, ty))
+                    }
+                    _ => format!("vts_print({});", value),
+                };
+                self.line(&code);
+            }
+            Stmt::Log { level, message } => {
+                let call = match level {
+                    LogLevel::Info => "vts_log_info",
+                    LogLevel::Warn => "vts_log_warn",
+                    LogLevel::Error => "vts_log_error",
+                };
+                let message = self.expr(message);
+                self.line(&format!("{}({});", call, message));
+            }
+            Stmt::SleepMs(ms) => {
+                let ms = self.expr(ms);
+                self.line(&format!("vts_sleep_ms((unsigned long)({}));", ms));
+            }
+            Stmt::Expr(expr) => {
+                let value = self.expr(expr);
+                if matches!(expr.kind, ExprKind::Call { .. }) {
+                    self.line(&format!("{};", value));
+                } else {
+                    self.line(&format!("(void)({});", value));
+                }
+            }
+            Stmt::Let {
+                name,
+                ty,
+                value,
+                captured,
+                ..
+            } => {
+                let ty = ty.clone().unwrap_or_else(|| value.ty().clone());
+                let value = self.expr(value);
+                self.declare(name, &ty, &value, *captured);
+            }
+            Stmt::Destructure { .. } => unreachable!("the checker expands destructuring"),
+            Stmt::Assign { target, op, value } => {
+                // compound operators are desugared by the checker
+                debug_assert!(op.is_none());
+                let target = self.expr(target);
+                let value = self.expr(value);
+                self.line(&format!("{} = {};", target, value));
+            }
+            Stmt::Return(value) => match value {
+                Some(expr) => {
+                    let value = self.expr(expr);
+                    self.line(&format!("return {};", value));
+                }
+                None if self.is_main => self.line("return 0;"),
+                None => self.line("return;"),
+            },
+            Stmt::If {
+                condition,
+                then_branch,
+                else_branch,
+            } => {
+                let condition = self.expr(condition);
+                self.emit_nested(&format!("if ({})", condition), then_branch);
+                if !else_branch.is_empty() {
+                    self.emit_nested("else", else_branch);
+                }
+            }
+            Stmt::While {
+                label,
+                condition,
+                body,
+            } => {
+                let condition = self.expr(condition);
+                self.emit_loop(
+                    &format!("while ({})", condition),
+                    label.as_deref(),
+                    None,
+                    body,
+                );
+            }
+            Stmt::ForRange {
+                label,
+                var,
+                range,
+                body,
+            } => self.emit_for_range(label.as_deref(), var, range, body),
+            Stmt::ForOf {
+                label,
+                var,
+                iterable,
+                body,
+            } => self.emit_for_of(label.as_deref(), var, iterable, body),
+            Stmt::Match { scrutinee, arms } => self.emit_match(scrutinee, arms),
+            Stmt::Break(label) => self.emit_jump(true, label.as_deref()),
+            Stmt::Continue(label) => self.emit_jump(false, label.as_deref()),
+        }
+    }
+
+    /// Lowers a `match` statement to a `switch` over the enum tag; payload
+    /// bindings become locals at the top of each case.
+    fn emit_match(&mut self, scrutinee: &Expr, arms: &[MatchArm<Block>]) {
+        let value = self.expr(scrutinee);
+        let subject = self.temp("m");
+        self.line("{");
+        self.indent += 1;
+        self.line(&format!(
+            "{} = {};",
+            c_decl(scrutinee.ty(), &subject),
+            value
+        ));
+        let selector = self.match_selector(scrutinee.ty(), &subject);
+        self.line(&format!("switch ({}) {{", selector));
+        self.breakables.push(Breakable::Switch);
+        for arm in arms {
+            let (case, bindings) = self.match_case(scrutinee.ty(), &subject, &arm.pattern);
+            self.line(&format!("{} {{", case));
+            self.indent += 1;
+            for binding in bindings {
+                self.line(&binding);
+            }
+            let depth = self.variables.len();
+            self.bind_pattern(&arm.pattern);
+            self.emit_block(&arm.body);
+            self.variables.truncate(depth);
+            self.line("break;");
+            self.indent -= 1;
+            self.line("}");
+        }
+        self.breakables.pop();
+        self.line("}");
+        self.indent -= 1;
+        self.line("}");
+    }
+
+    /// Binds the names a match arm's pattern declares.
+    fn bind_pattern(&mut self, pattern: &Pattern) {
+        if let Pattern::Variant { bindings, .. } = pattern {
+            for binding in bindings.iter().filter(|b| b.as_str() != "_") {
+                self.bind(binding, c_ident(binding), false);
+            }
+        }
+    }
+
+    /// Value a `match` switches on: the tag of an enum or tagged union, or the
+    /// index of the matching string for a union of string literals.
+    fn match_selector(&self, ty: &TypeAnnotation, subject: &str) -> String {
+        match ty {
+            TypeAnnotation::Union(members) if ty.is_string() => {
+                let mut selector = String::new();
+                for (index, member) in members.iter().enumerate() {
+                    let TypeAnnotation::Literal(text) = member else {
+                        unreachable!("literal union");
+                    };
+                    selector.push_str(&format!(
+                        "vts_str_eq({}, {}) ? {} : ",
+                        subject,
+                        vts_string_literal(text),
+                        index
+                    ));
+                }
+                format!("{}-1", selector)
+            }
+            TypeAnnotation::Result(..) => format!("(int){}.ok", subject),
+            _ => format!("{}.tag", subject),
+        }
+    }
+
+    /// `case VTS_TAG_E_V:` / `case 1:` (or `default:`) plus the binding
+    /// declarations of `pattern`.
+    fn match_case(
+        &self,
+        ty: &TypeAnnotation,
+        subject: &str,
+        pattern: &Pattern,
+    ) -> (String, Vec<String>) {
+        let binding = |binding: &str, field_ty: &TypeAnnotation, access: String| {
+            format!(
+                "VTS_UNUSED {} = {}.as.{};",
+                c_decl(field_ty, &c_ident(binding)),
+                subject,
+                access
+            )
+        };
+        match (pattern, ty) {
+            (Pattern::Wildcard, _) => ("default:".to_string(), Vec::new()),
+            (Pattern::Literal(text), TypeAnnotation::Union(members)) => {
+                let literal = TypeAnnotation::Literal(text.clone());
+                let index = members.iter().position(|m| *m == literal);
+                (
+                    format!("case {}:", index.expect("literal checked")),
+                    Vec::new(),
+                )
+            }
+            (Pattern::Variant { name, bindings, .. }, TypeAnnotation::Union(members)) => {
+                // the checker rewrites member patterns to the member's name
+                let index = members
+                    .iter()
+                    .position(|m| m.to_string() == *name)
+                    .expect("member checked");
+                let declarations = bindings
+                    .iter()
+                    .filter(|b| b.as_str() != "_")
+                    .map(|b| binding(b, &members[index], format!("m{}", index)))
+                    .collect();
+                (format!("case {}:", index), declarations)
+            }
+            (Pattern::Variant { name, bindings, .. }, TypeAnnotation::Named(enum_name)) => {
+                let variant = self
+                    .enums
+                    .iter()
+                    .find(|e| &e.name == enum_name)
+                    .and_then(|e| e.variants.iter().find(|v| &v.name == name))
+                    .expect("variant resolved by the checker");
+                let declarations = bindings
+                    .iter()
+                    .zip(&variant.fields)
+                    .enumerate()
+                    .filter(|(_, (b, _))| b.as_str() != "_")
+                    .map(|(index, (b, field_ty))| {
+                        binding(b, field_ty, format!("{}.f{}", name, index))
+                    })
+                    .collect();
+                (format!("case {}:", enum_tag(enum_name, name)), declarations)
+            }
+            (Pattern::Variant { name, bindings, .. }, TypeAnnotation::Result(value, error)) => {
+                let (case, field, field_ty) = if name == "ok" {
+                    ("case 1:", "value", value)
+                } else {
+                    ("case 0:", "error", error)
+                };
+                let declarations = bindings
+                    .iter()
+                    .filter(|b| b.as_str() != "_")
+                    .map(|b| {
+                        format!(
+                            "VTS_UNUSED {} = {}.{};",
+                            c_decl(field_ty, &c_ident(b)),
+                            subject,
+                            field
+                        )
+                    })
+                    .collect();
+                (case.to_string(), declarations)
+            }
+            _ => unreachable!("the checker only allows matching on enums, unions and results"),
+        }
+    }
+
+    /// Bounds and step are evaluated once; the loop runs over an element count
+    /// precomputed in unsigned 64-bit arithmetic (which holds the distance
+    /// between any two bounds) so stepped and reversed ranges cannot overflow.
+    fn emit_for_range(&mut self, label: Option<&str>, var: &str, range: &Range, body: &Block) {
+        let ty = range.start.ty();
+        let c_ty = c_type(ty);
+        let start = self.expr(&range.start);
+        let end = self.expr(&range.end);
+        let lo = self.temp("lo");
+        let hi = self.temp("hi");
+        let count = self.temp("count");
+        let k = self.temp("k");
+        self.line("{");
+        self.indent += 1;
+        self.line(&format!("{} {} = {};", c_ty, lo, start));
+        self.line(&format!("{} {} = {};", c_ty, hi, end));
+        let step = match &range.step {
+            Some(step_expr) => {
+                let value = self.expr(step_expr);
+                let step = self.temp("step");
+                self.line(&format!("{} {} = {};", c_ty, step, value));
+                let panic =
+                    self.panic_call(step_expr.line, "for の step は正の整数である必要があります");
+                self.line(&format!("if ({} <= 0) {}", step, panic));
+                Some(step)
+            }
+            None => None,
+        };
+        let (cmp, span) = if range.inclusive {
+            (">=", format!("(uint64_t){} - (uint64_t){}", hi, lo))
+        } else {
+            (">", format!("(uint64_t){} - (uint64_t){} - 1", hi, lo))
+        };
+        let per_step = match &step {
+            Some(step) => format!("({}) / (uint64_t){}", span, step),
+            None => span,
+        };
+        self.line(&format!(
+            "uint64_t {} = {} {} {} ? {} + 1 : 0;",
+            count, hi, cmp, lo, per_step
+        ));
+        let index = if range.reverse {
+            format!("({} - 1 - {})", count, k)
+        } else {
+            k.clone()
+        };
+        let value = match &step {
+            Some(step) => format!(
+                "({})((uint64_t){} + {} * (uint64_t){})",
+                c_ty, lo, index, step
+            ),
+            None => format!("({})((uint64_t){} + {})", c_ty, lo, index),
+        };
+        let depth = self.variables.len();
+        self.bind(var, c_ident(var), false);
+        self.emit_loop(
+            &format!("for (uint64_t {0} = 0; {0} < {1}; {0}++)", k, count),
+            label,
+            Some(&format!(
+                "VTS_UNUSED const {} = {};",
+                c_decl(ty, &c_ident(var)),
+                value
+            )),
+            body,
+        );
+        self.variables.truncate(depth);
+        self.indent -= 1;
+        self.line("}");
+    }
+
+    /// `for x of xs`: walks the indices while re-reading the length, so elements
+    /// pushed by the body are visited too (as in JavaScript).
+    fn emit_for_of(
+        &mut self,
+        label: Option<&str>,
+        var: &BindingPattern,
+        iterable: &Expr,
+        body: &Block,
+    ) {
+        let BindingPattern::Name(var) = var else {
+            unreachable!("the checker expands destructuring");
+        };
+        let mut value = self.expr(iterable);
+        let array = self.temp("arr");
+        let k = self.temp("k");
+        self.line("{");
+        self.indent += 1;
+        let (elem, item) = match iterable.ty() {
+            TypeAnnotation::Array(elem) => ((**elem).clone(), None),
+            // a Set iterates over a snapshot of its elements
+            TypeAnnotation::Set(elem) => {
+                value = format!("vts_map_entries({}, false)", value);
+                ((**elem).clone(), None)
+            }
+            // and a Map over snapshots of its keys and values, paired up
+            TypeAnnotation::Map(key, map_value) => {
+                let map = self.temp("map");
+                let values = self.temp("vals");
+                self.line(&format!("vts_map *{} = {};", map, value));
+                self.line(&format!(
+                    "vts_array *{} = vts_map_entries({}, true);",
+                    values, map
+                ));
+                value = format!("vts_map_entries({}, false)", map);
+                let elem = TypeAnnotation::Tuple(vec![(**key).clone(), (**map_value).clone()]);
+                let item = format!(
+                    "(({}){{ .f0 = (({} *){}->data)[{}], .f1 = (({} *){}->data)[{}] }})",
+                    c_type(&elem),
+                    c_type(key),
+                    array,
+                    k,
+                    c_type(map_value),
+                    values,
+                    k
+                );
+                (elem, Some(item))
+            }
+            _ => unreachable!("checked array"),
+        };
+        let item = item.unwrap_or_else(|| format!("(({} *){}->data)[{}]", c_type(&elem), array, k));
+        self.line(&format!("vts_array *{} = {};", array, value));
+        let depth = self.variables.len();
+        self.bind(var, c_ident(var), false);
+        self.emit_loop(
+            &format!("for (int {0} = 0; {0} < {1}->len; {0}++)", k, array),
+            label,
+            Some(&format!(
+                "VTS_UNUSED {} = {};",
+                c_decl(&elem, &c_ident(var)),
+                item
+            )),
+            body,
+        );
+        self.variables.truncate(depth);
+        self.indent -= 1;
+        self.line("}");
+    }
+
+    fn expr(&mut self, expr: &Expr) -> String {
+        match &expr.kind {
+            // a literal taking a float type (`x * 2`) is written as a double
+            ExprKind::Int(v) if *expr.ty() == TypeAnnotation::Float => format!("{:?}", *v as f64),
+            ExprKind::Int(v) if i64::try_from(*v).is_err() => format!("{}ULL", v),
+            ExprKind::Int(v) if i32::try_from(*v).is_err() => format!("{}LL", v),
+            ExprKind::Int(v) => v.to_string(),
+            ExprKind::Float(v) => format!("{:?}", v),
+            ExprKind::Str(text) => vts_string_literal(text),
+            ExprKind::Template(parts) => {
+                let mut pieces = Vec::new();
+                for part in parts {
+                    pieces.push(match part {
+                        TemplatePart::Text(text) => vts_string_literal(text),
+                        TemplatePart::Expr(value) => self.string_expr(value),
+                    });
+                }
+                let mut pieces = pieces.into_iter();
+                let first = pieces.next().unwrap_or_else(|| vts_string_literal(""));
+                pieces.fold(first, |acc, piece| {
+                    format!("vts_str_concat({}, {})", acc, piece)
+                })
+            }
+            ExprKind::Bool(v) => v.to_string(),
+            // only reached through a `Coerce` to a nullable or a none comparison
+            ExprKind::None => "0".to_string(),
+            ExprKind::Ident(name) => self.read_variable(name),
+            ExprKind::FnRef(name) => {
+                format!("(({}){{ NULL, vts_fnref_{} }})", c_type(expr.ty()), name)
+            }
+            ExprKind::Closure(closure) => self.closure(closure, expr.ty()),
+            ExprKind::CallValue { callee, args } => {
+                let function = self.expr(callee);
+                let args: Vec<String> = args.iter().map(|arg| self.expr(arg)).collect();
+                let call = |function: &str| {
+                    let mut call_args = vec![format!("{}.env", function)];
+                    call_args.extend(args.iter().cloned());
+                    format!("{}.call({})", function, call_args.join(", "))
+                };
+                if matches!(callee.kind, ExprKind::Ident(_)) {
+                    call(&function)
+                } else {
+                    // the callee is evaluated once
+                    let tmp = self.temp("fn");
+                    format!(
+                        "({{ {} = {}; {}; }})",
+                        c_decl(callee.ty(), &tmp),
+                        function,
+                        call(&tmp)
+                    )
+                }
+            }
+            ExprKind::Call {
+                callee,
+                args,
+                type_args,
+            } => {
+                let args: Vec<String> = args.iter().map(|arg| self.expr(arg)).collect();
+                format!("{}({})", function_name(callee, type_args), args.join(", "))
+            }
+            ExprKind::Unary {
+                op: UnaryOp::Neg,
+                expr: inner,
+            } if expr.ty().is_integer() && !matches!(inner.kind, ExprKind::Int(_)) => {
+                let value = self.expr(inner);
+                let panic = self.panic_call(
+                    expr.line,
+                    &format!("{} の演算 - がオーバーフローしました", expr.ty()),
+                );
+                self.overflow_check("sub", expr.ty(), "0", &value, &panic)
+            }
+            ExprKind::Unary {
+                op: UnaryOp::Neg,
+                expr: inner,
+            } if matches!(inner.kind, ExprKind::Int(v) if v == 1 << 63) => "INT64_MIN".to_string(),
+            // `-2147483648` keeps its type although its digits do not fit it
+            ExprKind::Unary {
+                op: UnaryOp::Neg,
+                expr: inner,
+            } if expr.ty().is_integer()
+                && matches!(inner.kind, ExprKind::Int(v) if i32::try_from(v).is_err()) =>
+            {
+                format!("(({})-{})", c_type(expr.ty()), self.expr(inner))
+            }
+            ExprKind::Unary { op, expr: inner } => {
+                format!("({}{})", op.as_str(), self.expr(inner))
+            }
+            ExprKind::Binary { op, lhs, rhs } => {
+                let is_string = |e: &Expr| e.ty().is_string();
+                let is_none = |e: &Expr| matches!(e.kind, ExprKind::None);
+                match op {
+                    BinaryOp::Eq | BinaryOp::Ne if is_none(lhs) || is_none(rhs) => {
+                        let value = if is_none(lhs) { rhs } else { lhs };
+                        let present = if is_none(value) {
+                            "false".to_string()
+                        } else {
+                            format!("({}).has_value", self.expr(value))
+                        };
+                        if *op == BinaryOp::Eq {
+                            format!("(!{})", present)
+                        } else {
+                            format!("({})", present)
+                        }
+                    }
+                    BinaryOp::Coalesce => {
+                        let tmp = self.temp("opt");
+                        let value = self.expr(lhs);
+                        let fallback = self.expr(rhs);
+                        let present = if matches!(expr.ty(), TypeAnnotation::Nullable(_)) {
+                            tmp.clone()
+                        } else {
+                            format!("{}.value", tmp)
+                        };
+                        format!(
+                            "({{ {} = {}; {}.has_value ? {} : {}; }})",
+                            c_decl(lhs.ty(), &tmp),
+                            value,
+                            tmp,
+                            present,
+                            fallback
+                        )
+                    }
+                    BinaryOp::Add if is_string(lhs) || is_string(rhs) => {
+                        let lhs = self.string_expr(lhs);
+                        let rhs = self.string_expr(rhs);
+                        format!("vts_str_concat({}, {})", lhs, rhs)
+                    }
+                    BinaryOp::Eq | BinaryOp::Ne if is_string(lhs) => {
+                        let lhs = self.expr(lhs);
+                        let rhs = self.expr(rhs);
+                        let negate = if *op == BinaryOp::Ne { "!" } else { "" };
+                        format!("({}vts_str_eq({}, {}))", negate, lhs, rhs)
+                    }
+                    BinaryOp::Lt | BinaryOp::Le | BinaryOp::Gt | BinaryOp::Ge if is_string(lhs) => {
+                        let lhs = self.expr(lhs);
+                        let rhs = self.expr(rhs);
+                        format!("(vts_str_cmp({}, {}) {} 0)", lhs, rhs, op.as_str())
+                    }
+                    BinaryOp::Add
+                    | BinaryOp::Sub
+                    | BinaryOp::Mul
+                    | BinaryOp::Div
+                    | BinaryOp::Rem
+                        if expr.ty().is_integer() =>
+                    {
+                        let lhs = self.expr(lhs);
+                        let rhs = self.expr(rhs);
+                        self.integer_arith(*op, expr.ty(), lhs, rhs, expr.line)
+                    }
+                    _ => {
+                        let lhs = self.expr(lhs);
+                        let rhs = self.expr(rhs);
+                        format!("({} {} {})", lhs, op.as_str(), rhs)
+                    }
+                }
+            }
+            ExprKind::Field { expr: inner, name } => {
+                let value = self.expr(inner);
+                field_access(&value, inner.ty(), name)
+            }
+            ExprKind::NewCollection(_) => {
+                let (key, value) = match expr.ty() {
+                    TypeAnnotation::Map(key, value) => (key, format!("sizeof({})", c_type(value))),
+                    TypeAnnotation::Set(elem) => (elem, "0".to_string()),
+                    _ => unreachable!("checked collection"),
+                };
+                format!(
+                    "vts_map_new({}, sizeof({}), {})",
+                    key.is_string(),
+                    map_key_type(key),
+                    value
+                )
+            }
+            ExprKind::ArrayLit(elems) => {
+                let tmp = self.temp("arr");
+                let elem_ty = array_elem_type(expr.ty());
+                let mut code = format!(
+                    "({{ vts_array *{} = vts_array_new(sizeof({}), {}); ",
+                    tmp,
+                    elem_ty,
+                    elems.len()
+                );
+                for value in elems {
+                    let value = self.expr(value);
+                    code.push_str(&format!(
+                        "*({} *)vts_array_push({}) = {}; ",
+                        elem_ty, tmp, value
+                    ));
+                }
+                code.push_str(&format!("{}; }})", tmp));
+                code
+            }
+            ExprKind::Index { expr: inner, index } => {
+                let value = self.expr(inner);
+                let index = self.expr(index);
+                let location = self.location(expr.line);
+                match inner.ty() {
+                    TypeAnnotation::Array(_) => format!(
+                        "(*({} *)vts_array_at({}, {}, {}))",
+                        array_elem_type(inner.ty()),
+                        value,
+                        index,
+                        location
+                    ),
+                    _ => format!("vts_str_at({}, {}, {})", value, index, location),
+                }
+            }
+            ExprKind::OptionalField { expr: inner, name } => {
+                let tmp = self.temp("opt");
+                let value = self.expr(inner);
+                let TypeAnnotation::Nullable(value_ty) = inner.ty() else {
+                    unreachable!("checked nullable");
+                };
+                let field = field_access(&format!("{}.value", tmp), value_ty, name);
+                let field_ty = match value_ty.as_ref() {
+                    TypeAnnotation::String
+                    | TypeAnnotation::Array(_)
+                    | TypeAnnotation::Map(..)
+                    | TypeAnnotation::Set(_) => TypeAnnotation::Int,
+                    TypeAnnotation::Tuple(elems) => {
+                        elems[name.parse::<usize>().expect("checked position")].clone()
+                    }
+                    record => self
+                        .record_fields(record)
+                        .into_iter()
+                        .flatten()
+                        .find(|(field, _)| field == name)
+                        .map(|(_, ty)| ty)
+                        .expect("checked field"),
+                };
+                let field = self.convert(field, &field_ty, expr.ty());
+                format!(
+                    "({{ {} = {}; {}.has_value ? {} : ({}){{0}}; }})",
+                    c_decl(inner.ty(), &tmp),
+                    value,
+                    tmp,
+                    field,
+                    c_type(expr.ty())
+                )
+            }
+            ExprKind::OptionalCall { expr: inner, call } => {
+                let tmp = self.temp("opt");
+                let value = self.expr(inner);
+                let depth = self.variables.len();
+                self.bind(OPTIONAL_RECEIVER, format!("{}.value", tmp), false);
+                let result = self.expr(call);
+                self.variables.truncate(depth);
+                // a method without a result only runs when the value is present
+                let result = match expr.ty() {
+                    TypeAnnotation::Void => format!("if ({}.has_value) {};", tmp, result),
+                    ty => format!(
+                        "{}.has_value ? {} : ({}){{0}};",
+                        tmp,
+                        self.convert(result, call.ty(), ty),
+                        c_type(ty)
+                    ),
+                };
+                format!(
+                    "({{ {} = {}; {} }})",
+                    c_decl(inner.ty(), &tmp),
+                    value,
+                    result
+                )
+            }
+            // an object literal may have been typed as the expected struct, a
+            // generic struct literal has the instance type
+            ExprKind::StructLit { fields, .. } | ExprKind::ObjectLit(fields) => {
+                let ty = c_type(expr.ty());
+                if fields.is_empty() {
+                    return format!("(({}){{0}})", ty);
+                }
+                let fields: Vec<String> = fields
+                    .iter()
+                    .map(|(field, value)| format!(".{} = {}", c_ident(field), self.expr(value)))
+                    .collect();
+                format!("(({}){{ {} }})", ty, fields.join(", "))
+            }
+            ExprKind::TupleLit(elems) => {
+                let elems: Vec<String> = elems
+                    .iter()
+                    .enumerate()
+                    .map(|(index, value)| format!(".f{} = {}", index, self.expr(value)))
+                    .collect();
+                format!("(({}){{ {} }})", c_type(expr.ty()), elems.join(", "))
+            }
+            ExprKind::MethodCall {
+                receiver,
+                method,
+                args,
+            } if let TypeAnnotation::Named(owner) = receiver.ty() => {
+                // a method of a struct takes a pointer to the value it is called on
+                let name = function_name(&format!("{}.{}", owner, method), &[]);
+                let value = self.expr(receiver);
+                let mut args: Vec<String> = args.iter().map(|arg| self.expr(arg)).collect();
+                if is_place(receiver) {
+                    args.insert(0, format!("&({})", value));
+                    format!("{}({})", name, args.join(", "))
+                } else {
+                    let tmp = self.temp("self");
+                    args.insert(0, format!("&{}", tmp));
+                    format!(
+                        "({{ {} = {}; {}({}); }})",
+                        c_decl(receiver.ty(), &tmp),
+                        value,
+                        name,
+                        args.join(", ")
+                    )
+                }
+            }
+            ExprKind::MethodCall {
+                receiver,
+                method,
+                args,
+            } => {
+                let value = self.expr(receiver);
+                let callback_ty = args.first().map(|arg| arg.ty().clone());
+                let args: Vec<String> = args.iter().map(|arg| self.expr(arg)).collect();
+                match (method.as_str(), receiver.ty(), args.as_slice()) {
+                    ("slice", _, [start, end]) => {
+                        format!("vts_str_slice({}, {}, {})", value, start, end)
+                    }
+                    ("slice", _, [start]) => {
+                        let tmp = self.temp("s");
+                        format!(
+                            "({{ vts_string {0} = {1}; vts_str_slice({0}, {2}, (int){0}.len); }})",
+                            tmp, value, start
+                        )
+                    }
+                    ("push", TypeAnnotation::Array(_), [item]) => {
+                        let elem_ty = array_elem_type(receiver.ty());
+                        let tmp = self.temp("arr");
+                        let item_tmp = self.temp("item");
+                        format!(
+                            "({{ vts_array *{0} = {1}; {2} {3} = {4}; *({2} *)vts_array_push({0}) = {3}; {0}->len; }})",
+                            tmp, value, elem_ty, item_tmp, item
+                        )
+                    }
+                    ("pop", TypeAnnotation::Array(elem), _) => {
+                        let elem_ty = array_elem_type(receiver.ty());
+                        let tmp = self.temp("arr");
+                        let slot = self.temp("slot");
+                        let popped = format!("*({} *){}", elem_ty, slot);
+                        // `pop()` on a `T?[]` yields the stored `T?` itself
+                        let some = match elem.as_ref() {
+                            TypeAnnotation::Nullable(_) => popped,
+                            _ => format!(
+                                "({}){{ .has_value = true, .value = {} }}",
+                                c_type(expr.ty()),
+                                popped
+                            ),
+                        };
+                        format!(
+                            "({{ vts_array *{0} = {1}; void *{2} = vts_array_pop({0}); {2} ? {3} : ({4}){{0}}; }})",
+                            tmp,
+                            value,
+                            slot,
+                            some,
+                            c_type(expr.ty())
+                        )
+                    }
+                    ("keys", TypeAnnotation::Map(..), _)
+                    | ("values", TypeAnnotation::Set(_), _) => {
+                        format!("vts_map_entries({}, false)", value)
+                    }
+                    ("values", TypeAnnotation::Map(..), _) => {
+                        format!("vts_map_entries({}, true)", value)
+                    }
+                    (_, TypeAnnotation::Map(key, _) | TypeAnnotation::Set(key), _) => {
+                        // the key goes through a temporary so its address can be taken
+                        let map = self.temp("map");
+                        let key_tmp = self.temp("key");
+                        let mut code = format!(
+                            "({{ vts_map *{} = {}; {} = {}; ",
+                            map,
+                            value,
+                            c_decl(key, &key_tmp),
+                            args[0]
+                        );
+                        let result = match method.as_str() {
+                            "get" => {
+                                let slot = self.temp("slot");
+                                let TypeAnnotation::Map(_, value_ty) = receiver.ty() else {
+                                    unreachable!("checked map");
+                                };
+                                code.push_str(&format!(
+                                    "int {} = vts_map_find({}, &{}); ",
+                                    slot, map, key_tmp
+                                ));
+                                let found = format!(
+                                    "*({} *)({1}->values + {1}->value_size * (size_t){2})",
+                                    c_type(value_ty),
+                                    map,
+                                    slot
+                                );
+                                // `get` on a `Map<K, V?>` yields the stored `V?` itself
+                                let some = match value_ty.as_ref() {
+                                    TypeAnnotation::Nullable(_) => found,
+                                    _ => format!(
+                                        "({}){{ .has_value = true, .value = {} }}",
+                                        c_type(expr.ty()),
+                                        found
+                                    ),
+                                };
+                                format!("{} >= 0 ? {} : ({}){{0}}", slot, some, c_type(expr.ty()))
+                            }
+                            "set" => {
+                                let item = self.temp("item");
+                                let TypeAnnotation::Map(_, value_ty) = receiver.ty() else {
+                                    unreachable!("checked map");
+                                };
+                                code.push_str(&format!(
+                                    "{} = {}; vts_map_set({}, &{}, &{}); ",
+                                    c_decl(value_ty, &item),
+                                    args[1],
+                                    map,
+                                    key_tmp,
+                                    item
+                                ));
+                                map
+                            }
+                            "add" => {
+                                code.push_str(&format!(
+                                    "vts_map_set({}, &{}, NULL); ",
+                                    map, key_tmp
+                                ));
+                                map
+                            }
+                            "has" => format!("vts_map_find({}, &{}) >= 0", map, key_tmp),
+                            "delete" => format!("vts_map_delete({}, &{})", map, key_tmp),
+                            _ => unreachable!("the checker only allows the built-in methods"),
+                        };
+                        code.push_str(&format!("{}; }})", result));
+                        code
+                    }
+                    ("toString", ty, _) => to_string_call(&value, ty),
+                    ("map" | "filter" | "forEach", TypeAnnotation::Array(_), [callback]) => {
+                        let callback_ty = callback_ty.expect("checked callback");
+                        self.array_callback(
+                            method,
+                            expr.ty(),
+                            receiver.ty(),
+                            &value,
+                            callback,
+                            &callback_ty,
+                        )
+                    }
+                    _ => unreachable!("the checker only allows the built-in methods"),
+                }
+            }
+            ExprKind::Variant {
+                enum_name,
+                variant,
+                args,
+            } => {
+                let mut fields = vec![format!(".tag = {}", enum_tag(enum_name, variant))];
+                for (index, arg) in args.iter().enumerate() {
+                    let value = self.expr(arg);
+                    fields.push(format!(".as.{}.f{} = {}", variant, index, value));
+                }
+                format!("(({}){{ {} }})", c_type_name(enum_name), fields.join(", "))
+            }
+            ExprKind::Match { scrutinee, arms } => {
+                // statement expression: a switch assigning the arm's value to a temporary
+                let value = self.expr(scrutinee);
+                let subject = self.temp("m");
+                let result = self.temp("r");
+                let mut code = format!(
+                    "({{ {} = {}; {}; switch ({}) {{ ",
+                    c_decl(scrutinee.ty(), &subject),
+                    value,
+                    c_decl(expr.ty(), &result),
+                    self.match_selector(scrutinee.ty(), &subject)
+                );
+                for arm in arms {
+                    let (case, bindings) = self.match_case(scrutinee.ty(), &subject, &arm.pattern);
+                    let depth = self.variables.len();
+                    self.bind_pattern(&arm.pattern);
+                    let value = self.expr(&arm.body);
+                    self.variables.truncate(depth);
+                    code.push_str(&format!(
+                        "{} {{ {}{} = {}; break; }} ",
+                        case,
+                        bindings
+                            .iter()
+                            .map(|b| format!("{} ", b))
+                            .collect::<String>(),
+                        result,
+                        value
+                    ));
+                }
+                if !arms
+                    .iter()
+                    .any(|arm| matches!(arm.pattern, Pattern::Wildcard))
+                {
+                    code.push_str("default: abort(); ");
+                }
+                code.push_str(&format!("}} {}; }})", result));
+                code
+            }
+            ExprKind::Is { expr: inner, ty } => {
+                let value = self.expr(inner);
+                match (inner.ty(), ty) {
+                    (_, TypeAnnotation::Literal(text)) => {
+                        format!("vts_str_eq({}, {})", value, vts_string_literal(text))
+                    }
+                    (TypeAnnotation::Union(members), _) => {
+                        let index = members.iter().position(|m| m == ty).expect("member");
+                        format!("(({}).tag == {})", value, index)
+                    }
+                    _ => unreachable!("the checker only allows `is` on unions"),
+                }
+            }
+            ExprKind::ResultOk(value) => {
+                let value = match value {
+                    Some(value) => format!(", .value = {}", self.expr(value)),
+                    None => String::new(),
+                };
+                format!("(({}){{ .ok = true{} }})", c_type(expr.ty()), value)
+            }
+            ExprKind::ResultErr(error) => {
+                let error = self.expr(error);
+                format!(
+                    "(({}){{ .ok = false, .error = {} }})",
+                    c_type(expr.ty()),
+                    error
+                )
+            }
+            ExprKind::Try(inner) => {
+                let TypeAnnotation::Result(value_ty, error_ty) = inner.ty() else {
+                    unreachable!("checked result");
+                };
+                let tmp = self.temp("res");
+                let value = self.expr(inner);
+                let early_return = if self.is_main {
+                    format!(
+                        "fprintf(stderr, \"error: %s\\n\", {}.error.data); return 1;",
+                        tmp
+                    )
+                } else {
+                    let TypeAnnotation::Result(_, ret_error) = self.ret.clone() else {
+                        unreachable!("checked return type");
+                    };
+                    let error = self.convert(format!("{}.error", tmp), error_ty, &ret_error);
+                    format!(
+                        "return ({}){{ .ok = false, .error = {} }};",
+                        c_type(&self.ret),
+                        error
+                    )
+                };
+                let result = match **value_ty {
+                    TypeAnnotation::Void => String::new(),
+                    _ => format!(" {}.value;", tmp),
+                };
+                format!(
+                    "({{ {} = {}; if (!{}.ok) {{ {} }}{} }})",
+                    c_decl(inner.ty(), &tmp),
+                    value,
+                    tmp,
+                    early_return,
+                    result
+                )
+            }
+            ExprKind::Await(inner) => self.expr(inner),
+            ExprKind::TimeNow => "vts_time_now_ms()".to_string(),
+            ExprKind::FsReadFile { path } => {
+                let path = self.expr(path);
+                let path_var = self.temp("path");
+                let contents = self.temp("tmp");
+                let result = c_type(expr.ty());
+                format!(
+                    "({{ vts_string {0} = {1}; vts_string {2}; vts_fs_read_file({0}.data, &{2}) ? ({3}){{ .ok = true, .value = {2} }} : ({3}){{ .ok = false, .error = vts_str_concat(VTS_STR(\"fs.readFile failed: \"), {0}) }}; }})",
+                    path_var, path, contents, result
+                )
+            }
+            ExprKind::FsWriteFile { path, contents } => {
+                let path = self.expr(path);
+                let contents = self.expr(contents);
+                let path_var = self.temp("path");
+                let result = c_type(expr.ty());
+                format!(
+                    "({{ vts_string {0} = {1}; vts_fs_write_file({0}.data, {2}) == 0 ? ({3}){{ .ok = true }} : ({3}){{ .ok = false, .error = vts_str_concat(VTS_STR(\"fs.writeFile failed: \"), {0}) }}; }})",
+                    path_var, path, contents, result
+                )
+            }
+            ExprKind::Convert { expr: inner, to } => {
+                let value = self.expr(inner);
+                let from = inner.ty();
+                if from == to {
+                    value
+                } else if *to == TypeAnnotation::Float {
+                    format!("((double)({}))", value)
+                } else if *from == TypeAnnotation::Float {
+                    // truncates toward zero; NaN fails the range check too
+                    let v = self.temp("v");
+                    let panic = self.panic_call(expr.line, &format!("値が {} の範囲外です", to));
+                    format!(
+                        "({{ double {} = {}; if (!({})) {} ({}){}; }})",
+                        v,
+                        value,
+                        float_range_check(&v, to),
+                        panic,
+                        c_type(to),
+                        v
+                    )
+                } else {
+                    let panic = self.panic_call(expr.line, &format!("値が {} の範囲外です", to));
+                    self.overflow_check("add", to, &value, "0", &panic)
+                }
+            }
+            ExprKind::Coerce(inner) => {
+                let value = self.expr(inner);
+                self.convert(value, inner.ty(), expr.ty())
+            }
+        }
+    }
+
+    /// `map` / `filter` / `forEach` on the array `array`: calls `callback` on
+    /// each element. Elements pushed meanwhile are not visited, as in JavaScript.
+    fn array_callback(
+        &mut self,
+        method: &str,
+        result_ty: &TypeAnnotation,
+        array_ty: &TypeAnnotation,
+        array: &str,
+        callback: &str,
+        callback_ty: &TypeAnnotation,
+    ) -> String {
+        let elem_ty = array_elem_type(array_ty);
+        let arr = self.temp("arr");
+        let function = self.temp("fn");
+        let count = self.temp("n");
+        let k = self.temp("k");
+        let item = self.temp("item");
+        let out = self.temp("out");
+        let call = format!("{0}.call({0}.env, {1})", function, item);
+        let (init, step, result) = match method {
+            "map" => {
+                let mapped_ty = array_elem_type(result_ty);
+                let mapped = self.temp("r");
+                (
+                    format!(
+                        "vts_array *{} = vts_array_new(sizeof({}), {}); ",
+                        out, mapped_ty, count
+                    ),
+                    format!(
+                        "{0} {1} = {2}; *({0} *)vts_array_push({3}) = {1};",
+                        mapped_ty, mapped, call, out
+                    ),
+                    format!("{}; ", out),
+                )
+            }
+            "filter" => (
+                format!(
+                    "vts_array *{} = vts_array_new(sizeof({}), 0); ",
+                    out, elem_ty
+                ),
+                format!(
+                    "if ({}) *({} *)vts_array_push({}) = {};",
+                    call, elem_ty, out, item
+                ),
+                format!("{}; ", out),
+            ),
+            _ => (String::new(), format!("{};", call), String::new()),
+        };
+        format!(
+            "({{ vts_array *{arr} = {array}; {decl} = {callback}; int {n} = {arr}->len; {init}for (int {k} = 0; {k} < {n} && {k} < {arr}->len; {k}++) {{ {elem} {item} = (({elem} *){arr}->data)[{k}]; {step} }} {result}}})",
+            arr = arr,
+            array = array,
+            decl = c_decl(callback_ty, &function),
+            callback = callback,
+            n = count,
+            init = init,
+            k = k,
+            elem = elem_ty,
+            item = item,
+            step = step,
+            result = result
+        )
+    }
+
+    /// `({ T r; if (__builtin_add_overflow(a, b, &r)) panic; r; })`: `a op b`
+    /// computed exactly and checked to fit the integer type `ty`.
+    fn overflow_check(
+        &mut self,
+        op: &str,
+        ty: &TypeAnnotation,
+        lhs: &str,
+        rhs: &str,
+        panic: &str,
+    ) -> String {
+        let result = self.temp("r");
+        format!(
+            "({{ {} {}; if (__builtin_{}_overflow({}, {}, &{})) {} {}; }})",
+            c_type(ty),
+            result,
+            op,
+            lhs,
+            rhs,
+            result,
+            panic,
+            result
+        )
+    }
+
+    /// Integer arithmetic: a result outside the type panics, and so does a
+    /// division by zero.
+    fn integer_arith(
+        &mut self,
+        op: BinaryOp,
+        ty: &TypeAnnotation,
+        lhs: String,
+        rhs: String,
+        line: usize,
+    ) -> String {
+        let overflow = self.panic_call(
+            line,
+            &format!("{} の演算 {} がオーバーフローしました", ty, op.as_str()),
+        );
+        let builtin = match op {
+            BinaryOp::Add => "add",
+            BinaryOp::Sub => "sub",
+            BinaryOp::Mul => "mul",
+            _ => {
+                let (a, b) = (self.temp("a"), self.temp("b"));
+                let c_ty = c_type(ty);
+                // `MIN / -1` is the one quotient that does not fit
+                let min_check = match ty {
+                    TypeAnnotation::Int => {
+                        format!("if ({} == -1 && {} == INT32_MIN) {} ", b, a, overflow)
+                    }
+                    TypeAnnotation::I64 => {
+                        format!("if ({} == -1 && {} == INT64_MIN) {} ", b, a, overflow)
+                    }
+                    _ => String::new(),
+                };
+                return format!(
+                    "({{ {0} {1} = {2}; {0} {3} = {4}; if ({3} == 0) {5} {6}({0})({1} {7} {3}); }})",
+                    c_ty,
+                    a,
+                    lhs,
+                    b,
+                    rhs,
+                    self.panic_call(line, "0 で割ることはできません"),
+                    min_check,
+                    op.as_str()
+                );
+            }
+        };
+        self.overflow_check(builtin, ty, &lhs, &rhs, &overflow)
+    }
+
+    /// Converts the C value `value` of type `from` into the representation of `to`.
+    /// The fields of a struct or object type.
+    fn record_fields(&self, ty: &TypeAnnotation) -> Option<Vec<(String, TypeAnnotation)>> {
+        match ty {
+            TypeAnnotation::Named(_) | TypeAnnotation::Generic(..) => {
+                let name = mangle(ty);
+                self.structs
+                    .iter()
+                    .find(|s| struct_name(s) == name)
+                    .map(|s| {
+                        s.fields
+                            .iter()
+                            .map(|f| (f.name.clone(), f.ty.clone()))
+                            .collect()
+                    })
+            }
+            TypeAnnotation::Object(fields) => Some(fields.clone()),
+            _ => None,
+        }
+    }
+
+    fn convert(&mut self, value: String, from: &TypeAnnotation, to: &TypeAnnotation) -> String {
+        match (from, to) {
+            (TypeAnnotation::None, TypeAnnotation::Nullable(_)) => {
+                format!("(({}){{ .has_value = false }})", c_type(to))
+            }
+            (TypeAnnotation::Nullable(from_inner), TypeAnnotation::Nullable(to_inner))
+                if from != to =>
+            {
+                let tmp = self.temp("cv");
+                let inner = self.convert(format!("{}.value", tmp), from_inner, to_inner);
+                format!(
+                    "({{ {} = {}; {}.has_value ? ({}){{ .has_value = true, .value = {} }} : ({}){{0}}; }})",
+                    c_decl(from, &tmp),
+                    value,
+                    tmp,
+                    c_type(to),
+                    inner,
+                    c_type(to)
+                )
+            }
+            (TypeAnnotation::Nullable(_), TypeAnnotation::Nullable(_)) => value,
+            (_, TypeAnnotation::Nullable(inner)) => {
+                let value = self.convert(value, from, inner);
+                format!(
+                    "(({}){{ .has_value = true, .value = {} }})",
+                    c_type(to),
+                    value
+                )
+            }
+            (TypeAnnotation::Nullable(inner), _) => {
+                // a variable narrowed by a none check
+                self.convert(format!("({}).value", value), inner, to)
+            }
+            (
+                TypeAnnotation::Named(_) | TypeAnnotation::Object(_),
+                TypeAnnotation::Named(_) | TypeAnnotation::Object(_),
+            ) if from != to => {
+                // structurally compatible structs and objects: copy the fields
+                // the target has (an object may leave extra ones behind)
+                let source = self.record_fields(from).expect("record");
+                let target = self.record_fields(to).expect("record");
+                let tmp = self.temp("cv");
+                let mut fields = Vec::new();
+                for (name, field_ty) in &target {
+                    let (_, from_ty) = source
+                        .iter()
+                        .find(|(other, _)| other == name)
+                        .expect("checked field");
+                    let access = format!("{}.{}", tmp, c_ident(name));
+                    let value = self.convert(access, from_ty, field_ty);
+                    fields.push(format!(".{} = {}", c_ident(name), value));
+                }
+                if fields.is_empty() {
+                    return format!("(({}){{0}})", c_type(to));
+                }
+                format!(
+                    "({{ {} = {}; ({}){{ {} }}; }})",
+                    c_decl(from, &tmp),
+                    value,
+                    c_type(to),
+                    fields.join(", ")
+                )
+            }
+            (TypeAnnotation::Tuple(from_elems), TypeAnnotation::Tuple(to_elems)) if from != to => {
+                // tuples with compatible elements: convert them one by one
+                let tmp = self.temp("cv");
+                let mut elems = Vec::new();
+                for (index, (from_ty, to_ty)) in from_elems.iter().zip(to_elems).enumerate() {
+                    let access = format!("{}.f{}", tmp, index);
+                    let value = self.convert(access, from_ty, to_ty);
+                    elems.push(format!(".f{} = {}", index, value));
+                }
+                format!(
+                    "({{ {} = {}; ({}){{ {} }}; }})",
+                    c_decl(from, &tmp),
+                    value,
+                    c_type(to),
+                    elems.join(", ")
+                )
+            }
+            (TypeAnnotation::Union(from_members), TypeAnnotation::Union(_))
+                if is_tagged(from) && is_tagged(to) =>
+            {
+                // re-tag every member the target also has; others cannot occur
+                let tmp = self.temp("cv");
+                let result = self.temp("cv");
+                let mut cases = String::new();
+                for (index, member) in from_members.iter().enumerate() {
+                    if !matches!(to, TypeAnnotation::Union(to_members) if to_members.contains(member))
+                    {
+                        continue;
+                    }
+                    let access = format!("{}.as.m{}", tmp, index);
+                    let value = self.convert(access, member, to);
+                    cases.push_str(&format!("case {}: {} = {}; break; ", index, result, value));
+                }
+                format!(
+                    "({{ {} = {}; {}; switch ({}.tag) {{ {}default: abort(); }} {}; }})",
+                    c_decl(from, &tmp),
+                    value,
+                    c_decl(to, &result),
+                    tmp,
+                    cases,
+                    result
+                )
+            }
+            (_, TypeAnnotation::Union(members)) if is_tagged(to) => {
+                let index = members.iter().position(|m| m == from).expect("member");
+                format!(
+                    "(({}){{ .tag = {}, .as.m{} = {} }})",
+                    c_type(to),
+                    index,
+                    index,
+                    value
+                )
+            }
+            (TypeAnnotation::Union(members), _) if is_tagged(from) => {
+                let index = members.iter().position(|m| m == to).expect("member");
+                format!("({}).as.m{}", value, index)
+            }
+            _ => value,
+        }
+    }
+
+    /// Emits `expr` converted to a C string (used by `+` concatenation).
+    fn string_expr(&mut self, expr: &Expr) -> String {
+        let value = self.expr(expr);
+        match expr.ty() {
+            ty if ty.is_numeric() || *ty == TypeAnnotation::Bool => to_string_call(&value, ty),
+            _ => value,
+        }
+    }
+}
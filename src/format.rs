@@ -0,0 +1,602 @@
+// --- Formatter: AST -> canonical VoltTS source ---
+use crate::ast::{
+    Block, Closure, ClosureBody, Expr, ExprKind, Function, LogLevel, MatchArm, Pattern, Program,
+    Range, Receiver, Stmt, TemplatePart, TypeAnnotation, UnaryOp, quote_str,
+};
+use crate::parser::IS_PRECEDENCE;
+
+pub fn format_program(program: &Program) -> String {
+    let mut out = String::new();
+    for import in &program.imports {
+        out.push_str(&format!(
+            "import {{ {} }} from \"{}\"\n",
+            import.names.join(", "),
+            import.module
+        ));
+    }
+
+    if !program.imports.is_empty() {
+        out.push('\n');
+    }
+
+    for alias in &program.aliases {
+        out.push_str(&format!("type {} = {}\n", alias.name, alias.ty));
+    }
+
+    for decl in &program.structs {
+        let name = format!("{}{}", decl.name, format_type_params(&decl.type_params));
+        let fields = decl
+            .fields
+            .iter()
+            .map(|field| format!("{}: {}", field.name, field.ty))
+            .collect::<Vec<_>>()
+            .join(", ");
+        if fields.is_empty() {
+            out.push_str(&format!("struct {} {{}}\n", name));
+        } else {
+            out.push_str(&format!("struct {} {{ {} }}\n", name, fields));
+        }
+    }
+
+    for decl in &program.enums {
+        let variants = decl
+            .variants
+            .iter()
+            .map(|variant| {
+                if variant.fields.is_empty() {
+                    variant.name.clone()
+                } else {
+                    let fields = variant
+                        .fields
+                        .iter()
+                        .map(|ty| ty.to_string())
+                        .collect::<Vec<_>>()
+                        .join(", ");
+                    format!("{}({})", variant.name, fields)
+                }
+            })
+            .collect::<Vec<_>>()
+            .join(", ");
+        out.push_str(&format!("enum {} {{ {} }}\n", decl.name, variants));
+    }
+
+    for decl in &program.interfaces {
+        let methods = decl
+            .methods
+            .iter()
+            .map(|method| {
+                let params = method
+                    .params
+                    .iter()
+                    .map(|param| format!("{}: {}", param.written_name(), param.ty))
+                    .collect::<Vec<_>>()
+                    .join(", ");
+                match &method.return_type {
+                    Some(rt) => format!("{}({}): {}", method.name, params, rt),
+                    None => format!("{}({})", method.name, params),
+                }
+            })
+            .collect::<Vec<_>>()
+            .join(", ");
+        if methods.is_empty() {
+            out.push_str(&format!("interface {} {{}}\n", decl.name));
+        } else {
+            out.push_str(&format!("interface {} {{ {} }}\n", decl.name, methods));
+        }
+    }
+
+    // the methods of a struct are gathered into one `impl` block, placed
+    // where its first method is
+    let mut owners: Vec<&str> = Vec::new();
+    for func in &program.functions {
+        let Some(owner) = &func.owner else {
+            format_function(&mut out, func, "");
+            continue;
+        };
+        if owners.contains(&owner.as_str()) {
+            continue;
+        }
+        owners.push(owner);
+        out.push_str(&format!("impl {} {{\n", owner));
+        for method in &program.functions {
+            if method.owner.as_ref() == Some(owner) {
+                format_function(&mut out, method, "    ");
+            }
+        }
+        out.push_str("}\n");
+    }
+    out
+}
+
+fn format_function(out: &mut String, func: &Function, indent: &str) {
+    let async_prefix = if func.is_async { "async " } else { "" };
+    let name = format!("{}{}", func.name, format_type_params(&func.type_params));
+    let receiver = match func.receiver {
+        Some(Receiver::Shared) => Some("self".to_string()),
+        Some(Receiver::Mut) => Some("mut self".to_string()),
+        None => None,
+    };
+    let params = receiver
+        .into_iter()
+        .chain(
+            func.params
+                .iter()
+                .map(|param| format!("{}: {}", param.written_name(), param.ty)),
+        )
+        .collect::<Vec<_>>()
+        .join(", ");
+    match &func.return_type {
+        Some(rt) => out.push_str(&format!(
+            "{indent}{async_prefix}fn {}({}): {} {{\n",
+            name, params, rt
+        )),
+        None => out.push_str(&format!(
+            "{indent}{async_prefix}fn {}({}) {{\n",
+            name, params
+        )),
+    }
+    for stmt in &func.body {
+        out.push_str(&format!("{indent}    {}\n", format_stmt(&stmt.value)));
+    }
+    out.push_str(&format!("{indent}}}\n"));
+}
+
+/// `<T, U>` of a generic declaration; empty otherwise.
+fn format_type_params(params: &[String]) -> String {
+    if params.is_empty() {
+        String::new()
+    } else {
+        format!("<{}>", params.join(", "))
+    }
+}
+
+fn format_block_inline(stmts: &Block) -> String {
+    stmts
+        .iter()
+        .map(|s| format_stmt(&s.value))
+        .collect::<Vec<_>>()
+        .join("; ")
+}
+
+/// Renders a statement on a single line; nested blocks are inlined with `; `.
+fn format_stmt(stmt: &Stmt) -> String {
+    match stmt {
+        Stmt::Print(expr) => format!("print({})", format_expr(expr)),
+        Stmt::Log { level, message } => {
+            let level = match level {
+                LogLevel::Info => "info",
+                LogLevel::Warn => "warn",
+                LogLevel::Error => "error",
+            };
+            format!("log.{}({})", level, format_expr(message))
+        }
+        Stmt::SleepMs(ms) => format!("time.sleep({})", format_expr(ms)),
+        Stmt::Expr(expr) => format_expr(expr),
+        Stmt::Let {
+            name,
+            mutable,
+            ty,
+            value,
+            ..
+        } => {
+            let keyword = if *mutable { "let" } else { "const" };
+            match ty {
+                Some(ty) => format!("{} {}: {} = {}", keyword, name, ty, format_expr(value)),
+                None => format!("{} {} = {}", keyword, name, format_expr(value)),
+            }
+        }
+        Stmt::Destructure {
+            pattern,
+            mutable,
+            ty,
+            value,
+        } => {
+            let keyword = if *mutable { "let" } else { "const" };
+            match ty {
+                Some(ty) => format!("{} {}: {} = {}", keyword, pattern, ty, format_expr(value)),
+                None => format!("{} {} = {}", keyword, pattern, format_expr(value)),
+            }
+        }
+        Stmt::Assign { target, op, value } => {
+            let op = op.map(|op| op.as_str()).unwrap_or("");
+            format!("{} {}= {}", format_expr(target), op, format_expr(value))
+        }
+        Stmt::Return(Some(expr)) => format!("return {}", format_expr(expr)),
+        Stmt::Return(None) => "return".to_string(),
+        Stmt::If {
+            condition,
+            then_branch,
+            else_branch,
+        } => {
+            let mut rendered = format!(
+                "if {} {{ {} }}",
+                format_head_expr(condition),
+                format_block_inline(then_branch)
+            );
+            match else_branch.as_slice() {
+                [] => {}
+                [single] if matches!(single.value, Stmt::If { .. }) => {
+                    rendered.push_str(" else ");
+                    rendered.push_str(&format_stmt(&single.value));
+                }
+                _ => {
+                    rendered.push_str(" else { ");
+                    rendered.push_str(&format_block_inline(else_branch));
+                    rendered.push_str(" }");
+                }
+            }
+            rendered
+        }
+        Stmt::While {
+            label,
+            condition,
+            body,
+        } => format!(
+            "{}while {} {{ {} }}",
+            format_label(label),
+            format_head_expr(condition),
+            format_block_inline(body)
+        ),
+        Stmt::ForRange {
+            label,
+            var,
+            range,
+            body,
+        } => format!(
+            "{}for {} in {} {{ {} }}",
+            format_label(label),
+            var,
+            format_range(range),
+            format_block_inline(body)
+        ),
+        Stmt::ForOf {
+            label,
+            var,
+            iterable,
+            body,
+        } => format!(
+            "{}for {} of {} {{ {} }}",
+            format_label(label),
+            var,
+            format_head_expr(iterable),
+            format_block_inline(body)
+        ),
+        Stmt::Match { scrutinee, arms } => {
+            format_match(scrutinee, arms, |body| match body.as_slice() {
+                [single] => format_stmt(&single.value),
+                [] => "{}".to_string(),
+                _ => format!("{{ {} }}", format_block_inline(body)),
+            })
+        }
+        Stmt::Break(label) => format_jump("break", label),
+        Stmt::Continue(label) => format_jump("continue", label),
+        Stmt::Await(inner) => format!("await {}", format_stmt(inner)),
+    }
+}
+
+/// `x => x + 1`, `(a: int, b): int => { .. }`
+fn format_closure(closure: &Closure) -> String {
+    let mut rendered = match (closure.params.as_slice(), &closure.return_type) {
+        ([param], None) if param.ty == TypeAnnotation::None && param.pattern.is_none() => {
+            param.name.clone()
+        }
+        (params, return_type) => {
+            let params = params
+                .iter()
+                .map(|param| match param.ty {
+                    TypeAnnotation::None => param.written_name(),
+                    ref ty => format!("{}: {}", param.written_name(), ty),
+                })
+                .collect::<Vec<_>>()
+                .join(", ");
+            match return_type {
+                Some(ty) => format!("({}): {}", params, ty),
+                None => format!("({})", params),
+            }
+        }
+    };
+    rendered.push_str(" => ");
+    match &closure.body {
+        // `=> {` would start a block
+        ClosureBody::Expr(body) if matches!(body.kind, ExprKind::ObjectLit(_)) => {
+            rendered.push_str(&format!("({})", format_expr(body)));
+        }
+        ClosureBody::Expr(body) => rendered.push_str(&format_expr(body)),
+        ClosureBody::Block(body) if body.is_empty() => rendered.push_str("{}"),
+        ClosureBody::Block(body) => {
+            rendered.push_str(&format!("{{ {} }}", format_block_inline(body)));
+        }
+    }
+    rendered
+}
+
+fn format_range(range: &Range) -> String {
+    let op = if range.inclusive { "..=" } else { ".." };
+    if range.step.is_none() && !range.reverse {
+        return format!(
+            "{}{}{}",
+            format_head_expr(&range.start),
+            op,
+            format_head_expr(&range.end)
+        );
+    }
+    let bounds = format!(
+        "{}{}{}",
+        format_expr(&range.start),
+        op,
+        format_expr(&range.end)
+    );
+    let mut rendered = format!("({})", bounds);
+    if let Some(step) = &range.step {
+        rendered.push_str(&format!(".step({})", format_expr(step)));
+    }
+    if range.reverse {
+        rendered.push_str(".rev()");
+    }
+    rendered
+}
+
+fn format_match<T>(scrutinee: &Expr, arms: &[MatchArm<T>], body: impl Fn(&T) -> String) -> String {
+    let arms = arms
+        .iter()
+        .map(|arm| format!("{} => {}", format_pattern(&arm.pattern), body(&arm.body)))
+        .collect::<Vec<_>>()
+        .join(", ");
+    format!("match {} {{ {} }}", format_head_expr(scrutinee), arms)
+}
+
+fn format_pattern(pattern: &Pattern) -> String {
+    match pattern {
+        Pattern::Wildcard => "_".to_string(),
+        Pattern::Literal(text) => quote_str(text),
+        Pattern::Variant {
+            enum_name,
+            name,
+            bindings,
+        } => {
+            let mut rendered = match enum_name {
+                Some(enum_name) => format!("{}.{}", enum_name, name),
+                None => name.clone(),
+            };
+            if !bindings.is_empty() {
+                rendered.push_str(&format!("({})", bindings.join(", ")));
+            }
+            rendered
+        }
+    }
+}
+
+fn format_label(label: &Option<String>) -> String {
+    label
+        .as_ref()
+        .map(|label| format!("{}: ", label))
+        .unwrap_or_default()
+}
+
+fn format_jump(keyword: &str, label: &Option<String>) -> String {
+    match label {
+        Some(label) => format!("{} {}", keyword, label),
+        None => keyword.to_string(),
+    }
+}
+
+/// Renders the expression in front of a block (`if x {`). A struct literal
+/// there would be read as the block itself, so such expressions get parentheses.
+fn format_head_expr(expr: &Expr) -> String {
+    fn has_bare_struct_literal(expr: &Expr) -> bool {
+        match &expr.kind {
+            ExprKind::StructLit { .. } | ExprKind::ObjectLit(_) => true,
+            ExprKind::Unary { expr, .. }
+            | ExprKind::Field { expr, .. }
+            | ExprKind::OptionalField { expr, .. }
+            | ExprKind::OptionalCall { expr, .. }
+            | ExprKind::Try(expr)
+            | ExprKind::Await(expr)
+            | ExprKind::Index { expr, .. }
+            | ExprKind::MethodCall { receiver: expr, .. }
+            | ExprKind::Is { expr, .. }
+            | ExprKind::Coerce(expr) => has_bare_struct_literal(expr),
+            ExprKind::Binary { lhs, rhs, .. } => {
+                has_bare_struct_literal(lhs) || has_bare_struct_literal(rhs)
+            }
+            _ => false,
+        }
+    }
+
+    if has_bare_struct_literal(expr) {
+        format!("({})", format_expr(expr))
+    } else {
+        format_expr(expr)
+    }
+}
+
+/// Precedence of the expression at the top of `expr` (primary expressions bind tightest).
+fn expr_precedence(expr: &Expr) -> u8 {
+    match &expr.kind {
+        ExprKind::Binary { op, .. } => op.precedence(),
+        ExprKind::Is { .. } => IS_PRECEDENCE,
+        ExprKind::Unary { .. } | ExprKind::Await(_) => 8,
+        ExprKind::Closure(_) => 0,
+        _ => 9,
+    }
+}
+
+fn format_operand(expr: &Expr, min_prec: u8) -> String {
+    let rendered = format_expr(expr);
+    if expr_precedence(expr) < min_prec {
+        format!("({})", rendered)
+    } else {
+        rendered
+    }
+}
+
+/// Template text as source: like `quote_str`, but newlines stay as they are
+/// and `` ` `` / `${` are escaped instead of `"`.
+fn escape_template_text(text: &str) -> String {
+    let mut out = String::new();
+    let mut chars = text.chars().peekable();
+    while let Some(c) = chars.next() {
+        match c {
+            '`' => out.push_str("\\`"),
+            '$' if chars.peek() == Some(&'{') => out.push_str("\\$"),
+            '"' | '\n' => out.push(c),
+            c => {
+                let quoted = quote_str(&c.to_string());
+                out.push_str(&quoted[1..quoted.len() - 1]);
+            }
+        }
+    }
+    out
+}
+
+fn format_expr(expr: &Expr) -> String {
+    match &expr.kind {
+        ExprKind::Int(v) => v.to_string(),
+        // `{:?}` keeps the decimal point (`2.0`) and round-trips exactly
+        ExprKind::Float(v) => format!("{:?}", v),
+        ExprKind::Str(text) => quote_str(text),
+        ExprKind::Template(parts) => {
+            let body: String = parts
+                .iter()
+                .map(|part| match part {
+                    TemplatePart::Text(text) => escape_template_text(text),
+                    TemplatePart::Expr(value) => format!("${{{}}}", format_expr(value)),
+                })
+                .collect();
+            format!("`{}`", body)
+        }
+        ExprKind::Bool(v) => v.to_string(),
+        ExprKind::None => "none".to_string(),
+        ExprKind::Ident(name) | ExprKind::FnRef(name) => name.clone(),
+        ExprKind::CallValue { callee, args } => format!(
+            "{}({})",
+            format_operand(callee, 9),
+            args.iter().map(format_expr).collect::<Vec<_>>().join(", ")
+        ),
+        ExprKind::Closure(closure) => format_closure(closure),
+        ExprKind::Call { callee, args, .. } => format!(
+            "{}({})",
+            callee,
+            args.iter().map(format_expr).collect::<Vec<_>>().join(", ")
+        ),
+        ExprKind::Unary { op, expr: inner } => match (op, &inner.kind) {
+            // `-(-a)` must not read as a `--` decrement
+            (
+                UnaryOp::Neg,
+                ExprKind::Unary {
+                    op: UnaryOp::Neg, ..
+                },
+            ) => format!("-({})", format_expr(inner)),
+            _ => format!("{}{}", op.as_str(), format_operand(inner, 8)),
+        },
+        ExprKind::Binary { op, lhs, rhs } => {
+            // operators are left-associative: the right operand needs parens at equal precedence
+            let prec = op.precedence();
+            format!(
+                "{} {} {}",
+                format_operand(lhs, prec),
+                op.as_str(),
+                format_operand(rhs, prec + 1)
+            )
+        }
+        ExprKind::Field { expr: inner, name } => format!("{}.{}", format_operand(inner, 9), name),
+        ExprKind::OptionalField { expr: inner, name } => {
+            format!("{}?.{}", format_operand(inner, 9), name)
+        }
+        ExprKind::OptionalCall { expr: inner, call } => match &call.kind {
+            ExprKind::MethodCall { method, args, .. } => format!(
+                "{}?.{}({})",
+                format_operand(inner, 9),
+                method,
+                args.iter().map(format_expr).collect::<Vec<_>>().join(", ")
+            ),
+            _ => unreachable!("parsed as a method call"),
+        },
+        ExprKind::Try(inner) => format!("{}?", format_operand(inner, 9)),
+        ExprKind::NewCollection(ty) => match ty {
+            TypeAnnotation::Map(key, _) | TypeAnnotation::Set(key)
+                if **key == TypeAnnotation::None =>
+            {
+                let name = if matches!(ty, TypeAnnotation::Map(..)) {
+                    "Map"
+                } else {
+                    "Set"
+                };
+                format!("new {}()", name)
+            }
+            _ => format!("new {}()", ty),
+        },
+        ExprKind::ArrayLit(elems) => format!(
+            "[{}]",
+            elems.iter().map(format_expr).collect::<Vec<_>>().join(", ")
+        ),
+        ExprKind::TupleLit(elems) => format!(
+            "({})",
+            elems.iter().map(format_expr).collect::<Vec<_>>().join(", ")
+        ),
+        ExprKind::Index { expr: inner, index } => {
+            format!("{}[{}]", format_operand(inner, 9), format_expr(index))
+        }
+        ExprKind::Await(inner) => format!("await {}", format_operand(inner, 8)),
+        ExprKind::TimeNow => "time.now()".to_string(),
+        ExprKind::FsReadFile { path } => format!("fs.readFile({})", format_expr(path)),
+        ExprKind::FsWriteFile { path, contents } => format!(
+            "fs.writeFile({}, {})",
+            format_expr(path),
+            format_expr(contents)
+        ),
+        ExprKind::ObjectLit(fields) if fields.is_empty() => "{}".to_string(),
+        ExprKind::ObjectLit(fields) => format!(
+            "{{ {} }}",
+            fields
+                .iter()
+                .map(|(field, value)| format!("{}: {}", field, format_expr(value)))
+                .collect::<Vec<_>>()
+                .join(", ")
+        ),
+        ExprKind::StructLit { name, fields } if fields.is_empty() => format!("{} {{}}", name),
+        ExprKind::StructLit { name, fields } => format!(
+            "{} {{ {} }}",
+            name,
+            fields
+                .iter()
+                .map(|(field, value)| format!("{}: {}", field, format_expr(value)))
+                .collect::<Vec<_>>()
+                .join(", ")
+        ),
+        ExprKind::MethodCall {
+            receiver,
+            method,
+            args,
+        } => format!(
+            "{}.{}({})",
+            format_operand(receiver, 9),
+            method,
+            args.iter().map(format_expr).collect::<Vec<_>>().join(", ")
+        ),
+        ExprKind::Variant {
+            enum_name,
+            variant,
+            args,
+        } if args.is_empty() => format!("{}.{}", enum_name, variant),
+        ExprKind::Variant {
+            enum_name,
+            variant,
+            args,
+        } => format!(
+            "{}.{}({})",
+            enum_name,
+            variant,
+            args.iter().map(format_expr).collect::<Vec<_>>().join(", ")
+        ),
+        ExprKind::Match { scrutinee, arms } => format_match(scrutinee, arms, format_expr),
+        ExprKind::Is { expr: inner, ty } => {
+            format!("{} is {}", format_operand(inner, IS_PRECEDENCE + 1), ty)
+        }
+        ExprKind::ResultOk(None) => "ok()".to_string(),
+        ExprKind::ResultOk(Some(value)) => format!("ok({})", format_expr(value)),
+        ExprKind::ResultErr(error) => format!("err({})", format_expr(error)),
+        ExprKind::Convert { expr: inner, to } => format!("{}({})", to, format_expr(inner)),
+        ExprKind::Coerce(inner) => format_expr(inner),
+    }
+}
@@ -0,0 +1,540 @@
+// --- Lexer: source text -> token stream ---
+use std::fmt;
+
+use anyhow::{Result, anyhow};
+
+use crate::ast::{Spanned, quote_str};
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum TemplateToken {
+    /// Literal text between interpolations (escapes decoded).
+    Text(String),
+    /// Tokens of one `${...}` expression, ending with `Token::Eof`.
+    Expr(Vec<Spanned<Token>>),
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum Token {
+    Ident(String),
+    Int(u64),
+    Float(f64),
+    /// String literal value without the surrounding quotes (escapes decoded).
+    Str(String),
+    /// Template literal (`` `a ${b}` ``) split into text and interpolated parts.
+    Template(Vec<TemplateToken>),
+    // keywords
+    Import,
+    From,
+    Export,
+    Async,
+    Fn,
+    Struct,
+    Enum,
+    Match,
+    Let,
+    Const,
+    Await,
+    Return,
+    If,
+    Else,
+    While,
+    For,
+    In,
+    Break,
+    Continue,
+    True,
+    False,
+    None,
+    // punctuation
+    LParen,
+    RParen,
+    LBracket,
+    RBracket,
+    LBrace,
+    RBrace,
+    Comma,
+    Colon,
+    Semicolon,
+    Dot,
+    QuestionDot,
+    DotDot,
+    DotDotEq,
+    // operators
+    FatArrow,
+    Eq,
+    PlusEq,
+    MinusEq,
+    StarEq,
+    SlashEq,
+    PercentEq,
+    Plus,
+    Minus,
+    Star,
+    Slash,
+    Percent,
+    EqEq,
+    BangEq,
+    Lt,
+    Le,
+    Gt,
+    Ge,
+    AndAnd,
+    OrOr,
+    QuestionQuestion,
+    Question,
+    Pipe,
+    Bang,
+    Eof,
+}
+
+impl fmt::Display for Token {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Token::Ident(name) => write!(f, "{}", name),
+            Token::Int(v) => write!(f, "{}", v),
+            Token::Float(v) => write!(f, "{:?}", v),
+            Token::Str(text) => f.write_str(&quote_str(text)),
+            Token::Template(parts) => {
+                f.write_str("`")?;
+                for part in parts {
+                    match part {
+                        TemplateToken::Text(text) => f.write_str(text)?,
+                        TemplateToken::Expr(_) => f.write_str("${...}")?,
+                    }
+                }
+                f.write_str("`")
+            }
+            Token::Import => f.write_str("import"),
+            Token::From => f.write_str("from"),
+            Token::Export => f.write_str("export"),
+            Token::Async => f.write_str("async"),
+            Token::Fn => f.write_str("fn"),
+            Token::Struct => f.write_str("struct"),
+            Token::Enum => f.write_str("enum"),
+            Token::Match => f.write_str("match"),
+            Token::Let => f.write_str("let"),
+            Token::Const => f.write_str("const"),
+            Token::Await => f.write_str("await"),
+            Token::Return => f.write_str("return"),
+            Token::If => f.write_str("if"),
+            Token::Else => f.write_str("else"),
+            Token::While => f.write_str("while"),
+            Token::For => f.write_str("for"),
+            Token::In => f.write_str("in"),
+            Token::Break => f.write_str("break"),
+            Token::Continue => f.write_str("continue"),
+            Token::True => f.write_str("true"),
+            Token::False => f.write_str("false"),
+            Token::None => f.write_str("none"),
+            Token::LParen => f.write_str("("),
+            Token::RParen => f.write_str(")"),
+            Token::LBracket => f.write_str("["),
+            Token::RBracket => f.write_str("]"),
+            Token::LBrace => f.write_str("{"),
+            Token::RBrace => f.write_str("}"),
+            Token::Comma => f.write_str(","),
+            Token::Colon => f.write_str(":"),
+            Token::Semicolon => f.write_str(";"),
+            Token::Dot => f.write_str("."),
+            Token::QuestionDot => f.write_str("?."),
+            Token::DotDot => f.write_str(".."),
+            Token::DotDotEq => f.write_str("..="),
+            Token::FatArrow => f.write_str("=>"),
+            Token::Eq => f.write_str("="),
+            Token::PlusEq => f.write_str("+="),
+            Token::MinusEq => f.write_str("-="),
+            Token::StarEq => f.write_str("*="),
+            Token::SlashEq => f.write_str("/="),
+            Token::PercentEq => f.write_str("%="),
+            Token::Plus => f.write_str("+"),
+            Token::Minus => f.write_str("-"),
+            Token::Star => f.write_str("*"),
+            Token::Slash => f.write_str("/"),
+            Token::Percent => f.write_str("%"),
+            Token::EqEq => f.write_str("=="),
+            Token::BangEq => f.write_str("!="),
+            Token::Lt => f.write_str("<"),
+            Token::Le => f.write_str("<="),
+            Token::Gt => f.write_str(">"),
+            Token::Ge => f.write_str(">="),
+            Token::AndAnd => f.write_str("&&"),
+            Token::OrOr => f.write_str("||"),
+            Token::QuestionQuestion => f.write_str("??"),
+            Token::Question => f.write_str("?"),
+            Token::Pipe => f.write_str("|"),
+            Token::Bang => f.write_str("!"),
+            Token::Eof => f.write_str("end of file"),
+        }
+    }
+}
+
+fn keyword(ident: &str) -> Option<Token> {
+    let token = match ident {
+        "import" => Token::Import,
+        "from" => Token::From,
+        "export" => Token::Export,
+        "async" => Token::Async,
+        "fn" => Token::Fn,
+        "struct" => Token::Struct,
+        "enum" => Token::Enum,
+        "match" => Token::Match,
+        "let" => Token::Let,
+        "const" => Token::Const,
+        "await" => Token::Await,
+        "return" => Token::Return,
+        "if" => Token::If,
+        "else" => Token::Else,
+        "while" => Token::While,
+        "for" => Token::For,
+        "in" => Token::In,
+        "break" => Token::Break,
+        "continue" => Token::Continue,
+        "true" => Token::True,
+        "false" => Token::False,
+        "none" => Token::None,
+        _ => return None,
+    };
+    Some(token)
+}
+
+/// Splits VoltTS source into tokens, dropping whitespace and comments.
+/// The returned stream always ends with `Token::Eof`.
+pub fn tokenize(source: &str) -> Result<Vec<Spanned<Token>>> {
+    let chars: Vec<char> = source.chars().collect();
+    let mut tokens = Vec::new();
+    let mut line = 1;
+    let mut i = 0;
+
+    while i < chars.len() {
+        let c = chars[i];
+
+        if c == '\n' {
+            line += 1;
+            i += 1;
+            continue;
+        }
+        if c.is_whitespace() {
+            i += 1;
+            continue;
+        }
+
+        if c == '/' && chars.get(i + 1) == Some(&'/') {
+            while i < chars.len() && chars[i] != '\n' {
+                i += 1;
+            }
+            continue;
+        }
+        if c == '/' && chars.get(i + 1) == Some(&'*') {
+            let start_line = line;
+            i += 2;
+            loop {
+                match chars.get(i) {
+                    None => {
+                        return Err(anyhow!(
+                            "行 {}: ブロックコメントが閉じられていません",
+                            start_line
+                        ));
+                    }
+                    Some('*') if chars.get(i + 1) == Some(&'/') => {
+                        i += 2;
+                        break;
+                    }
+                    Some('\n') => line += 1,
+                    Some(_) => {}
+                }
+                i += 1;
+            }
+            continue;
+        }
+
+        let token_line = line;
+
+        if c == '"' {
+            let mut text = String::new();
+            i += 1;
+            loop {
+                match chars.get(i) {
+                    None | Some('\n') => {
+                        return Err(anyhow!(
+                            "行 {}: 文字列リテラルが閉じられていません",
+                            token_line
+                        ));
+                    }
+                    Some('"') => {
+                        i += 1;
+                        break;
+                    }
+                    Some('\\') => text.push(lex_escape(&chars, &mut i, token_line)?),
+                    Some(&other) => {
+                        text.push(other);
+                        i += 1;
+                    }
+                }
+            }
+            tokens.push(Spanned {
+                value: Token::Str(text),
+                line: token_line,
+            });
+            continue;
+        }
+
+        if c == '`' {
+            let parts = lex_template(&chars, &mut i, &mut line)?;
+            tokens.push(Spanned {
+                value: Token::Template(parts),
+                line: token_line,
+            });
+            continue;
+        }
+
+        if c.is_ascii_digit() {
+            let start = i;
+            while i < chars.len() && (chars[i].is_ascii_digit() || chars[i] == '_') {
+                i += 1;
+            }
+            // `1.5`, `1e9`, `2.5e-3`; `1..5` and `1.toString()` stay integers,
+            // and so does a tuple position (`t.0.1` is two accesses)
+            let position = tokens
+                .last()
+                .is_some_and(|t| matches!(t.value, Token::Dot | Token::QuestionDot));
+            let mut is_float = false;
+            if !position
+                && chars.get(i) == Some(&'.')
+                && chars.get(i + 1).is_some_and(|c| c.is_ascii_digit())
+            {
+                is_float = true;
+                i += 1;
+                while i < chars.len() && (chars[i].is_ascii_digit() || chars[i] == '_') {
+                    i += 1;
+                }
+            }
+            if !position && matches!(chars.get(i), Some('e' | 'E')) {
+                let sign = usize::from(matches!(chars.get(i + 1), Some('+' | '-')));
+                if chars.get(i + 1 + sign).is_some_and(|c| c.is_ascii_digit()) {
+                    is_float = true;
+                    i += 1 + sign;
+                    while i < chars.len() && chars[i].is_ascii_digit() {
+                        i += 1;
+                    }
+                }
+            }
+            let digits: String = chars[start..i].iter().filter(|c| **c != '_').collect();
+            if is_float {
+                let value = digits
+                    .parse::<f64>()
+                    .ok()
+                    .filter(|v| v.is_finite())
+                    .ok_or_else(|| {
+                        anyhow!(
+                            "行 {}: 浮動小数点リテラルが大きすぎます: {}",
+                            token_line,
+                            digits
+                        )
+                    })?;
+                tokens.push(Spanned {
+                    value: Token::Float(value),
+                    line: token_line,
+                });
+                continue;
+            }
+            let value = digits.parse::<u64>().map_err(|_| {
+                anyhow!("行 {}: 整数リテラルが大きすぎます: {}", token_line, digits)
+            })?;
+            tokens.push(Spanned {
+                value: Token::Int(value),
+                line: token_line,
+            });
+            continue;
+        }
+
+        if c.is_alphabetic() || c == '_' || c == '$' {
+            let start = i;
+            while i < chars.len()
+                && (chars[i].is_alphanumeric() || chars[i] == '_' || chars[i] == '$')
+            {
+                i += 1;
+            }
+            let ident: String = chars[start..i].iter().collect();
+            let token = keyword(&ident).unwrap_or(Token::Ident(ident));
+            tokens.push(Spanned {
+                value: token,
+                line: token_line,
+            });
+            continue;
+        }
+
+        let next = chars.get(i + 1).copied();
+        let (token, width) = match c {
+            '(' => (Token::LParen, 1),
+            ')' => (Token::RParen, 1),
+            '[' => (Token::LBracket, 1),
+            ']' => (Token::RBracket, 1),
+            '{' => (Token::LBrace, 1),
+            '}' => (Token::RBrace, 1),
+            ',' => (Token::Comma, 1),
+            ':' => (Token::Colon, 1),
+            ';' => (Token::Semicolon, 1),
+            '.' if next == Some('.') && chars.get(i + 2) == Some(&'=') => (Token::DotDotEq, 3),
+            '.' if next == Some('.') => (Token::DotDot, 2),
+            '.' => (Token::Dot, 1),
+            '+' if next == Some('=') => (Token::PlusEq, 2),
+            '+' => (Token::Plus, 1),
+            '-' if next == Some('=') => (Token::MinusEq, 2),
+            '-' => (Token::Minus, 1),
+            '*' if next == Some('=') => (Token::StarEq, 2),
+            '*' => (Token::Star, 1),
+            '/' if next == Some('=') => (Token::SlashEq, 2),
+            '/' => (Token::Slash, 1),
+            '%' if next == Some('=') => (Token::PercentEq, 2),
+            '%' => (Token::Percent, 1),
+            '=' if next == Some('=') => (Token::EqEq, 2),
+            '=' if next == Some('>') => (Token::FatArrow, 2),
+            '=' => (Token::Eq, 1),
+            '!' if next == Some('=') => (Token::BangEq, 2),
+            '!' => (Token::Bang, 1),
+            '<' if next == Some('=') => (Token::Le, 2),
+            '<' => (Token::Lt, 1),
+            '>' if next == Some('=') => (Token::Ge, 2),
+            '>' => (Token::Gt, 1),
+            '&' if next == Some('&') => (Token::AndAnd, 2),
+            '|' if next == Some('|') => (Token::OrOr, 2),
+            '|' => (Token::Pipe, 1),
+            '?' if next == Some('?') => (Token::QuestionQuestion, 2),
+            '?' if next == Some('.') => (Token::QuestionDot, 2),
+            '?' => (Token::Question, 1),
+            other => {
+                return Err(anyhow!(
+                    "行 {}: 予期しない文字 '{}' があります",
+                    token_line,
+                    other
+                ));
+            }
+        };
+        tokens.push(Spanned {
+            value: token,
+            line: token_line,
+        });
+        i += width;
+    }
+
+    tokens.push(Spanned {
+        value: Token::Eof,
+        line,
+    });
+    Ok(tokens)
+}
+
+/// Lexes a template literal starting at the opening backtick at `chars[*i]`.
+/// Each `${...}` is tokenized on its own.
+fn lex_template(chars: &[char], i: &mut usize, line: &mut usize) -> Result<Vec<TemplateToken>> {
+    let start_line = *line;
+    let unterminated = || anyhow!("行 {}: テンプレート文字列が閉じられていません", start_line);
+    let mut parts = Vec::new();
+    let mut text = String::new();
+    *i += 1;
+    loop {
+        match chars.get(*i) {
+            None => return Err(unterminated()),
+            Some('`') => {
+                *i += 1;
+                break;
+            }
+            Some('\\') => text.push(lex_escape(chars, i, *line)?),
+            Some('$') if chars.get(*i + 1) == Some(&'{') => {
+                if !text.is_empty() {
+                    parts.push(TemplateToken::Text(std::mem::take(&mut text)));
+                }
+                *i += 2;
+                let expr_line = *line;
+                let start = *i;
+                let mut depth = 0;
+                loop {
+                    match chars.get(*i) {
+                        None => return Err(unterminated()),
+                        Some('}') if depth == 0 => break,
+                        Some('{') => depth += 1,
+                        Some('}') => depth -= 1,
+                        Some('\n') => *line += 1,
+                        Some('`') => {
+                            lex_template(chars, i, line)?;
+                            continue;
+                        }
+                        Some('"') => {
+                            *i += 1;
+                            while let Some(&c) = chars.get(*i) {
+                                match c {
+                                    '"' | '\n' => break,
+                                    '\\' => *i += 1,
+                                    _ => {}
+                                }
+                                *i += 1;
+                            }
+                        }
+                        Some(_) => {}
+                    }
+                    *i += 1;
+                }
+                // leading newlines keep the expression's lines (and errors) in place
+                let mut source = "\n".repeat(expr_line - 1);
+                source.extend(&chars[start..*i]);
+                *i += 1;
+                parts.push(TemplateToken::Expr(tokenize(&source)?));
+            }
+            Some(&other) => {
+                if other == '\n' {
+                    *line += 1;
+                }
+                text.push(other);
+                *i += 1;
+            }
+        }
+    }
+    if !text.is_empty() {
+        parts.push(TemplateToken::Text(text));
+    }
+    Ok(parts)
+}
+
+/// Decodes the escape sequence starting at the backslash at `chars[*i]`:
+/// `\n \t \r \0 \\ \" \' \` \$` and `\u{hex}`.
+fn lex_escape(chars: &[char], i: &mut usize, line: usize) -> Result<char> {
+    let escaped = chars.get(*i + 1).copied();
+    *i += 2;
+    let decoded = match escaped {
+        Some('n') => '\n',
+        Some('t') => '\t',
+        Some('r') => '\r',
+        Some('0') => '\0',
+        Some(c @ ('\\' | '"' | '\'' | '`' | '$')) => c,
+        Some('u') if chars.get(*i) == Some(&'{') => {
+            let start = *i + 1;
+            let end = (start..chars.len())
+                .find(|&j| chars[j] == '}' || chars[j] == '\n')
+                .unwrap_or(chars.len());
+            let digits: String = chars[start..end].iter().collect();
+            *i = end + 1;
+            u32::from_str_radix(&digits, 16)
+                .ok()
+                .filter(|_| (1..=6).contains(&digits.len()) && chars.get(end) == Some(&'}'))
+                .and_then(char::from_u32)
+                .ok_or_else(|| {
+                    anyhow!(
+                        "行 {}: \\u{{{}}} は有効な Unicode エスケープではありません",
+                        line,
+                        digits
+                    )
+                })?
+        }
+        Some('\n') | None => {
+            return Err(anyhow!("行 {}: 文字列リテラルが閉じられていません", line));
+        }
+        Some(other) => {
+            return Err(anyhow!(
+                "行 {}: 不明なエスケープシーケンス '\\{}' です",
+                line,
+                other
+            ));
+        }
+    };
+    Ok(decoded)
+}
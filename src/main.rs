@@ -7,6 +7,19 @@ use anyhow::{Context, Result, anyhow};
 use clap::{ArgAction, Parser, Subcommand};
 use globwalk::GlobWalkerBuilder;
 
+mod ast;
+mod codegen;
+mod format;
+mod lexer;
+mod parser;
+mod typeck;
+
+use ast::Program;
+use codegen::codegen_c;
+use format::format_program;
+use parser::parse_program;
+use typeck::type_check;
+
 #[derive(Parser)]
 #[command(name = "voltts", version, about = "VoltTS CLI (v0.1 prototype)")]
 struct Cli {
@@ -79,22 +92,6 @@ fn main() -> Result<()> {
     Ok(())
 }
 
-fn stmt_contains_return(stmt: &Stmt) -> bool {
-    match stmt {
-        Stmt::ReturnInt(_) => true,
-        Stmt::If {
-            condition: _,
-            then_branch,
-            else_branch,
-        } => then_branch.iter().any(stmt_contains_return)
-            || else_branch.iter().any(stmt_contains_return),
-        Stmt::While { body, .. } => body.iter().any(stmt_contains_return),
-        Stmt::ForRange { body, .. } => body.iter().any(stmt_contains_return),
-        Stmt::Await(inner) => stmt_contains_return(inner),
-        _ => false,
-    }
-}
-
 fn handle_init(root: PathBuf) -> Result<()> {
     fs::create_dir_all(root.join("src"))
         .with_context(|| format!("failed to create src directory under {}", root.display()))?;
@@ -194,7 +191,8 @@ fn handle_fmt(path: PathBuf, check: bool) -> Result<()> {
     let source =
         fs::read_to_string(&path).with_context(|| format!("failed to read {}", path.display()))?;
     let program = parse_program(&source)?;
-    type_check(&program)?;
+    // imported modules are loaded so that their names type-check
+    type_check(&load_program(&path)?)?;
     let formatted = format_program(&program);
 
     if check {
@@ -212,9 +210,7 @@ fn handle_fmt(path: PathBuf, check: bool) -> Result<()> {
 }
 
 fn handle_lint(path: PathBuf) -> Result<()> {
-    let source =
-        fs::read_to_string(&path).with_context(|| format!("failed to read {}", path.display()))?;
-    let program = parse_program(&source)?;
+    let program = load_program(&path)?;
     type_check(&program)?;
     println!("{} linted successfully", path.display());
     Ok(())
@@ -223,14 +219,18 @@ fn handle_lint(path: PathBuf) -> Result<()> {
 fn handle_build(entry: PathBuf, c_out: PathBuf, bin_out: PathBuf) -> Result<PathBuf> {
     ensure_entry_exists(&entry)?;
     let program = load_program(&entry)?;
-    type_check(&program)?;
+    // imported modules may hold only declarations; the entry file runs code
+    if !program.functions.iter().any(|func| func.source.is_none()) {
+        return Err(anyhow!("no functions found in {}", entry.display()));
+    }
+    let program = type_check(&program)?;
     let c_code = codegen_c(&program, &entry);
 
-    if let Some(parent) = c_out.parent() {
-        if !parent.as_os_str().is_empty() {
-            fs::create_dir_all(parent)
-                .with_context(|| format!("failed to create output dir {}", parent.display()))?;
-        }
+    if let Some(parent) = c_out.parent()
+        && !parent.as_os_str().is_empty()
+    {
+        fs::create_dir_all(parent)
+            .with_context(|| format!("failed to create output dir {}", parent.display()))?;
     }
     fs::write(&c_out, &c_code)
         .with_context(|| format!("failed to write C artifact at {}", c_out.display()))?;
@@ -242,6 +242,9 @@ fn handle_build(entry: PathBuf, c_out: PathBuf, bin_out: PathBuf) -> Result<Path
     fs::create_dir_all(&bin_parent)
         .with_context(|| format!("failed to create binary dir {}", bin_parent.display()))?;
 
+    // the generated C uses statement expressions and `__builtin_*_overflow`,
+    // which gcc and clang accept in c99 mode (without `-pedantic`); gnu99
+    // would predefine `unix` / `linux` and break variables of those names
     let status = Command::new("cc")
         .args(["-std=c99", "-Wall", "-Werror"])
         .arg(&c_out)
@@ -262,7 +265,7 @@ fn handle_build(entry: PathBuf, c_out: PathBuf, bin_out: PathBuf) -> Result<Path
     Ok(bin_out)
 }
 
-fn ensure_entry_exists(entry: &PathBuf) -> Result<()> {
+fn ensure_entry_exists(entry: &Path) -> Result<()> {
     if entry.exists() {
         return Ok(());
     }
@@ -270,216 +273,6 @@ fn ensure_entry_exists(entry: &PathBuf) -> Result<()> {
     Err(anyhow!("entry file {} does not exist", entry.display()))
 }
 
-// --- Frontend (very small subset) ---
-#[derive(Debug, Clone)]
-struct Import {
-    names: Vec<String>,
-    module: String,
-}
-
-#[derive(Debug, Clone)]
-struct Program {
-    imports: Vec<Import>,
-    functions: Vec<Function>,
-}
-
-#[derive(Debug, Clone)]
-struct Function {
-    name: String,
-    return_type: Option<TypeAnnotation>,
-    body: Vec<Spanned<Stmt>>,
-    is_async: bool,
-    #[allow(dead_code)]
-    line: usize,
-}
-
-#[derive(Debug, Clone, PartialEq, Eq)]
-enum TypeAnnotation {
-    Int,
-    String,
-    Void,
-    Unknown(String),
-}
-
-impl TypeAnnotation {
-    fn as_str(&self) -> &str {
-        match self {
-            TypeAnnotation::Int => "int",
-            TypeAnnotation::String => "string",
-            TypeAnnotation::Void => "void",
-            TypeAnnotation::Unknown(raw) => raw.as_str(),
-        }
-    }
-}
-
-#[derive(Debug, Clone)]
-struct Spanned<T> {
-    value: T,
-    line: usize,
-}
-
-#[derive(Debug, Clone, Copy)]
-enum LogLevel {
-    Info,
-    Warn,
-    Error,
-}
-
-#[derive(Debug, Clone)]
-enum Stmt {
-    Print(String),
-    ReturnInt(i32),
-    Log { level: LogLevel, message: String },
-    SleepMs(u64),
-    TimeNow,
-    FsReadFile { path: String },
-    FsWriteFile { path: String, contents: String },
-    Call(String),
-    Await(Box<Stmt>),
-    If {
-        condition: Condition,
-        then_branch: Vec<Stmt>,
-        else_branch: Vec<Stmt>,
-    },
-    While {
-        condition: Condition,
-        body: Vec<Stmt>,
-    },
-    ForRange {
-        var: String,
-        start: i32,
-        end: i32,
-        body: Vec<Stmt>,
-    },
-}
-
-#[derive(Debug, Clone)]
-enum Condition {
-    BoolLiteral(bool),
-}
-
-fn parse_program(source: &str) -> Result<Program> {
-    let mut lines = source.lines().enumerate().peekable();
-    let mut imports = Vec::new();
-    let mut functions = Vec::new();
-
-    while let Some((line_number, line)) = lines.next() {
-        let trimmed = line.trim();
-        if trimmed.is_empty() || trimmed.starts_with("//") {
-            continue;
-        }
-
-        if trimmed.starts_with("import ") {
-            imports.push(parse_import(trimmed, line_number + 1)?);
-            continue;
-        }
-
-        if trimmed.starts_with("export fn")
-            || trimmed.starts_with("fn")
-            || trimmed.starts_with("export async fn")
-            || trimmed.starts_with("async fn")
-        {
-            let signature = trimmed.strip_prefix("export ").unwrap_or(trimmed);
-            let (name, return_type, is_async) = parse_signature(signature, line_number + 1)?;
-
-            let mut body = Vec::new();
-            // consume until '{'
-            if !signature.contains('{') {
-                while let Some((_, next)) = lines.next() {
-                    if next.contains('{') {
-                        break;
-                    }
-                }
-            }
-
-            for (body_idx, body_line) in &mut lines {
-                let body_trimmed = body_line.trim();
-                if body_trimmed.starts_with('}') {
-                    break;
-                }
-                if body_trimmed.is_empty() || body_trimmed.starts_with("//") {
-                    continue;
-                }
-                body.push(Spanned {
-                    value: parse_stmt(body_trimmed)?,
-                    line: body_idx + 1,
-                });
-            }
-
-            functions.push(Function {
-                name,
-                return_type,
-                body,
-                is_async,
-                line: line_number + 1,
-            });
-        }
-    }
-
-    if functions.is_empty() {
-        return Err(anyhow!("no functions found"));
-    }
-
-    Ok(Program { imports, functions })
-}
-
-fn type_check(program: &Program) -> Result<()> {
-    for func in &program.functions {
-        if func.name == "main" {
-            match func.return_type {
-                Some(TypeAnnotation::Int) | Some(TypeAnnotation::Void) => {}
-                Some(ref other) => {
-                    return Err(anyhow!(
-                        "行 {}: main は戻り値型として int もしくは void を明示してください (指定: {})",
-                        func.line,
-                        other.as_str()
-                    ))
-                }
-                None => {
-                    return Err(anyhow!(
-                        "行 {}: main は戻り値型として int もしくは void を明示してください",
-                        func.line
-                    ))
-                }
-            }
-        }
-
-        let declared = func
-            .return_type
-            .as_ref()
-            .cloned()
-            .unwrap_or(TypeAnnotation::Void);
-        let expected = declared;
-
-        let mut saw_return = false;
-        for stmt in &func.body {
-            if stmt_contains_return(&stmt.value) {
-                saw_return = true;
-                if !matches!(expected, TypeAnnotation::Int) {
-                    return Err(anyhow!(
-                        "行 {}: 関数 {} は {} を返すべきなのに、int を返しています",
-                        stmt.line,
-                        func.name,
-                        expected.as_str()
-                    ));
-                }
-            }
-        }
-
-        if func.name != "main"
-            && matches!(expected, TypeAnnotation::Int)
-            && !saw_return
-        {
-            return Err(anyhow!(
-                "行 {}: 関数 {} は int を返す必要がありますが、return が見つかりません",
-                func.line, func.name
-            ));
-        }
-    }
-
-    Ok(())
-}
-
 fn load_program(entry: &PathBuf) -> Result<Program> {
     let mut visited = HashSet::new();
     load_program_recursive(entry, &mut visited)
@@ -490,6 +283,10 @@ fn load_program_recursive(path: &PathBuf, visited: &mut HashSet<PathBuf>) -> Res
     if !visited.insert(abs.clone()) {
         return Ok(Program {
             imports: Vec::new(),
+            structs: Vec::new(),
+            enums: Vec::new(),
+            aliases: Vec::new(),
+            interfaces: Vec::new(),
             functions: Vec::new(),
         });
     }
@@ -498,6 +295,10 @@ fn load_program_recursive(path: &PathBuf, visited: &mut HashSet<PathBuf>) -> Res
         fs::read_to_string(&abs).with_context(|| format!("failed to read {}", abs.display()))?;
     let mut program = parse_program(&source)?;
 
+    let mut extra_structs = Vec::new();
+    let mut extra_enums = Vec::new();
+    let mut extra_aliases = Vec::new();
+    let mut extra_interfaces = Vec::new();
     let mut extra_functions = Vec::new();
     let base_dir = abs
         .parent()
@@ -509,689 +310,33 @@ fn load_program_recursive(path: &PathBuf, visited: &mut HashSet<PathBuf>) -> Res
             if resolved.extension().is_none() {
                 resolved.set_extension("vts");
             }
-            let nested = load_program_recursive(&resolved, visited)?;
+            let mut nested = load_program_recursive(&resolved, visited)?;
+            // panics name the module the way the entry file is named: relative
+            // to the working directory when it lies below it
+            let source = fs::canonicalize(&resolved).unwrap_or(resolved);
+            let source = std::env::current_dir()
+                .ok()
+                .and_then(|cwd| source.strip_prefix(cwd).ok().map(Path::to_path_buf))
+                .unwrap_or(source);
+            for func in &mut nested.functions {
+                func.source.get_or_insert_with(|| source.clone());
+            }
+            extra_structs.extend(nested.structs);
+            extra_enums.extend(nested.enums);
+            extra_aliases.extend(nested.aliases);
+            extra_interfaces.extend(nested.interfaces);
             extra_functions.extend(nested.functions);
         }
     }
 
+    program.structs.extend(extra_structs);
+    program.enums.extend(extra_enums);
+    program.aliases.extend(extra_aliases);
+    program.interfaces.extend(extra_interfaces);
     program.functions.extend(extra_functions);
     Ok(program)
 }
 
-fn parse_import(line: &str, line_number: usize) -> Result<Import> {
-    let without_suffix = line.trim().trim_end_matches(';').trim();
-    let without_prefix = without_suffix
-        .strip_prefix("import")
-        .ok_or_else(|| anyhow!("invalid import syntax at line {}: {}", line_number, line))?
-        .trim();
-
-    let (names_part, rest) = without_prefix.split_once('}').ok_or_else(|| {
-        anyhow!(
-            "import must include a closing brace ('}}') at line {}: {}",
-            line_number,
-            line
-        )
-    })?;
-    let names_block = names_part.strip_prefix('{').ok_or_else(|| {
-        anyhow!(
-            "import must start with '{{' at line {}: {}",
-            line_number,
-            line
-        )
-    })?;
-    let names: Vec<String> = names_block
-        .split(',')
-        .map(|n| n.trim())
-        .filter(|n| !n.is_empty())
-        .map(|n| n.to_string())
-        .collect();
-
-    if names.is_empty() {
-        return Err(anyhow!(
-            "import must list at least one name at line {}: {}",
-            line_number,
-            line
-        ));
-    }
-
-    let module = rest
-        .trim()
-        .strip_prefix("from")
-        .ok_or_else(|| anyhow!("import missing 'from' at line {}: {}", line_number, line))?
-        .trim()
-        .trim_matches('"')
-        .to_string();
-
-    if module.is_empty() {
-        return Err(anyhow!(
-            "import module path is empty at line {}: {}",
-            line_number,
-            line
-        ));
-    }
-
-    Ok(Import { names, module })
-}
-
-fn parse_signature(
-    signature: &str,
-    line_number: usize,
-) -> Result<(String, Option<TypeAnnotation>, bool)> {
-    // signature like: fn main(): int {
-    let mut without_prefix = signature.trim_start_matches("export").trim();
-    let mut is_async = false;
-    if without_prefix.starts_with("async") {
-        is_async = true;
-        without_prefix = without_prefix.trim_start_matches("async").trim();
-    }
-    without_prefix = without_prefix.trim_start_matches("fn").trim();
-
-    let name_and_rest: Vec<&str> = without_prefix.splitn(2, '(').collect();
-    if name_and_rest.len() < 2 {
-        return Err(anyhow!(
-            "invalid function signature at line {}: {}",
-            line_number,
-            signature
-        ));
-    }
-    let name = name_and_rest[0].trim().to_string();
-
-    let after_params = name_and_rest[1];
-    let return_type = if let Some(idx) = after_params.find(':') {
-        let rt = after_params[idx + 1..].trim();
-        let rt = rt.trim_end_matches('{').trim();
-        if rt.is_empty() {
-            None
-        } else {
-            Some(parse_type_annotation(rt, line_number))
-        }
-    } else {
-        None
-    };
-
-    Ok((name, return_type, is_async))
-}
-
-fn parse_type_annotation(raw: &str, line_number: usize) -> TypeAnnotation {
-    match raw {
-        "int" => TypeAnnotation::Int,
-        "string" => TypeAnnotation::String,
-        "void" => TypeAnnotation::Void,
-        other => {
-            eprintln!(
-                "warning: 未知の型 '{}' (行 {}) を見つけました。Unknown type will be treated as opaque.",
-                other, line_number
-            );
-            TypeAnnotation::Unknown(other.to_string())
-        }
-    }
-}
-
-fn parse_stmt(line: &str) -> Result<Stmt> {
-    let trimmed = line.trim().trim_end_matches(';');
-    if let Some(rest) = trimmed.strip_prefix("await ") {
-        let inner = parse_stmt_core(rest)?;
-        return Ok(Stmt::Await(Box::new(inner)));
-    }
-
-    parse_stmt_core(trimmed)
-}
-
-fn parse_stmt_core(trimmed: &str) -> Result<Stmt> {
-    if let Some(rest) = trimmed.strip_prefix("if ") {
-        let (cond_raw, branches_raw) = rest
-            .split_once('{')
-            .ok_or_else(|| anyhow!("if 文は {{ が必要です: {}", trimmed))?;
-        let condition = parse_condition(cond_raw.trim())?;
-        let (then_raw, else_raw) = branches_raw
-            .rsplit_once("} else {")
-            .map(|(then_part, else_part)| (then_part, Some(else_part)))
-            .unwrap_or((branches_raw, None));
-        let then_branch = parse_inline_block(then_raw.trim_end_matches('}').trim())?;
-        let else_branch = if let Some(raw) = else_raw {
-            parse_inline_block(raw.trim_end_matches('}').trim())?
-        } else {
-            Vec::new()
-        };
-
-        return Ok(Stmt::If {
-            condition,
-            then_branch,
-            else_branch,
-        });
-    }
-
-    if let Some(rest) = trimmed.strip_prefix("while ") {
-        let (cond_raw, body_raw) = rest
-            .split_once('{')
-            .ok_or_else(|| anyhow!("while 文は {{ が必要です: {}", trimmed))?;
-        let condition = parse_condition(cond_raw.trim())?;
-        let body = parse_inline_block(body_raw.trim_end_matches('}').trim())?;
-        return Ok(Stmt::While { condition, body });
-    }
-
-    if let Some(rest) = trimmed.strip_prefix("for ") {
-        let (head, body_raw) = rest
-            .split_once('{')
-            .ok_or_else(|| anyhow!("for 文は {{ が必要です: {}", trimmed))?;
-        let (var_part, range_part) = head
-            .trim()
-            .split_once(" in ")
-            .ok_or_else(|| anyhow!("for 文は 'for <var> in <start>..<end>' 形式です: {}", head))?;
-        let var = var_part.trim().to_string();
-        let (start_raw, end_raw) = range_part
-            .split_once("..")
-            .ok_or_else(|| anyhow!("for の range は start..end 形式です: {}", range_part))?;
-        let start = start_raw
-            .trim()
-            .parse::<i32>()
-            .context("for range start は整数である必要があります")?;
-        let end = end_raw
-            .trim()
-            .parse::<i32>()
-            .context("for range end は整数である必要があります")?;
-        let body = parse_inline_block(body_raw.trim_end_matches('}').trim())?;
-        return Ok(Stmt::ForRange {
-            var,
-            start,
-            end,
-            body,
-        });
-    }
-
-    if trimmed.starts_with("print(") && trimmed.ends_with(')') {
-        let inner = trimmed.trim_start_matches("print(").trim_end_matches(')');
-        let text = inner
-            .trim()
-            .trim_start_matches('"')
-            .trim_end_matches('"')
-            .replace('\"', "\"");
-        return Ok(Stmt::Print(text));
-    }
-
-    if trimmed.starts_with("log.") {
-        let (level, rest) = trimmed
-            .split_once('(')
-            .ok_or_else(|| anyhow!("invalid log call: {}", trimmed))?;
-        let level = level.trim_end_matches('.');
-        let message = rest
-            .trim_end_matches(')')
-            .trim()
-            .trim_start_matches('"')
-            .trim_end_matches('"')
-            .replace('\"', "\"");
-        let level = match level {
-            "log.info" => LogLevel::Info,
-            "log.warn" => LogLevel::Warn,
-            "log.error" => LogLevel::Error,
-            _ => {
-                return Err(anyhow!(
-                    "unsupported log level '{}'; use log.info/log.warn/log.error",
-                    level
-                ));
-            }
-        };
-
-        return Ok(Stmt::Log { level, message });
-    }
-
-    if let Some(rest) = trimmed.strip_prefix("fs.readFile(") {
-        let inner = rest.trim_end_matches(')');
-        let path = inner
-            .trim()
-            .trim_start_matches('"')
-            .trim_end_matches('"')
-            .replace('\"', "\"");
-        return Ok(Stmt::FsReadFile { path });
-    }
-
-    if let Some(rest) = trimmed.strip_prefix("fs.writeFile(") {
-        let inner = rest.trim_end_matches(')');
-        let parts: Vec<&str> = inner.splitn(2, ',').collect();
-        if parts.len() != 2 {
-            return Err(anyhow!("fs.writeFile expects path and contents"));
-        }
-        let path = parts[0]
-            .trim()
-            .trim_start_matches('"')
-            .trim_end_matches('"')
-            .replace('\"', "\"");
-        let contents = parts[1]
-            .trim()
-            .trim_start_matches('"')
-            .trim_end_matches('"')
-            .replace('\"', "\"");
-        return Ok(Stmt::FsWriteFile { path, contents });
-    }
-
-    if let Some(rest) = trimmed.strip_prefix("time.sleep(") {
-        let value = rest
-            .trim_end_matches(')')
-            .trim()
-            .parse::<u64>()
-            .context("expected integer milliseconds for time.sleep")?;
-        return Ok(Stmt::SleepMs(value));
-    }
-
-    if trimmed == "time.now()" {
-        return Ok(Stmt::TimeNow);
-    }
-
-    if let Some(rest) = trimmed.strip_prefix("return ") {
-        let value = rest
-            .trim()
-            .parse::<i32>()
-            .context("expected integer return value")?;
-        return Ok(Stmt::ReturnInt(value));
-    }
-
-    if trimmed.ends_with("()") {
-        let name = trimmed.trim_end_matches("()").trim();
-        if !name.is_empty() {
-            return Ok(Stmt::Call(name.to_string()));
-        }
-    }
-
-    Err(anyhow!("unsupported statement: {}", trimmed))
-}
-
-fn parse_condition(raw: &str) -> Result<Condition> {
-    match raw.trim() {
-        "true" => Ok(Condition::BoolLiteral(true)),
-        "false" => Ok(Condition::BoolLiteral(false)),
-        other => Err(anyhow!("条件式がサポートされていません: {}", other)),
-    }
-}
-
-fn parse_inline_block(raw: &str) -> Result<Vec<Stmt>> {
-    if raw.is_empty() {
-        return Ok(Vec::new());
-    }
-
-    let mut stmts = Vec::new();
-    for part in raw.split(';') {
-        let trimmed = part.trim();
-        if trimmed.is_empty() {
-            continue;
-        }
-        stmts.push(parse_stmt_core(trimmed)?);
-    }
-    Ok(stmts)
-}
-
-fn emit_stmt(
-    out: &mut String,
-    stmt: &Stmt,
-    temp_counter: &mut usize,
-    saw_return: Option<&mut bool>,
-    in_main: bool,
-    returns_int: bool,
-) {
-    let mut saw_return = saw_return;
-    match stmt {
-        Stmt::Await(inner) => emit_stmt(out, inner, temp_counter, saw_return, in_main, returns_int),
-        Stmt::Print(text) => {
-            out.push_str(&format!(
-                "    printf(\"%s\\n\", \"{}\");\n",
-                text.replace('"', "\\\"")
-            ));
-        }
-        Stmt::Log { level, message } => {
-            let call = match level {
-                LogLevel::Info => "vts_log_info",
-                LogLevel::Warn => "vts_log_warn",
-                LogLevel::Error => "vts_log_error",
-            };
-            out.push_str(&format!(
-                "    {}(\"{}\");\n",
-                call,
-                message.replace('"', "\\\"")
-            ));
-        }
-        Stmt::SleepMs(ms) => {
-            out.push_str(&format!("    vts_sleep_ms({});\n", ms));
-        }
-        Stmt::TimeNow => {
-            out.push_str("    printf(\"%lld\\n\", vts_time_now_ms());\n");
-        }
-        Stmt::FsReadFile { path } => {
-            let tmp = format!("vts_tmp{}", temp_counter);
-            *temp_counter += 1;
-            out.push_str(&format!(
-                "    char *{} = vts_fs_read_file(\"{}\");\n",
-                tmp,
-                path.replace('"', "\\\"")
-            ));
-            let ret = if returns_int { "return 1;" } else { "return;" };
-            out.push_str(&format!(
-                "    if ({0}) {{ printf(\"%s\\n\", {0}); free({0}); }} else {{ fprintf(stderr, \"[fs.readFile] failed: {1}\\n\"); {2} }}\n",
-                tmp,
-                path.replace('"', "\\\"")
-                ,
-                ret
-            ));
-        }
-        Stmt::FsWriteFile { path, contents } => {
-            let ret = if returns_int { "return 1;" } else { "return;" };
-            out.push_str(&format!(
-                "    if (vts_fs_write_file(\"{}\", \"{}\") != 0) {{ fprintf(stderr, \"[fs.writeFile] failed: {}\\n\"); {3} }}\n",
-                path.replace('"', "\\\""),
-                contents.replace('"', "\\\""),
-                path.replace('"', "\\\""),
-                ret
-            ));
-        }
-        Stmt::Call(name) => {
-            out.push_str(&format!("    {}();\n", name));
-        }
-        Stmt::ReturnInt(v) => {
-            if let Some(flag) = saw_return.as_deref_mut() {
-                *flag = true;
-            }
-            out.push_str(&format!("    return {};\n", v));
-        }
-        Stmt::If {
-            condition,
-            then_branch,
-            else_branch,
-        } => {
-            out.push_str(&format!("    if ({}) {{\n", emit_condition(condition)));
-            for inner in then_branch {
-                emit_stmt(
-                    out,
-                    inner,
-                    temp_counter,
-                    saw_return.as_deref_mut(),
-                    in_main,
-                    returns_int,
-                );
-            }
-            out.push_str("    }\n");
-            if !else_branch.is_empty() {
-                out.push_str("    else {\n");
-                for inner in else_branch {
-                    emit_stmt(
-                        out,
-                        inner,
-                        temp_counter,
-                        saw_return.as_deref_mut(),
-                        in_main,
-                        returns_int,
-                    );
-                }
-                out.push_str("    }\n");
-            }
-        }
-        Stmt::While { condition, body } => {
-            out.push_str(&format!("    while ({}) {{\n", emit_condition(condition)));
-            for inner in body {
-                emit_stmt(
-                    out,
-                    inner,
-                    temp_counter,
-                    saw_return.as_deref_mut(),
-                    in_main,
-                    returns_int,
-                );
-            }
-            out.push_str("    }\n");
-        }
-        Stmt::ForRange {
-            var,
-            start,
-            end,
-            body,
-        } => {
-            out.push_str(&format!(
-                "    for (int {} = {}; {} < {}; {}++) {{\n",
-                var, start, var, end, var
-            ));
-            for inner in body {
-                emit_stmt(
-                    out,
-                    inner,
-                    temp_counter,
-                    saw_return.as_deref_mut(),
-                    in_main,
-                    returns_int,
-                );
-            }
-            out.push_str("    }\n");
-        }
-    }
-}
-
-fn emit_condition(condition: &Condition) -> String {
-    match condition {
-        Condition::BoolLiteral(true) => "1".to_string(),
-        Condition::BoolLiteral(false) => "0".to_string(),
-    }
-}
-
-fn codegen_c(program: &Program, source_path: &Path) -> String {
-    let mut out = String::new();
-    out.push_str("// VoltTS v0.1 generated C (prototype)\n");
-    out.push_str(&format!("// Source: {}\n", source_path.display()));
-    out.push_str("#define _XOPEN_SOURCE 700\n");
-    out.push_str("#include <stdio.h>\n");
-    out.push_str("#include <stdlib.h>\n");
-    out.push_str("#include <string.h>\n");
-    out.push_str("#include <sys/time.h>\n");
-    out.push_str("#include <sys/stat.h>\n");
-    out.push_str("#include <unistd.h>\n\n");
-
-    out.push_str("// forward declaration for usleep on some libc variants\n");
-    out.push_str("int usleep(unsigned int);\n\n");
-
-    out.push_str("#if defined(__GNUC__) || defined(__clang__)\n#define VTS_UNUSED __attribute__((unused))\n#else\n#define VTS_UNUSED\n#endif\n\n");
-
-    out.push_str("// --- standard runtime (prototype) ---\n");
-    out.push_str(
-        "static VTS_UNUSED void vts_log_info(const char *msg) { printf(\"[info] %s\\n\", msg); }\n",
-    );
-    out.push_str(
-        "static VTS_UNUSED void vts_log_warn(const char *msg) { printf(\"[warn] %s\\n\", msg); }\n",
-    );
-    out.push_str(
-        "static VTS_UNUSED void vts_log_error(const char *msg) { printf(\"[error] %s\\n\", msg); }\n",
-    );
-    out.push_str("static VTS_UNUSED void vts_sleep_ms(unsigned long ms) { usleep(ms * 1000); }\n");
-    out.push_str(
-        "static VTS_UNUSED long long vts_time_now_ms(void) { struct timeval tv; gettimeofday(&tv, NULL); return (long long)tv.tv_sec * 1000 + tv.tv_usec / 1000; }\n\n",
-    );
-    out.push_str("static VTS_UNUSED char *vts_fs_read_file(const char *path) { FILE *f = fopen(path, \"rb\"); if (!f) return NULL; if (fseek(f, 0, SEEK_END) != 0) { fclose(f); return NULL; } long size = ftell(f); if (size < 0) { fclose(f); return NULL; } if (fseek(f, 0, SEEK_SET) != 0) { fclose(f); return NULL; } char *buf = (char *)malloc((size_t)size + 1); if (!buf) { fclose(f); return NULL; } size_t read = fread(buf, 1, (size_t)size, f); buf[read] = 0; fclose(f); return buf; }\n");
-    out.push_str("static VTS_UNUSED int vts_fs_write_file(const char *path, const char *contents) { const char *slash = strrchr(path, '/'); if (slash) { size_t len = (size_t)(slash - path); if (len > 0) { char *dir = (char *)malloc(len + 1); if (!dir) return -1; memcpy(dir, path, len); dir[len] = 0; struct stat st; if (stat(dir, &st) != 0) { mkdir(dir, 0755); } free(dir); } } FILE *f = fopen(path, \"wb\"); if (!f) return -1; size_t len = strlen(contents); size_t written = fwrite(contents, 1, len, f); fclose(f); return written == len ? 0 : -1; }\n\n");
-
-    out.push_str("// --- user prototypes ---\n");
-    for func in &program.functions {
-        let is_main = func.name == "main";
-        let returns_int = is_main || matches!(func.return_type, Some(TypeAnnotation::Int));
-        let c_return = if returns_int { "int" } else { "void" };
-        out.push_str(&format!("{} {}(void);\n", c_return, func.name));
-    }
-    out.push_str("\n");
-
-    for func in &program.functions {
-        let mut temp_counter = 0;
-        let is_main = func.name == "main";
-        let returns_int = is_main || matches!(func.return_type, Some(TypeAnnotation::Int));
-        if is_main {
-            out.push_str(&format!("{} main(void) {{\n", if returns_int { "int" } else { "void" }));
-            let mut saw_return = false;
-            for stmt in &func.body {
-                emit_stmt(
-                    &mut out,
-                    &stmt.value,
-                    &mut temp_counter,
-                    Some(&mut saw_return),
-                    returns_int,
-                    returns_int,
-                );
-            }
-            if returns_int && !saw_return {
-                out.push_str("    return 0;\n");
-            }
-            out.push_str("}\n\n");
-        } else {
-            let c_return = if returns_int { "int" } else { "void" };
-            out.push_str(&format!("{} {}(void) {{\n", c_return, func.name));
-            let mut saw_return = false;
-            for stmt in &func.body {
-                emit_stmt(
-                    &mut out,
-                    &stmt.value,
-                    &mut temp_counter,
-                    if returns_int {
-                        Some(&mut saw_return)
-                    } else {
-                        None
-                    },
-                    false,
-                    returns_int,
-                );
-            }
-            if returns_int && !saw_return {
-                out.push_str("    return 0;\n");
-            } else if matches!(func.return_type, Some(TypeAnnotation::Void) | None) {
-                out.push_str("    return;\n");
-            }
-            out.push_str("}\n\n");
-        }
-    }
-
-    out
-}
-
-fn format_program(program: &Program) -> String {
-    let mut out = String::new();
-    for import in &program.imports {
-        out.push_str(&format!(
-            "import {{ {} }} from \"{}\"\n",
-            import.names.join(", "),
-            import.module
-        ));
-    }
-
-    if !program.imports.is_empty() {
-        out.push('\n');
-    }
-
-    for func in &program.functions {
-        let async_prefix = if func.is_async { "async " } else { "" };
-        match &func.return_type {
-            Some(rt) => out.push_str(&format!(
-                "{async_prefix}fn {}(): {} {{\n",
-                func.name,
-                rt.as_str()
-            )),
-            None => out.push_str(&format!("{async_prefix}fn {}() {{\n", func.name)),
-        }
-        for stmt in &func.body {
-            match &stmt.value {
-                Stmt::Await(inner) => {
-                    out.push_str("    await ");
-                    match **inner {
-                        Stmt::Print(ref text) => out.push_str(&format!("print(\"{}\")\n", text)),
-                        Stmt::Log { level, ref message } => {
-                            let level = match level {
-                                LogLevel::Info => "info",
-                                LogLevel::Warn => "warn",
-                                LogLevel::Error => "error",
-                            };
-                            out.push_str(&format!("log.{}(\"{}\")\n", level, message));
-                        }
-                        Stmt::SleepMs(ms) => out.push_str(&format!("time.sleep({})\n", ms)),
-                        Stmt::TimeNow => out.push_str("time.now()\n"),
-                        Stmt::FsReadFile { ref path } => {
-                            out.push_str(&format!("fs.readFile(\"{}\")\n", path));
-                        }
-                        Stmt::FsWriteFile {
-                            ref path,
-                            ref contents,
-                        } => {
-                            out.push_str(&format!(
-                                "fs.writeFile(\"{}\", \"{}\")\n",
-                                path, contents
-                            ));
-                        }
-                        Stmt::Call(ref name) => {
-                            out.push_str(&format!("{}()\n", name));
-                        }
-                        Stmt::ReturnInt(v) => out.push_str(&format!("return {}\n", v)),
-                        Stmt::Await(_) => unreachable!("nested await handled earlier"),
-                        Stmt::If { .. } | Stmt::While { .. } | Stmt::ForRange { .. } => {
-                            out.push_str("/* unsupported await nesting */\n")
-                        }
-                    }
-                }
-                Stmt::If {
-                    condition,
-                    then_branch,
-                    else_branch,
-                } => {
-                    out.push_str(&format!(
-                        "    if {} {{ {} }}",
-                        format_condition(condition),
-                        format_block_inline(then_branch)
-                    ));
-                    if !else_branch.is_empty() {
-                        out.push_str(" else { ");
-                        out.push_str(&format_block_inline(else_branch));
-                        out.push_str(" }");
-                    }
-                    out.push('\n');
-                }
-                Stmt::While { condition, body } => {
-                    out.push_str(&format!(
-                        "    while {} {{ {} }}\n",
-                        format_condition(condition),
-                        format_block_inline(body)
-                    ));
-                }
-                Stmt::ForRange {
-                    var,
-                    start,
-                    end,
-                    body,
-                } => {
-                    out.push_str(&format!(
-                        "    for {} in {}..{} {{ {} }}\n",
-                        var,
-                        start,
-                        end,
-                        format_block_inline(body)
-                    ));
-                }
-                Stmt::Print(text) => out.push_str(&format!("    print(\"{}\")\n", text)),
-                Stmt::Log { level, message } => {
-                    let level = match level {
-                        LogLevel::Info => "info",
-                        LogLevel::Warn => "warn",
-                        LogLevel::Error => "error",
-                    };
-                    out.push_str(&format!("    log.{}(\"{}\")\n", level, message));
-                }
-                Stmt::SleepMs(ms) => out.push_str(&format!("    time.sleep({})\n", ms)),
-                Stmt::TimeNow => out.push_str("    time.now()\n"),
-                Stmt::FsReadFile { path } => {
-                    out.push_str(&format!("    fs.readFile(\"{}\")\n", path));
-                }
-                Stmt::FsWriteFile { path, contents } => {
-                    out.push_str(&format!(
-                        "    fs.writeFile(\"{}\", \"{}\")\n",
-                        path, contents
-                    ));
-                }
-                Stmt::Call(name) => {
-                    out.push_str(&format!("    {}()\n", name));
-                }
-                Stmt::ReturnInt(v) => out.push_str(&format!("    return {}\n", v)),
-            }
-        }
-        out.push_str("}\n");
-    }
-    out
-}
 const SAMPLE_MAIN: &str = r#"// VoltTS v0.1 sample
 // Goal:
 //   - TS-like readability
@@ -1219,74 +364,6 @@ export async fn main() {
 }
 "#;
 
-fn format_condition(cond: &Condition) -> String {
-    match cond {
-        Condition::BoolLiteral(true) => "true".to_string(),
-        Condition::BoolLiteral(false) => "false".to_string(),
-    }
-}
-
-fn format_block_inline(stmts: &[Stmt]) -> String {
-    stmts
-        .iter()
-        .map(|s| match s {
-            Stmt::Print(text) => format!("print(\"{}\")", text),
-            Stmt::Log { level, message } => {
-                let level = match level {
-                    LogLevel::Info => "info",
-                    LogLevel::Warn => "warn",
-                    LogLevel::Error => "error",
-                };
-                format!("log.{}(\"{}\")", level, message)
-            }
-            Stmt::SleepMs(ms) => format!("time.sleep({})", ms),
-            Stmt::TimeNow => "time.now()".to_string(),
-            Stmt::FsReadFile { path } => format!("fs.readFile(\"{}\")", path),
-            Stmt::FsWriteFile { path, contents } => {
-                format!("fs.writeFile(\"{}\", \"{}\")", path, contents)
-            }
-            Stmt::Call(name) => format!("{}()", name),
-            Stmt::ReturnInt(v) => format!("return {}", v),
-            Stmt::If {
-                condition,
-                then_branch,
-                else_branch,
-            } => {
-                let mut rendered = format!(
-                    "if {} {{ {} }}",
-                    format_condition(condition),
-                    format_block_inline(then_branch)
-                );
-                if !else_branch.is_empty() {
-                    rendered.push_str(" else { ");
-                    rendered.push_str(&format_block_inline(else_branch));
-                    rendered.push_str(" }");
-                }
-                rendered
-            }
-            Stmt::While { condition, body } => format!(
-                "while {} {{ {} }}",
-                format_condition(condition),
-                format_block_inline(body)
-            ),
-            Stmt::ForRange {
-                var,
-                start,
-                end,
-                body,
-            } => format!(
-                "for {} in {}..{} {{ {} }}",
-                var,
-                start,
-                end,
-                format_block_inline(body)
-            ),
-            Stmt::Await(inner) => format!("await {}", format_block_inline(&[*inner.clone()])),
-        })
-        .collect::<Vec<_>>()
-        .join("; ")
-}
-
 const SAMPLE_HELPER: &str = r#"import { log, time } from "std"
 
 export async fn logHelper() {
@@ -1299,6 +376,7 @@ export async fn logHelper() {
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::ast::{BinaryOp, ExprKind, Stmt, TypeAnnotation};
 
     #[test]
     fn int_function_requires_explicit_return() {
@@ -1324,9 +402,10 @@ fn main() {
 "#;
         let program = parse_program(src).expect("parse program");
         let err = type_check(&program).expect_err("main without type should fail");
-        assert!(err
-            .to_string()
-            .contains("main は戻り値型として int もしくは void を明示してください"));
+        assert!(
+            err.to_string()
+                .contains("main は戻り値型として int もしくは void を明示してください")
+        );
     }
 
     #[test]
@@ -1350,4 +429,1349 @@ fn main(): void {
         let program = parse_program(src).expect("parse program");
         type_check(&program).expect("main with void annotation is allowed");
     }
+
+    #[test]
+    fn multi_line_blocks_do_not_end_function_early() {
+        let src = r#"
+fn main(): int {
+    if true {
+        log.info("then")
+        print("still then")
+    } else {
+        log.warn("else")
+    }
+    for i in 0..2 {
+        while false {
+            print("never")
+        }
+    }
+    return 0
+}
+
+fn after() {
+    print("after")
+}
+"#;
+        let program = parse_program(src).expect("parse program");
+        assert_eq!(program.functions.len(), 2);
+        let main = &program.functions[0];
+        assert_eq!(main.body.len(), 3);
+        match &main.body[0].value {
+            Stmt::If {
+                then_branch,
+                else_branch,
+                ..
+            } => {
+                assert_eq!(then_branch.len(), 2);
+                assert_eq!(else_branch.len(), 1);
+            }
+            other => panic!("expected if statement, got {other:?}"),
+        }
+        assert_eq!(main.body[2].line, 14);
+        type_check(&program).expect("type check");
+    }
+
+    #[test]
+    fn statements_can_be_joined_or_split_across_lines() {
+        let src = r#"
+fn main(): void { print("a"); log.info("b"); await time.sleep(
+    5
+) }
+"#;
+        let program = parse_program(src).expect("parse program");
+        let main = &program.functions[0];
+        assert_eq!(main.body.len(), 3);
+        assert!(matches!(main.body[2].value, Stmt::Await(_)));
+    }
+
+    #[test]
+    fn binary_operators_follow_precedence() {
+        let src = r#"
+fn main(): int {
+    return 1 + 2 * 3 - 4 / 2
+}
+"#;
+        let program = parse_program(src).expect("parse program");
+        let checked = type_check(&program).expect("type check");
+        match &checked.functions[0].body[0].value {
+            Stmt::Return(Some(expr)) => {
+                let ExprKind::Binary { op, lhs, .. } = &expr.kind else {
+                    panic!("expected binary expression, got {expr:?}");
+                };
+                assert_eq!(*op, BinaryOp::Sub);
+                assert!(matches!(
+                    lhs.kind,
+                    ExprKind::Binary {
+                        op: BinaryOp::Add,
+                        ..
+                    }
+                ));
+                assert_eq!(expr.ty(), &TypeAnnotation::Int);
+            }
+            other => panic!("expected return, got {other:?}"),
+        }
+    }
+
+    #[test]
+    fn nested_negation_keeps_its_parentheses_when_formatted() {
+        let src = "fn main(): int {\n    const a = 2\n    print(-(-a))\n    print(-(-(-a)))\n    print(!!true)\n    return 0\n}\n";
+        let program = parse_program(src).expect("parse program");
+        let formatted = format_program(&program);
+        assert!(formatted.contains("print(-(-a))"));
+        assert!(formatted.contains("print(-(-(-a)))"));
+        assert!(formatted.contains("print(!!true)"));
+        let reparsed = parse_program(&formatted).expect("reparse formatted");
+        assert_eq!(format_program(&reparsed), formatted);
+    }
+
+    #[test]
+    fn string_concatenation_accepts_ints_but_arithmetic_rejects_strings() {
+        let src = r#"
+fn main(): void {
+    log.info("n=" + 1)
+    print("a" - 1)
+}
+"#;
+        let program = parse_program(src).expect("parse program");
+        let err = type_check(&program).expect_err("string subtraction should fail");
+        assert!(
+            err.to_string().contains("行 4"),
+            "expected diagnostic on line 4, got: {err}"
+        );
+    }
+
+    #[test]
+    fn return_value_must_match_declared_type() {
+        let src = r#"
+fn name(): string {
+    return 1 == 1
+}
+"#;
+        let program = parse_program(src).expect("parse program");
+        let err = type_check(&program).expect_err("bool is not a string");
+        assert!(err.to_string().contains("string を返すべきなのに、bool"));
+    }
+
+    #[test]
+    fn const_bindings_cannot_be_reassigned() {
+        let src = r#"
+fn main(): int {
+    let total = 1
+    total += 2
+    const limit = 10
+    limit = 3
+    return 0
+}
+"#;
+        let program = parse_program(src).expect("parse program");
+        let err = type_check(&program).expect_err("const reassignment");
+        assert!(
+            err.to_string()
+                .contains("行 6: const 'limit' には再代入できません")
+        );
+    }
+
+    #[test]
+    fn bindings_shadow_in_inner_blocks_but_not_in_the_same_block() {
+        let shadowing = r#"
+fn main(): int {
+    let x = 1
+    if true { let x = "inner"; print(x) }
+    let y: int = x + 1
+    return y
+}
+"#;
+        let program = parse_program(shadowing).expect("parse program");
+        let typed = type_check(&program).expect("inner shadowing is allowed");
+        match &typed.functions[0].body[0].value {
+            Stmt::Let { ty, .. } => assert_eq!(ty, &Some(TypeAnnotation::Int)),
+            other => panic!("expected let, got {:?}", other),
+        }
+
+        let redeclared = r#"
+fn main(): int {
+    let x = 1
+    let x = 2
+    return x
+}
+"#;
+        let program = parse_program(redeclared).expect("parse program");
+        let err = type_check(&program).expect_err("same-block redeclaration");
+        assert!(
+            err.to_string()
+                .contains("'x' はこのブロックで既に宣言されています")
+        );
+    }
+
+    #[test]
+    fn calls_are_checked_against_parameter_lists() {
+        let src = r#"
+fn add(a: int, b: int): int {
+    return a + b
+}
+
+fn main(): int {
+    return add(1, 2)
+}
+"#;
+        let program = parse_program(src).expect("parse program");
+        assert_eq!(program.functions[0].params.len(), 2);
+        let typed = type_check(&program).expect("well-typed call");
+        let c_code = codegen_c(&typed, Path::new("add.vts"));
+        assert!(c_code.contains("int vts_f_add(int vts_v_a, int vts_v_b);"));
+
+        let wrong_arity = src.replace("add(1, 2)", "add(1)");
+        let err = type_check(&parse_program(&wrong_arity).unwrap()).expect_err("arity");
+        assert!(
+            err.to_string()
+                .contains("2 個の引数を取りますが 1 個渡されました")
+        );
+
+        let wrong_type = src.replace("add(1, 2)", "add(1, \"2\")");
+        let err = type_check(&parse_program(&wrong_type).unwrap()).expect_err("argument type");
+        assert!(err.to_string().contains("第 2 引数の型が一致しません"));
+
+        for (decl, message) in [
+            ("fn add() {}", "行 10: 関数 add が重複して宣言されています"),
+            (
+                "fn print(x: int) {}",
+                "行 10: print は組み込み関数の名前なので、関数名には使えません",
+            ),
+            (
+                "fn ok(x: int): int { return x }",
+                "行 10: ok は組み込み関数の名前なので、関数名には使えません",
+            ),
+            (
+                "fn u8(x: int): int { return x }",
+                "行 10: u8 は組み込み関数の名前なので、関数名には使えません",
+            ),
+        ] {
+            let bad = parse_program(&format!("{}\n{}\n", src, decl)).expect("parse program");
+            let err = type_check(&bad).unwrap_err().to_string();
+            assert!(err.contains(message), "{}", err);
+        }
+
+        // functions merged from an imported module clash with local ones too
+        let mut program = parse_program(src).expect("parse program");
+        let mut imported = parse_program("fn add(a: int): int { return a }\n").unwrap();
+        for func in &mut imported.functions {
+            func.source = Some(PathBuf::from("lib/math.vts"));
+        }
+        program.functions.extend(imported.functions);
+        let err = type_check(&program).unwrap_err().to_string();
+        assert!(
+            err.contains("行 1: 関数 add が重複して宣言されています (lib/math.vts から import)"),
+            "{}",
+            err
+        );
+    }
+
+    #[test]
+    fn conditions_must_be_bool_and_else_if_chains() {
+        let src = r#"
+fn sign(n: int): int {
+    if n < 0 { return -1 } else if n == 0 { return 0 } else { return 1 }
+}
+"#;
+        let program = parse_program(src).expect("parse program");
+        let Stmt::If { else_branch, .. } = &program.functions[0].body[0].value else {
+            panic!("expected if");
+        };
+        assert!(matches!(
+            else_branch.as_slice(),
+            [nested] if matches!(nested.value, Stmt::If { .. })
+        ));
+        type_check(&program).expect("bool conditions");
+
+        let err = type_check(&parse_program("fn main(): void { while 1 { print(1) } }").unwrap())
+            .expect_err("int condition");
+        assert!(
+            err.to_string()
+                .contains("条件式は bool である必要がありますが int")
+        );
+    }
+
+    #[test]
+    fn break_and_continue_require_an_enclosing_loop() {
+        let src = r#"
+fn main(): void {
+    outer: while true {
+        for i in 0..3 { continue outer }
+        break
+    }
+}
+"#;
+        let program = parse_program(src).expect("parse program");
+        match &program.functions[0].body[0].value {
+            Stmt::While { label, .. } => assert_eq!(label.as_deref(), Some("outer")),
+            other => panic!("expected labeled while, got {:?}", other),
+        }
+        type_check(&program).expect("jumps inside loops");
+
+        let err = type_check(&parse_program("fn main(): void { break }").unwrap())
+            .expect_err("break outside loop");
+        assert!(err.to_string().contains("break はループの外では使えません"));
+
+        let unknown = src.replace("continue outer", "continue inner");
+        let err = type_check(&parse_program(&unknown).unwrap()).expect_err("unknown label");
+        assert!(
+            err.to_string()
+                .contains("ラベル 'inner' に対応するループがありません")
+        );
+    }
+
+    #[test]
+    fn for_ranges_accept_expressions_steps_and_bind_the_loop_variable() {
+        let src = r#"
+fn main(): void {
+    let n = 3
+    for i in (1..=n * 2).step(2).rev() { print(i) }
+}
+"#;
+        let program = parse_program(src).expect("parse program");
+        let Stmt::ForRange { var, range, .. } = &program.functions[0].body[1].value else {
+            panic!("expected for range");
+        };
+        assert_eq!(var, "i");
+        assert!(range.inclusive && range.reverse && range.step.is_some());
+        type_check(&program).expect("loop variable is readable");
+
+        let assign = src.replace("print(i)", "i = 0");
+        let err = type_check(&parse_program(&assign).unwrap()).expect_err("loop var is read-only");
+        assert!(
+            err.to_string()
+                .contains("ループ変数 'i' には再代入できません")
+        );
+
+        let zero_step = src.replace("step(2)", "step(0)");
+        let err = type_check(&parse_program(&zero_step).unwrap()).expect_err("zero step");
+        assert!(
+            err.to_string()
+                .contains("step は正の整数である必要があります")
+        );
+    }
+
+    #[test]
+    fn structs_are_compared_structurally() {
+        let src = r#"
+struct Point { x: int, y: int }
+struct Size { y: int, x: int }
+struct Named { x: int, y: int, name: string }
+
+fn width(s: Size): int {
+    return s.x
+}
+
+fn main(): int {
+    let p = Point { x: 1, y: 2 }
+    p.y = width(p)
+    return p.y
+}
+"#;
+        let program = parse_program(src).expect("parse program");
+        assert_eq!(program.structs.len(), 3);
+        assert_eq!(program.structs[1].fields[0].name, "y");
+        let typed = type_check(&program).expect("same shape is compatible");
+        let c_code = codegen_c(&typed, Path::new("structs.vts"));
+        assert!(c_code.contains("typedef struct vts_t_Point {"));
+        assert!(c_code.contains("(vts_t_Size){ .vts_v_y = "));
+
+        let wider = src.replace("width(p)", "width(Named { x: 1, y: 2, name: \"n\" })");
+        let err = type_check(&parse_program(&wider).unwrap()).expect_err("extra field");
+        assert!(
+            err.to_string()
+                .contains("Size が必要ですが Named が渡されました")
+        );
+    }
+
+    #[test]
+    fn match_on_enums_must_be_exhaustive() {
+        let src = r#"
+enum Shape { Circle(int), Rect(int, int), Empty }
+
+fn area(s: Shape): int {
+    return match s {
+        Circle(r) => r * r,
+        Rect(w, h) => w * h,
+        Empty => 0,
+    }
+}
+
+fn main(): int {
+    return area(Shape.Rect(2, 3))
+}
+"#;
+        let program = parse_program(src).expect("parse program");
+        assert_eq!(program.enums[0].variants.len(), 3);
+        assert_eq!(program.enums[0].variants[1].fields.len(), 2);
+        let typed = type_check(&program).expect("exhaustive match");
+        let c_code = codegen_c(&typed, Path::new("enums.vts"));
+        assert!(c_code.contains("struct { int f0; int f1; } Rect;"));
+        assert!(c_code.contains("switch (vts_m"));
+
+        let missing = src.replace("        Empty => 0,\n", "");
+        let err = type_check(&parse_program(&missing).unwrap()).expect_err("missing arm");
+        assert!(
+            err.to_string()
+                .contains("match が網羅的ではありません: Empty が処理されていません")
+        );
+
+        let unreachable = src.replace("Empty => 0,", "Empty => 0,\n        _ => 1,");
+        let err = type_check(&parse_program(&unreachable).unwrap()).expect_err("dead arm");
+        assert!(err.to_string().contains("このアームには到達しません"));
+
+        let arity = src.replace("Shape.Rect(2, 3)", "Shape.Rect(2)");
+        let err = type_check(&parse_program(&arity).unwrap()).expect_err("payload arity");
+        assert!(
+            err.to_string()
+                .contains("2 個の値を取りますが 1 個渡されました")
+        );
+    }
+
+    #[test]
+    fn type_aliases_resolve_and_unions_narrow() {
+        let src = r#"
+type Id = int
+type Status = "ok" | "err"
+type Value = string | int
+
+fn describe(v: Value): string {
+    if v is int {
+        return "int " + v
+    }
+    return v
+}
+
+fn main(): int {
+    const id: Id = 1
+    let status: Status = "ok"
+    print(describe(id) + status)
+    return id
+}
+"#;
+        let program = parse_program(src).expect("parse program");
+        assert_eq!(program.aliases.len(), 3);
+        let typed = type_check(&program).expect("aliases resolve");
+        assert_eq!(typed.functions[0].params[0].ty.to_string(), "int | string");
+        let c_code = codegen_c(&typed, Path::new("unions.vts"));
+        assert!(c_code.contains("typedef struct vts_union_int_string {"));
+        assert!(c_code.contains(".tag == 0"));
+
+        let bad_literal = src.replace("status: Status = \"ok\"", "status: Status = \"maybe\"");
+        let err = type_check(&parse_program(&bad_literal).unwrap()).expect_err("not a member");
+        assert!(
+            err.to_string()
+                .contains("\"err\" | \"ok\" が必要ですが string")
+        );
+
+        let not_member = src.replace("v is int", "v is bool");
+        let err = type_check(&parse_program(&not_member).unwrap()).expect_err("bool");
+        assert!(
+            err.to_string()
+                .contains("bool は int | string のメンバーではありません")
+        );
+
+        let cyclic = src.replace("type Id = int", "type Id = Key\ntype Key = Id");
+        let err = type_check(&parse_program(&cyclic).unwrap()).expect_err("cycle");
+        assert!(err.to_string().contains("型エイリアス Id が循環しています"));
+
+        let unknown = src.replace("type Id = int", "type Id = Idx");
+        let err = type_check(&parse_program(&unknown).unwrap()).expect_err("unknown name");
+        assert!(err.to_string().contains("行 2: 未定義の型 'Idx' です"));
+    }
+
+    #[test]
+    fn nullable_values_need_a_none_check() {
+        let src = r#"
+fn lookup(key: string): int? {
+    if key == "a" {
+        return 1
+    }
+    return none
+}
+
+fn main(): int {
+    const found = lookup("a")
+    if found != none {
+        print(found + 1)
+    }
+    print(lookup("b") ?? 0)
+    return 0
+}
+"#;
+        let program = parse_program(src).expect("parse program");
+        let typed = type_check(&program).expect("none check narrows");
+        assert_eq!(
+            typed.functions[0].return_type.as_ref().unwrap().to_string(),
+            "int?"
+        );
+        let c_code = codegen_c(&typed, Path::new("nullable.vts"));
+        assert!(c_code.contains("typedef struct vts_opt_int { bool has_value; int value; }"));
+        assert!(c_code.contains(".has_value = false"));
+
+        let unchecked = src.replace(
+            "if found != none {",
+            "if true {\n        const n: int = found",
+        );
+        let err = type_check(&parse_program(&unchecked).unwrap()).expect_err("unchecked");
+        assert!(
+            err.to_string()
+                .contains("int が必要ですが int? が渡されました")
+        );
+
+        let untyped = src.replace("const found = lookup(\"a\")", "const found = none");
+        let err = type_check(&parse_program(&untyped).unwrap()).expect_err("bare none");
+        assert!(
+            err.to_string()
+                .contains("none だけでは 'found' の型を決められません")
+        );
+
+        // assigning a narrowed variable checks against its declared type and
+        // ends the narrowing, also for the code before it in a loop
+        let reassigned = src.replace(
+            "print(found + 1)",
+            "let y: int? = lookup(\"b\")\n        if y != none {\n            y = lookup(\"c\")\n            print(y ?? 1)\n            y = none\n            while true {\n                print(found + 1)\n                found = none\n            }\n        }",
+        ).replace("const found", "let found");
+        let program = parse_program(&reassigned).unwrap();
+        let err = type_check(&program).expect_err("read before found = none in a loop");
+        assert!(
+            err.to_string()
+                .contains("行 18: 演算子 + は int? と int の組み合わせに使えません"),
+            "{}",
+            err
+        );
+        let fixed = reassigned.replace("print(found + 1)", "print(found ?? 0)");
+        type_check(&parse_program(&fixed).unwrap()).expect("reassign narrowed");
+
+        // `?.` calls methods on the present value
+        let calls = r#"
+struct Point { x: int }
+fn Point.double(self): int { return self.x * 2 }
+fn Point.show(self) { print(self.x) }
+fn Point.reset(mut self) { self.x = 0 }
+fn main(): int {
+    const p: Point? = Point { x: 2 }
+    print(p?.double() ?? 0)
+    p?.show()
+    return 0
+}
+"#;
+        let program = parse_program(calls).expect("parse program");
+        assert!(format_program(&program).contains("print(p?.double() ?? 0)\n    p?.show()"));
+        let typed = type_check(&program).expect("optional calls");
+        let c_code = codegen_c(&typed, Path::new("nullable.vts"));
+        assert!(c_code.contains(
+            "vts_opt_Point vts_opt1 = vts_v_p; vts_opt1.has_value ? ((vts_opt_int){ .has_value = true, .value = vts_f_Point_double(&(vts_opt1.value)) })"
+        ));
+        assert!(c_code.contains("if (vts_opt2.has_value) vts_f_Point_show(&(vts_opt2.value));"));
+        let err = type_check(&parse_program(&calls.replace("p?.show()", "p?.reset()")).unwrap())
+            .expect_err("mut self through ?.");
+        assert!(
+            err.to_string()
+                .contains("行 9: Point.reset は mut self を取るため、?. では呼び出せません"),
+            "{}",
+            err
+        );
+    }
+
+    #[test]
+    fn results_propagate_errors_with_question_mark() {
+        let src = r#"
+fn parse(text: string): Result<int, string> {
+    if text == "1" {
+        return ok(1)
+    }
+    return err("bad: " + text)
+}
+
+fn twice(text: string): Result<int, string> {
+    const n = parse(text)?
+    return ok(n * 2)
+}
+
+fn main(): int {
+    match twice("1") {
+        ok(n) => print(n)
+        err(message) => print(message)
+    }
+    return 0
+}
+"#;
+        let program = parse_program(src).expect("parse program");
+        let typed = type_check(&program).expect("results check");
+        let c_code = codegen_c(&typed, Path::new("results.vts"));
+        assert!(c_code.contains(
+            "typedef struct vts_result_int_string { bool ok; int value; vts_string error; }"
+        ));
+        assert!(c_code.contains("if (!vts_res0.ok) { return (vts_result_int_string){ .ok = false"));
+
+        let void_fn = src.replace(
+            "fn twice(text: string): Result<int, string>",
+            "fn twice(text: string): int",
+        );
+        let err = type_check(&parse_program(&void_fn).unwrap()).expect_err("not a result");
+        assert!(
+            err.to_string()
+                .contains("? は戻り値が Result の関数でしか使えません")
+        );
+
+        let ignored = src.replace("const n = parse(text)?", "parse(text)\n    const n = 1");
+        let err = type_check(&parse_program(&ignored).unwrap()).expect_err("ignored result");
+        assert!(err.to_string().contains("Result の値が使われていません"));
+
+        let partial = src.replace("        err(message) => print(message)\n", "");
+        let err = type_check(&parse_program(&partial).unwrap()).expect_err("not exhaustive");
+        assert!(err.to_string().contains("err が処理されていません"));
+    }
+
+    #[test]
+    fn time_now_and_read_file_are_values() {
+        let src = r#"
+import { fs, time } from "std"
+
+async fn load(path: string): Result<string, string> {
+    const started = await time.now()
+    const text = await fs.readFile(path)?
+    return ok(text + " in " + (time.now() - started) + "ms")
+}
+
+async fn main(): int {
+    print(await load("a.txt")?)
+    return 0
+}
+"#;
+        let program = parse_program(src).expect("parse program");
+        let typed = type_check(&program).expect("values check");
+        let Stmt::Let { ty, .. } = &typed.functions[0].body[0].value else {
+            panic!("expected let");
+        };
+        assert_eq!(ty.as_ref().map(|ty| ty.to_string()).as_deref(), Some("i64"));
+        let c_code = codegen_c(&typed, Path::new("values.vts"));
+        assert!(c_code.contains("VTS_UNUSED int64_t vts_v_started = vts_time_now_ms();"));
+        assert!(c_code.contains("vts_fs_read_file("));
+
+        let formatted = format_program(&program);
+        assert!(formatted.contains("const text = await fs.readFile(path)?"));
+
+        // a local named like a std module shadows it
+        let shadowed = parse_program(
+            r#"
+struct Logger { prefix: string }
+fn Logger.info(self, message: string) { print(self.prefix + message) }
+fn main(): int {
+    const log = Logger { prefix: ">> " }
+    log.info("hi")
+    let fs = [1]
+    fs.push(2)
+    return 0
+}
+"#,
+        )
+        .expect("parse program");
+        let typed = type_check(&shadowed).expect("shadowed check");
+        let c_code = codegen_c(&typed, Path::new("shadowed.vts"));
+        assert!(c_code.contains("(void)(vts_f_Logger_info(&(vts_v_log), VTS_STR(\"hi\")));"));
+        assert!(!c_code.contains("    vts_log_info("));
+
+        for (body, message) in [
+            (
+                "log.debug(\"x\")",
+                "行 3: unsupported log level 'log.debug'; use log.info/log.warn/log.error",
+            ),
+            (
+                "const x = log.info(\"x\")",
+                "行 3: log.info(..) は値を返さないため、文としてしか使えません",
+            ),
+            (
+                "const t = time.now(1)",
+                "行 3: time.now は 0 個の引数を取りますが 1 個渡されました",
+            ),
+            (
+                "fs.remove(\"a.txt\")",
+                "行 3: unsupported call: fs.remove(); use fs.readFile/fs.writeFile",
+            ),
+        ] {
+            let bad = parse_program(&format!(
+                "import {{ fs, log, time }} from \"std\"\nfn main(): int {{\n    {}\n    return 0\n}}\n",
+                body
+            ))
+            .expect("parse program");
+            let err = type_check(&bad).unwrap_err().to_string();
+            assert!(err.contains(message), "{}", err);
+        }
+    }
+
+    #[test]
+    fn strings_support_length_index_slice_and_compare() {
+        let src = r#"
+fn main(): int {
+    const s = "hello"
+    print(s.length + 1)
+    print(s[1] + s.slice(-3))
+    print(s < "world" && 7.toString() != s)
+    return 0
+}
+"#;
+        let program = parse_program(src).expect("parse program");
+        let typed = type_check(&program).expect("strings check");
+        let c_code = codegen_c(&typed, Path::new("strings.vts"));
+        assert!(c_code.contains("VTS_UNUSED vts_string vts_v_s = VTS_STR(\"hello\");"));
+        assert!(c_code.contains("((int)vts_v_s.len)"));
+        assert!(c_code.contains("vts_str_at(vts_v_s, 1, \"strings.vts:5\")"));
+        assert!(c_code.contains("(vts_str_cmp(vts_v_s, VTS_STR(\"world\")) < 0)"));
+
+        let bad = parse_program(
+            "fn main(): int {\n    let s = \"a\"\n    s.length = 2\n    return s[true]\n}\n",
+        )
+        .expect("parse program");
+        let err = type_check(&bad).unwrap_err().to_string();
+        assert!(err.contains("読み取り専用"), "{}", err);
+
+        let formatted = format_program(&program);
+        assert!(formatted.contains("print(s[1] + s.slice(-3))"));
+    }
+
+    #[test]
+    fn template_literals_interpolate_values() {
+        let src = r#"
+fn main(): int {
+    const n = 2
+    print(`n=${n + 1} ok=${n > 1} "${`in ${n}`}"`)
+    return 0
+}
+"#;
+        let program = parse_program(src).expect("parse program");
+        let typed = type_check(&program).expect("template check");
+        let c_code = codegen_c(&typed, Path::new("template.vts"));
+        assert!(c_code.contains(
+            "vts_str_concat(VTS_STR(\"n=\"), vts_int_to_str(({ int vts_r0; if (__builtin_add_overflow(vts_v_n, 1, &vts_r0)) vts_panic("
+        ));
+        assert!(c_code.contains("vts_r0; }))), VTS_STR(\" ok=\"))"));
+        assert!(c_code.contains("VTS_STR(\"\\\"\")"));
+
+        let formatted = format_program(&program);
+        assert!(formatted.contains("print(`n=${n + 1} ok=${n > 1} \"${`in ${n}`}\"`)"));
+
+        let bad = parse_program("fn main(): int {\n    print(`${missing}`)\n    return 0\n}\n")
+            .expect("parse program");
+        let err = type_check(&bad).unwrap_err().to_string();
+        assert!(err.contains("未定義の変数 'missing'"), "{}", err);
+        let unterminated =
+            parse_program("fn main(): int {\n    print(`oops ${1}\n}\n").unwrap_err();
+        assert!(
+            unterminated
+                .to_string()
+                .contains("行 2: テンプレート文字列が閉じられていません")
+        );
+    }
+
+    #[test]
+    fn string_escapes_round_trip_and_emit_safe_c() {
+        let src = "fn main(): int {\n    print(\"a\\tb \\\"q\\\" \\\\ 100% \\u{e9}??=\\n\")\n    print(`x \\` \\${y} \\u{41}`)\n    return 0\n}\n";
+        let program = parse_program(src).expect("parse program");
+        let Stmt::Print(expr) = &program.functions[0].body[0].value else {
+            panic!("expected print");
+        };
+        let ExprKind::Str(text) = &expr.kind else {
+            panic!("expected string literal");
+        };
+        assert_eq!(text, "a\tb \"q\" \\ 100% \u{e9}??=\n");
+
+        let formatted = format_program(&program);
+        assert!(formatted.contains(r#"print("a\tb \"q\" \\ 100% é??=\n")"#));
+        assert!(formatted.contains(r"print(`x \` \${y} A`)"));
+        let reparsed = parse_program(&formatted).expect("reparse formatted");
+        assert_eq!(format_program(&reparsed), formatted);
+
+        let typed = type_check(&program).expect("escapes check");
+        let c_code = codegen_c(&typed, Path::new("escapes.vts"));
+        assert!(
+            c_code.contains(r#"vts_print(VTS_STR("a\011b \"q\" \\ 100% \303\251\?\?=\012"));"#)
+        );
+
+        let err = parse_program("fn main(): int {\n    print(\"\\q\")\n}\n").unwrap_err();
+        assert!(
+            err.to_string()
+                .contains("行 2: 不明なエスケープシーケンス '\\q' です")
+        );
+    }
+
+    #[test]
+    fn arrays_type_check_and_lower_to_vectors() {
+        let src = r#"
+fn main(): int {
+    const xs: int[] = []
+    xs.push(1)
+    const ys = [xs[0], none]
+    for y of ys {
+        print(y ?? 0)
+    }
+    return xs.length
+}
+"#;
+        let program = parse_program(src).expect("parse program");
+        let typed = type_check(&program).expect("arrays check");
+        let Stmt::Let { ty, .. } = &typed.functions[0].body[2].value else {
+            panic!("expected let");
+        };
+        assert_eq!(
+            ty.as_ref().map(|ty| ty.to_string()).as_deref(),
+            Some("int?[]")
+        );
+        let c_code = codegen_c(&typed, Path::new("arrays.vts"));
+        assert!(c_code.contains("VTS_UNUSED vts_array *vts_v_xs = ({ vts_array *vts_arr0 = vts_array_new(sizeof(int), 0); vts_arr0; });"));
+        assert!(c_code.contains("(*(int *)vts_array_at(vts_v_xs, 0, \"arrays.vts:5\"))"));
+        assert!(c_code.contains("VTS_UNUSED vts_opt_int vts_v_y = ((vts_opt_int *)"));
+
+        let formatted = format_program(&program);
+        assert!(formatted.contains("const ys = [xs[0], none]"));
+
+        let bad =
+            parse_program("fn main(): int {\n    const xs = [1]\n    xs = [2]\n    return 0\n}\n")
+                .expect("parse program");
+        let err = type_check(&bad).unwrap_err().to_string();
+        assert!(err.contains("const 'xs' には再代入できません"), "{}", err);
+
+        // elements are checked against the expected element type, and integer
+        // literals take the type of the other numeric elements
+        for (binding, expected) in [
+            ("const xs: float[] = [1.5, 2]", "float[]"),
+            ("const xs: i64[] = [1, 3000000000]", "i64[]"),
+            (
+                "const xs: { name: string }[] = [{ name: \"x\" }, { name: \"y\", age: 1 }]",
+                "{ name: string }[]",
+            ),
+            (
+                "const xs: ((int) => Box<int>)[] = [x => Box { value: x }]",
+                "((int) => Box<int>)[]",
+            ),
+            ("const xs = [1.5, 2]", "float[]"),
+            ("const xs = [1, 3000000000]", "i64[]"),
+            ("const xs = [1, \"a\"]", "(int | string)[]"),
+        ] {
+            let program = parse_program(&format!(
+                "struct Box<T> {{ value: T }}\nfn main(): int {{\n    {}\n    return 0\n}}\n",
+                binding
+            ))
+            .expect("parse program");
+            let typed = type_check(&program).unwrap_or_else(|err| panic!("{}: {}", binding, err));
+            let main = typed.functions.iter().find(|f| f.name == "main").unwrap();
+            let Stmt::Let { ty, .. } = &main.body[0].value else {
+                panic!("expected let");
+            };
+            assert_eq!(ty.as_ref().unwrap().to_string(), expected, "{}", binding);
+        }
+    }
+
+    #[test]
+    fn maps_and_sets_type_check_and_lower_to_hash_tables() {
+        let src = r#"
+fn main(): int {
+    const ages: Map<string, int> = new Map()
+    ages.set("volt", 3)
+    const ids = new Set<int>()
+    for id of ids {
+        print(id)
+    }
+    return ages.get("volt") ?? ids.size
+}
+"#;
+        let program = parse_program(src).expect("parse program");
+        let typed = type_check(&program).expect("collections check");
+        let c_code = codegen_c(&typed, Path::new("maps.vts"));
+        assert!(c_code.contains(
+            "VTS_UNUSED vts_map *vts_v_ages = vts_map_new(true, sizeof(vts_string), sizeof(int));"
+        ));
+        assert!(
+            c_code.contains("VTS_UNUSED vts_map *vts_v_ids = vts_map_new(false, sizeof(int), 0);")
+        );
+        assert!(c_code.contains("vts_map_entries(vts_v_ids, false)"));
+        assert!(c_code.contains("vts_map_find(vts_map"));
+
+        let formatted = format_program(&program);
+        assert!(formatted.contains("const ages: Map<string, int> = new Map()"));
+        assert!(formatted.contains("const ids = new Set<int>()"));
+
+        let bad = parse_program(
+            "fn main(): int {\n    const m = new Map<int, int>()\n    m.set(1, \"x\")\n    return 0\n}\n",
+        )
+        .expect("parse program");
+        let err = type_check(&bad).unwrap_err().to_string();
+        assert!(err.contains("行 3: 型が一致しません"), "{}", err);
+
+        let bad =
+            parse_program("fn main(): int {\n    const s = new Set<bool>()\n    return 0\n}\n")
+                .expect("parse program");
+        let err = type_check(&bad).unwrap_err().to_string();
+        assert!(
+            err.contains("Set の要素に使えるのは int か string だけです"),
+            "{}",
+            err
+        );
+    }
+
+    #[test]
+    fn objects_are_structural_and_lower_to_shape_structs() {
+        let src = r#"
+struct Point { x: int, y: int }
+
+fn show(p: { x: int }): int {
+    return p.x
+}
+
+fn main(): int {
+    const o = { y: 2, x: 1, label: "a" }
+    const p: Point = { x: o.x, y: o.y }
+    return show(o) + show(p)
+}
+"#;
+        let program = parse_program(src).expect("parse program");
+        let typed = type_check(&program).expect("objects check");
+        let Stmt::Let { ty, .. } = &typed.functions[1].body[0].value else {
+            panic!("expected let");
+        };
+        assert_eq!(
+            ty.as_ref().map(|ty| ty.to_string()).as_deref(),
+            Some("{ label: string, x: int, y: int }")
+        );
+        let c_code = codegen_c(&typed, Path::new("objects.vts"));
+        assert!(c_code.contains("typedef struct vts_obj_label_string_x_int_y_int {"));
+        assert!(c_code.contains(
+            "vts_f_show(({ vts_obj_label_string_x_int_y_int vts_cv0 = vts_v_o; (vts_obj_x_int){ .vts_v_x = vts_cv0.vts_v_x }; }))"
+        ));
+        assert!(c_code.contains("VTS_UNUSED vts_t_Point vts_v_p = ((vts_t_Point){ .vts_v_x = vts_v_o.vts_v_x, .vts_v_y = vts_v_o.vts_v_y });"));
+
+        let formatted = format_program(&program);
+        assert!(formatted.contains("fn show(p: { x: int }): int {"));
+        assert!(formatted.contains("const o = { y: 2, x: 1, label: \"a\" }"));
+
+        let bad = parse_program(
+            "struct P { x: int }\nfn main(): int {\n    const p: P = { x: 1, y: 2 }\n    return 0\n}\n",
+        )
+        .expect("parse program");
+        let err = type_check(&bad).unwrap_err().to_string();
+        assert!(
+            err.contains("型が一致しません: P が必要ですが { x: int, y: int } が渡されました"),
+            "{}",
+            err
+        );
+    }
+
+    #[test]
+    fn generics_are_inferred_and_monomorphized() {
+        let src = r#"
+struct Box<T> { value: T }
+
+fn first<T>(xs: T[]): T? {
+    if xs.length == 0 {
+        return none
+    }
+    return xs[0]
+}
+
+fn main(): int {
+    const b = Box { value: first(["a"]) }
+    print(b.value ?? "")
+    return first([1]) ?? 0
+}
+"#;
+        let program = parse_program(src).expect("parse program");
+        let typed = type_check(&program).expect("generics check");
+        let names: Vec<String> = typed
+            .functions
+            .iter()
+            .map(|func| format!("{}{:?}", func.name, func.type_args))
+            .collect();
+        assert_eq!(names, ["main[]", "first[String]", "first[Int]"]);
+        let c_code = codegen_c(&typed, Path::new("generics.vts"));
+        assert!(c_code.contains("vts_opt_string vts_f_first__string(vts_array *vts_v_xs);"));
+        assert!(c_code.contains("vts_opt_int vts_f_first__int(vts_array *vts_v_xs);"));
+        assert!(c_code.contains("typedef struct vts_t_Box__vts_opt_string {"));
+        assert!(!c_code.contains("Box__T"));
+
+        let formatted = format_program(&program);
+        assert!(formatted.contains("struct Box<T> { value: T }"));
+        assert!(formatted.contains("fn first<T>(xs: T[]): T? {"));
+
+        let bad = parse_program("fn add<T>(a: T, b: T): T {\n    return a + b\n}\n")
+            .expect("parse program");
+        let err = type_check(&bad).unwrap_err().to_string();
+        assert!(
+            err.contains("行 2: 演算子 + は T と T の組み合わせに使えません"),
+            "{}",
+            err
+        );
+
+        // unresolved names are errors, not types that accept anything
+        for (src, message) in [
+            (
+                "fn f(x: Foo): Foo {\n    return x\n}\n",
+                "行 1: 未定義の型 'Foo' です",
+            ),
+            (
+                "import { two } from \"./missing.vts\"\nfn main(): int {\n    return two()\n}\n",
+                "行 3: 未定義の関数 'two' です",
+            ),
+        ] {
+            let err = type_check(&parse_program(src).unwrap()).unwrap_err();
+            assert!(err.to_string().contains(message), "{}", err);
+        }
+    }
+
+    #[test]
+    fn numeric_types_take_literals_and_check_arithmetic() {
+        let src = r#"
+fn main(): int {
+    const big: i64 = 5000000000
+    let byte: u8 = 250
+    byte += 5
+    const ratio = float(byte) / 2
+    print(`${big + 1} ${ratio} ${int(2.5)}`)
+    for i in 0..big {
+        break
+    }
+    return 0
+}
+"#;
+        let program = parse_program(src).expect("parse program");
+        let typed = type_check(&program).expect("numeric check");
+        let types: Vec<String> = typed.functions[0]
+            .body
+            .iter()
+            .filter_map(|stmt| match &stmt.value {
+                Stmt::Let { ty, value, .. } => {
+                    Some(ty.clone().unwrap_or_else(|| value.ty().clone()).to_string())
+                }
+                _ => None,
+            })
+            .collect();
+        assert_eq!(types, ["i64", "u8", "float"]);
+        let c_code = codegen_c(&typed, Path::new("numbers.vts"));
+        assert!(c_code.contains("VTS_UNUSED int64_t vts_v_big = 5000000000LL;"));
+        assert!(c_code.contains("if (__builtin_add_overflow(vts_v_byte, 5, &vts_r0)) vts_panic("));
+        assert!(c_code.contains("VTS_UNUSED double vts_v_ratio = (((double)(vts_v_byte)) / 2.0);"));
+        assert!(c_code.contains("vts_float_to_str(vts_v_ratio)"));
+        assert!(c_code.contains("VTS_UNUSED const int64_t vts_v_i = "));
+
+        let formatted = format_program(&program);
+        assert!(formatted.contains("const ratio = float(byte) / 2"));
+
+        for (body, message) in [
+            (
+                "let b: u8 = 256",
+                "行 2: 整数リテラル 256 は u8 の範囲外です",
+            ),
+            (
+                "const a: i64 = 1\n    const b = 2\n    print(a + b)",
+                "行 4: 演算子 + は i64 と int の組み合わせに使えません",
+            ),
+            (
+                "print(1.5 % 2.0)",
+                "行 2: 演算子 % は float と float の組み合わせに使えません",
+            ),
+            (
+                "const n: u64 = 1\n    print(-n)",
+                "行 3: 単項演算子 - は u64 型に使えません",
+            ),
+        ] {
+            let bad = parse_program(&format!(
+                "fn main(): int {{\n    {}\n    return 0\n}}\n",
+                body
+            ))
+            .expect("parse program");
+            let err = type_check(&bad).unwrap_err().to_string();
+            assert!(err.contains(message), "{}", err);
+        }
+    }
+
+    #[test]
+    fn closures_capture_and_infer_parameter_types() {
+        let src = r#"
+fn main(): int {
+    let total = 0
+    const step = 2
+    const xs = [1, 2, 3]
+    xs.forEach(x => {
+        total += x * step
+    })
+    const inc: (int) => int = n => n + 1
+    print(inc(total))
+    return 0
+}
+"#;
+        let program = parse_program(src).expect("parse program");
+        let typed = type_check(&program).expect("closure check");
+        let body = &typed.functions[0].body;
+        assert!(matches!(
+            &body[0].value,
+            Stmt::Let { name, captured: true, .. } if name == "total"
+        ));
+        assert!(matches!(
+            &body[1].value,
+            Stmt::Let {
+                captured: false,
+                ..
+            }
+        ));
+        let c_code = codegen_c(&typed, Path::new("closures.vts"));
+        assert!(c_code.contains("VTS_UNUSED int *vts_v_total = (int *)vts_alloc(sizeof(int));"));
+        assert!(c_code.contains("{ int *vts_v_total; int vts_v_step; } vts_closure"));
+        assert!(c_code.contains(
+            "typedef struct vts_fn_int_to_int { void *env; int (*call)(void *, int); } vts_fn_int_to_int;"
+        ));
+        assert!(c_code.contains("vts_v_inc.call(vts_v_inc.env, (*vts_v_total))"));
+
+        let formatted = format_program(&program);
+        assert!(formatted.contains("xs.forEach(x => { total += x * step })"));
+        assert!(formatted.contains("const inc: (int) => int = n => n + 1"));
+
+        for (body, message) in [
+            ("const f = x => x", "行 2: 引数 'x' の型を推論できません"),
+            (
+                "let v: int? = 1\n    const f = () => { v = none }",
+                "行 3: 外側の変数 'v' は int? 型なので、クロージャの中からは代入できません",
+            ),
+            ("const n = 1\n    n(2)", "行 3: int 型の値は呼び出せません"),
+            (
+                "const xs = [1]\n    const ys = xs.map((x: string) => x)",
+                "行 3: map には (int) => .. 型の関数が必要ですが (string) => string が渡されました",
+            ),
+        ] {
+            let bad = parse_program(&format!(
+                "fn main(): int {{\n    {}\n    return 0\n}}\n",
+                body
+            ))
+            .expect("parse program");
+            let err = type_check(&bad).unwrap_err().to_string();
+            assert!(err.contains(message), "{}", err);
+        }
+    }
+
+    #[test]
+    fn methods_take_self_by_pointer() {
+        let src = r#"
+struct Counter { count: int }
+
+impl Counter {
+    fn new(): Counter {
+        return Counter { count: 0 }
+    }
+
+    fn get(self): int {
+        return self.count
+    }
+}
+
+fn Counter.bump(mut self, by: int) {
+    self.count += by
+}
+
+fn main(): int {
+    let c = Counter.new()
+    c.bump(2)
+    print(c.get())
+    return 0
+}
+"#;
+        let program = parse_program(src).expect("parse program");
+        let owners: Vec<String> = program.functions.iter().map(|f| f.full_name()).collect();
+        assert_eq!(
+            owners,
+            ["Counter.new", "Counter.get", "Counter.bump", "main"]
+        );
+        let typed = type_check(&program).expect("method check");
+        let c_code = codegen_c(&typed, Path::new("methods.vts"));
+        assert!(c_code.contains("vts_t_Counter vts_f_Counter_new(void);"));
+        assert!(c_code.contains("int vts_f_Counter_get(vts_t_Counter *self) {"));
+        assert!(c_code.contains("void vts_f_Counter_bump(vts_t_Counter *self, int vts_v_by);"));
+        assert!(c_code.contains(
+            "(*self).vts_v_count = ({ int vts_r0; if (__builtin_add_overflow((*self).vts_v_count, vts_v_by"
+        ));
+        assert!(c_code.contains("vts_f_Counter_bump(&(vts_v_c), 2)"));
+
+        let formatted = format_program(&program);
+        assert!(formatted.contains(
+            "impl Counter {\n    fn new(): Counter {\n        return Counter { count: 0 }\n    }\n"
+        ));
+        assert!(formatted.contains("    fn bump(mut self, by: int) {\n"));
+
+        for (body, message) in [
+            (
+                "const c = Counter.new()\n    c.bump(1)",
+                "行 7: Counter.bump は mut self を取るため、変更できない 'c' には呼び出せません",
+            ),
+            (
+                "Counter.get()",
+                "行 6: Counter.get は self を取るメソッドなので",
+            ),
+            (
+                "Counter.reset()",
+                "行 6: 構造体 Counter にメソッド reset はありません",
+            ),
+        ] {
+            let bad = parse_program(&format!(
+                "struct Counter {{ count: int }}\nfn Counter.new(): Counter {{ return Counter {{ count: 0 }} }}\nfn Counter.get(self): int {{ return self.count }}\nfn Counter.bump(mut self, by: int) {{ self.count += by }}\nfn main(): int {{\n    {}\n    return 0\n}}\n",
+                body
+            ))
+            .expect("parse program");
+            let err = type_check(&bad).unwrap_err().to_string();
+            assert!(err.contains(message), "{}", err);
+        }
+        let bad = parse_program(
+            "struct P { x: int }\nfn P.set(self) { self.x = 1 }\nfn main(): int { return 0 }\n",
+        )
+        .expect("parse program");
+        let err = type_check(&bad).unwrap_err().to_string();
+        assert!(err.contains("self は読み取り専用です"), "{}", err);
+    }
+
+    #[test]
+    fn interfaces_instantiate_per_struct() {
+        let src = r#"
+interface Writer { write(s: string): void, size(): int }
+
+struct Console { width: int }
+struct Silent {}
+
+impl Console {
+    fn write(self, s: string) {
+        print(s)
+    }
+
+    fn size(self): int {
+        return self.width
+    }
+}
+
+fn Silent.write(self, s: string) {}
+fn Silent.size(self): int { return 0 }
+
+fn emit(w: Writer, s: string): int {
+    w.write(s)
+    return w.size()
+}
+
+fn main(): int {
+    print(emit(Console { width: 80 }, "hi"))
+    print(emit(Silent {}, "hi"))
+    return 0
+}
+"#;
+        let program = parse_program(src).expect("parse program");
+        assert_eq!(program.interfaces[0].methods.len(), 2);
+        let typed = type_check(&program).expect("interface check");
+        let instances: Vec<String> = typed
+            .functions
+            .iter()
+            .filter(|f| f.name == "emit")
+            .map(|f| f.params[0].ty.to_string())
+            .collect();
+        assert_eq!(instances, ["Console", "Silent"]);
+        let c_code = codegen_c(&typed, Path::new("interfaces.vts"));
+        assert!(
+            c_code.contains("int vts_f_emit__Console(vts_t_Console vts_v_w, vts_string vts_v_s) {")
+        );
+        assert!(c_code.contains("(void)(vts_f_Console_write(&(vts_v_w), vts_v_s));"));
+        assert!(c_code.contains("vts_f_emit__Silent(((vts_t_Silent){0}), VTS_STR(\"hi\"))"));
+
+        let formatted = format_program(&program);
+        assert!(formatted.contains("interface Writer { write(s: string): void, size(): int }\n"));
+
+        for (decl, call, message) in [
+            (
+                "fn Console.write(self, s: int) {}",
+                "emit(Console {})",
+                "行 5: Console はインターフェース Writer を満たしていません: write の型が (string) => void ではなく (int) => void です, size(): int がありません",
+            ),
+            (
+                "",
+                "let w: Writer? = none",
+                "行 5: インターフェース Writer は関数の引数の型としてだけ使えます",
+            ),
+            (
+                "",
+                "emit(1)",
+                "行 5: int はインターフェース Writer を満たしていません",
+            ),
+        ] {
+            let bad = parse_program(&format!(
+                "interface Writer {{ write(s: string): void, size(): int }}\nstruct Console {{}}\n{}\nfn emit(w: Writer) {{ w.write(\"x\") }}\nfn main(): int {{ {}\n return 0 }}\n",
+                decl, call
+            ))
+            .expect("parse program");
+            let err = type_check(&bad).unwrap_err().to_string();
+            assert!(err.contains(message), "{}", err);
+        }
+    }
+
+    #[test]
+    fn tuples_and_destructuring() {
+        let src = r#"
+struct Point { x: int, y: int }
+
+fn split(s: string): (string, int) {
+    return (s, s.length)
+}
+
+fn area({ x, y }: Point): int {
+    return x * y
+}
+
+fn main(): int {
+    const (word, size) = split("volt")
+    const { x, y: down } = Point { x: 1, y: 2 }
+    let [a, _] = [x, down]
+    const ages = new Map<string, int>()
+    for (name, age) of ages {
+        print(name + age.toString())
+    }
+    print(split(word).1 + size + a + area(Point { x: 2, y: 3 }))
+    return 0
+}
+"#;
+        let program = parse_program(src).expect("parse program");
+        let typed = type_check(&program).expect("tuple check");
+        let c_code = codegen_c(&typed, Path::new("tuples.vts"));
+        assert!(c_code.contains("typedef struct vts_tuple_string_int {"));
+        assert!(c_code.contains(
+            "return ((vts_tuple_string_int){ .f0 = vts_v_s, .f1 = ((int)vts_v_s.len) });"
+        ));
+        assert!(c_code.contains("VTS_UNUSED int vts_v_down = vts_v_vts_destructure"));
+        assert!(c_code.contains("int vts_f_area(vts_t_Point vts_v_vts_arg0) {"));
+
+        let formatted = format_program(&program);
+        assert!(formatted.contains("fn split(s: string): (string, int) {\n"));
+        assert!(formatted.contains("fn area({ x, y }: Point): int {\n"));
+        assert!(formatted.contains("const { x, y: down } = Point { x: 1, y: 2 }\n"));
+        assert!(formatted.contains("let [a, _] = [x, down]\n"));
+        assert!(formatted.contains("for (name, age) of ages {"));
+
+        for (stmt, message) in [
+            (
+                "const (a, b) = (1, 2, 3)",
+                "行 2: (int, int, int) 型の値は 3 個の要素を持ちますが、(a, b) は 2 個の要素を取り出します",
+            ),
+            (
+                "const { z } = { x: 1 }",
+                "行 2: { x: int } 型にフィールド 'z' はありません",
+            ),
+            (
+                "const [a] = (1, 2)",
+                "行 2: (int, int) 型の値は [a] として分割代入できません (配列が必要です)",
+            ),
+            (
+                "const t = (1, none)",
+                "行 2: タプルの要素の型を決められません。't' に型注釈を書いてください",
+            ),
+            (
+                "const t = (1, 2)\nprint(t.2)",
+                "行 3: (int, int) 型にはフィールド '2' がありません",
+            ),
+        ] {
+            let bad = parse_program(&format!("fn main(): int {{\n{}\nreturn 0 }}\n", stmt))
+                .expect("parse program");
+            let err = type_check(&bad).unwrap_err().to_string();
+            assert!(err.contains(message), "{}", err);
+        }
+    }
 }
@@ -0,0 +1,1462 @@
+// --- Parser: token stream -> AST (recursive descent) ---
+use anyhow::{Result, anyhow};
+
+use crate::ast::{
+    BinaryOp, BindingPattern, Block, Closure, ClosureBody, EnumDecl, Expr, ExprKind, Field,
+    Function, Import, InterfaceDecl, InterfaceMethod, MatchArm, OPTIONAL_RECEIVER, Param, Pattern,
+    Program, Range, Receiver, Spanned, Stmt, StructDecl, TemplatePart, TypeAlias, TypeAnnotation,
+    UnaryOp, Variant,
+};
+use crate::lexer::{TemplateToken, Token, tokenize};
+
+pub fn parse_program(source: &str) -> Result<Program> {
+    let tokens = tokenize(source)?;
+    Parser::new(tokens).parse_program()
+}
+
+/// Maps a type name to its annotation. Names other than the builtins become
+/// `Named`; the checker resolves them against the declared structs.
+pub fn parse_type_annotation(raw: &str) -> TypeAnnotation {
+    match raw {
+        "int" => TypeAnnotation::Int,
+        "i64" => TypeAnnotation::I64,
+        "u64" => TypeAnnotation::U64,
+        "u8" => TypeAnnotation::U8,
+        "float" | "f64" => TypeAnnotation::Float,
+        "string" => TypeAnnotation::String,
+        "bool" => TypeAnnotation::Bool,
+        "void" => TypeAnnotation::Void,
+        other => TypeAnnotation::Named(other.to_string()),
+    }
+}
+
+/// Parses the `${...}` token streams of a template literal.
+fn parse_template(parts: Vec<TemplateToken>) -> Result<Vec<TemplatePart>> {
+    parts
+        .into_iter()
+        .map(|part| match part {
+            TemplateToken::Text(text) => Ok(TemplatePart::Text(text)),
+            TemplateToken::Expr(tokens) => {
+                let mut parser = Parser::new(tokens);
+                let expr = parser.parse_expr()?;
+                if !parser.check(&Token::Eof) {
+                    return Err(parser.unexpected("'}'"));
+                }
+                Ok(TemplatePart::Expr(expr))
+            }
+        })
+        .collect()
+}
+
+/// Precedence of `x is T` (same as `<`).
+pub const IS_PRECEDENCE: u8 = 5;
+
+struct Parser {
+    tokens: Vec<Spanned<Token>>,
+    pos: usize,
+    /// Set while parsing `if`/`while`/`for` heads, where `Name {` opens the
+    /// body rather than a struct literal (parentheses lift the restriction).
+    no_struct_literal: bool,
+}
+
+impl Parser {
+    fn new(tokens: Vec<Spanned<Token>>) -> Self {
+        Self {
+            tokens,
+            pos: 0,
+            no_struct_literal: false,
+        }
+    }
+
+    fn peek(&self) -> &Token {
+        &self.tokens[self.pos].value
+    }
+
+    fn peek_at(&self, offset: usize) -> &Token {
+        let idx = (self.pos + offset).min(self.tokens.len() - 1);
+        &self.tokens[idx].value
+    }
+
+    fn line(&self) -> usize {
+        self.tokens[self.pos].line
+    }
+
+    /// Line of the most recently consumed token.
+    fn prev_line(&self) -> usize {
+        self.tokens[self.pos.saturating_sub(1)].line
+    }
+
+    fn advance(&mut self) -> Token {
+        let token = self.tokens[self.pos].value.clone();
+        if self.pos + 1 < self.tokens.len() {
+            self.pos += 1;
+        }
+        token
+    }
+
+    fn check(&self, token: &Token) -> bool {
+        self.peek() == token
+    }
+
+    fn eat(&mut self, token: &Token) -> bool {
+        if self.check(token) {
+            self.advance();
+            true
+        } else {
+            false
+        }
+    }
+
+    fn expect(&mut self, token: &Token) -> Result<()> {
+        if self.eat(token) {
+            Ok(())
+        } else {
+            Err(self.unexpected(&format!("'{}'", token)))
+        }
+    }
+
+    fn expect_ident(&mut self) -> Result<String> {
+        match self.peek().clone() {
+            Token::Ident(name) => {
+                self.advance();
+                Ok(name)
+            }
+            _ => Err(self.unexpected("識別子")),
+        }
+    }
+
+    fn expect_str(&mut self) -> Result<String> {
+        match self.peek().clone() {
+            Token::Str(text) => {
+                self.advance();
+                Ok(text)
+            }
+            _ => Err(self.unexpected("文字列リテラル")),
+        }
+    }
+
+    fn unexpected(&self, expected: &str) -> anyhow::Error {
+        anyhow!(
+            "行 {}: {} が必要ですが '{}' が見つかりました",
+            self.line(),
+            expected,
+            self.peek()
+        )
+    }
+
+    fn skip_semicolons(&mut self) {
+        while self.eat(&Token::Semicolon) {}
+    }
+
+    fn parse_program(mut self) -> Result<Program> {
+        let mut imports = Vec::new();
+        let mut structs = Vec::new();
+        let mut enums = Vec::new();
+        let mut aliases = Vec::new();
+        let mut interfaces = Vec::new();
+        let mut functions = Vec::new();
+
+        loop {
+            self.skip_semicolons();
+            match (self.peek(), self.peek_at(1)) {
+                (Token::Eof, _) => break,
+                (Token::Import, _) => imports.push(self.parse_import()?),
+                (Token::Struct, _) | (Token::Export, Token::Struct) => {
+                    structs.push(self.parse_struct()?)
+                }
+                (Token::Enum, _) | (Token::Export, Token::Enum) => enums.push(self.parse_enum()?),
+                (Token::Ident(kw), _) if kw == "type" => aliases.push(self.parse_type_alias()?),
+                (Token::Export, Token::Ident(kw)) if kw == "type" => {
+                    aliases.push(self.parse_type_alias()?)
+                }
+                (Token::Ident(kw), Token::Ident(_)) if kw == "impl" => {
+                    functions.extend(self.parse_impl()?)
+                }
+                (Token::Ident(kw), Token::Ident(_)) if kw == "interface" => {
+                    interfaces.push(self.parse_interface()?)
+                }
+                (Token::Export, Token::Ident(kw)) if kw == "interface" => {
+                    interfaces.push(self.parse_interface()?)
+                }
+                (Token::Export | Token::Async | Token::Fn, _) => {
+                    functions.push(self.parse_function(None)?)
+                }
+                _ => {
+                    return Err(
+                        self.unexpected("import / struct / enum / type / interface / impl / fn")
+                    );
+                }
+            }
+        }
+
+        Ok(Program {
+            imports,
+            structs,
+            enums,
+            aliases,
+            interfaces,
+            functions,
+        })
+    }
+
+    /// `type Name = <type>`; `type` is only a keyword at the top level.
+    fn parse_type_alias(&mut self) -> Result<TypeAlias> {
+        let line = self.line();
+        self.eat(&Token::Export);
+        self.advance();
+        let name = self.expect_ident()?;
+        self.expect(&Token::Eq)?;
+        let ty = self.parse_type()?;
+        Ok(TypeAlias { name, ty, line })
+    }
+
+    /// `struct Name { field: type, ... }`; fields are separated by `,`, `;` or newlines.
+    fn parse_struct(&mut self) -> Result<StructDecl> {
+        let line = self.line();
+        self.eat(&Token::Export);
+        self.expect(&Token::Struct)?;
+        let name = self.expect_ident()?;
+        let type_params = self.parse_type_params()?;
+        self.expect(&Token::LBrace)?;
+        let mut fields = Vec::new();
+        loop {
+            while self.eat(&Token::Comma) || self.eat(&Token::Semicolon) {}
+            if self.eat(&Token::RBrace) {
+                break;
+            }
+            let line = self.line();
+            let name = self.expect_ident()?;
+            self.expect(&Token::Colon)?;
+            let ty = self.parse_type()?;
+            fields.push(Field { name, ty, line });
+            if !matches!(self.peek(), Token::Comma | Token::Semicolon | Token::RBrace)
+                && self.line() == self.prev_line()
+            {
+                return Err(self.unexpected("',' もしくは '}'"));
+            }
+        }
+        Ok(StructDecl {
+            name,
+            type_params,
+            type_args: Vec::new(),
+            fields,
+            line,
+        })
+    }
+
+    /// `enum Name { Unit, WithPayload(int, string) }`; variants are separated by
+    /// `,`, `;` or newlines.
+    fn parse_enum(&mut self) -> Result<EnumDecl> {
+        let line = self.line();
+        self.eat(&Token::Export);
+        self.expect(&Token::Enum)?;
+        let name = self.expect_ident()?;
+        self.expect(&Token::LBrace)?;
+        let mut variants = Vec::new();
+        loop {
+            while self.eat(&Token::Comma) || self.eat(&Token::Semicolon) {}
+            if self.eat(&Token::RBrace) {
+                break;
+            }
+            let line = self.line();
+            let name = self.expect_ident()?;
+            let mut fields = Vec::new();
+            if self.eat(&Token::LParen) {
+                while !self.check(&Token::RParen) {
+                    fields.push(self.parse_type()?);
+                    if !self.eat(&Token::Comma) {
+                        break;
+                    }
+                }
+                self.expect(&Token::RParen)?;
+            }
+            variants.push(Variant { name, fields, line });
+            if !matches!(self.peek(), Token::Comma | Token::Semicolon | Token::RBrace)
+                && self.line() == self.prev_line()
+            {
+                return Err(self.unexpected("',' もしくは '}'"));
+            }
+        }
+        Ok(EnumDecl {
+            name,
+            variants,
+            line,
+        })
+    }
+
+    /// `<T, U>` after the name of a generic function or struct.
+    fn parse_type_params(&mut self) -> Result<Vec<String>> {
+        let mut params = Vec::new();
+        if !self.eat(&Token::Lt) {
+            return Ok(params);
+        }
+        loop {
+            params.push(self.expect_ident()?);
+            if !self.eat(&Token::Comma) {
+                break;
+            }
+        }
+        self.expect(&Token::Gt)?;
+        Ok(params)
+    }
+
+    fn parse_import(&mut self) -> Result<Import> {
+        let line = self.line();
+        self.expect(&Token::Import)?;
+        self.expect(&Token::LBrace)?;
+        let mut names = Vec::new();
+        while !self.check(&Token::RBrace) {
+            names.push(self.expect_ident()?);
+            if !self.eat(&Token::Comma) {
+                break;
+            }
+        }
+        self.expect(&Token::RBrace)?;
+        if names.is_empty() {
+            return Err(anyhow!(
+                "import must list at least one name at line {}",
+                line
+            ));
+        }
+        self.expect(&Token::From)?;
+        let module = self.expect_str()?;
+        if module.is_empty() {
+            return Err(anyhow!("import module path is empty at line {}", line));
+        }
+        Ok(Import { names, module })
+    }
+
+    /// `interface Name { method(a: int): string ... }`; the methods are
+    /// separated by `,`, `;` or newlines. `interface` is only a keyword at the
+    /// top level.
+    fn parse_interface(&mut self) -> Result<InterfaceDecl> {
+        let line = self.line();
+        self.eat(&Token::Export);
+        self.advance();
+        let name = self.expect_ident()?;
+        self.expect(&Token::LBrace)?;
+        let mut methods = Vec::new();
+        loop {
+            while self.eat(&Token::Comma) || self.eat(&Token::Semicolon) {}
+            if self.eat(&Token::RBrace) {
+                break;
+            }
+            let line = self.line();
+            let name = self.expect_ident()?;
+            let (_, params) = self.parse_params(false)?;
+            let return_type = if self.eat(&Token::Colon) {
+                Some(self.parse_type()?)
+            } else {
+                None
+            };
+            methods.push(InterfaceMethod {
+                name,
+                params,
+                return_type,
+                line,
+            });
+            if !matches!(self.peek(), Token::Comma | Token::Semicolon | Token::RBrace)
+                && self.line() == self.prev_line()
+            {
+                return Err(self.unexpected("',' もしくは '}'"));
+            }
+        }
+        Ok(InterfaceDecl {
+            name,
+            methods,
+            line,
+        })
+    }
+
+    /// `impl Point { fn dist(self): float { .. } .. }`: methods of `Point`;
+    /// `impl` is only a keyword at the top level.
+    fn parse_impl(&mut self) -> Result<Vec<Function>> {
+        self.advance();
+        let owner = self.expect_ident()?;
+        self.expect(&Token::LBrace)?;
+        let mut methods = Vec::new();
+        loop {
+            self.skip_semicolons();
+            if self.eat(&Token::RBrace) {
+                break;
+            }
+            if !matches!(self.peek(), Token::Async | Token::Fn) {
+                return Err(self.unexpected("fn もしくは '}'"));
+            }
+            methods.push(self.parse_function(Some(owner.clone()))?);
+        }
+        Ok(methods)
+    }
+
+    /// `fn name(..)`, or a method: `fn Point.dist(self)` at the top level, or
+    /// `fn dist(self)` inside `impl Point` (`owner`).
+    fn parse_function(&mut self, mut owner: Option<String>) -> Result<Function> {
+        let line = self.line();
+        self.eat(&Token::Export);
+        let is_async = self.eat(&Token::Async);
+        self.expect(&Token::Fn)?;
+        let mut name = self.expect_ident()?;
+        if owner.is_none() && self.eat(&Token::Dot) {
+            owner = Some(std::mem::replace(&mut name, self.expect_ident()?));
+        }
+        let type_params = self.parse_type_params()?;
+        let (receiver, params) = self.parse_params(owner.is_some())?;
+
+        let return_type = if self.eat(&Token::Colon) {
+            Some(self.parse_type()?)
+        } else {
+            None
+        };
+
+        let body = self.parse_block()?;
+
+        Ok(Function {
+            name,
+            owner,
+            receiver,
+            type_params,
+            type_args: Vec::new(),
+            params,
+            return_type,
+            body,
+            is_async,
+            line,
+            source: None,
+        })
+    }
+
+    /// `(a: int, b: string)` — every parameter needs a type annotation. A
+    /// method may start with `self` or `mut self`. A destructured parameter
+    /// (`(a, b): (int, int)`) is passed as `vts_arg<position>`.
+    fn parse_params(&mut self, is_method: bool) -> Result<(Option<Receiver>, Vec<Param>)> {
+        self.expect(&Token::LParen)?;
+        let mut receiver = None;
+        if is_method {
+            match (self.peek(), self.peek_at(1)) {
+                (Token::Ident(name), _) if name == "self" => {
+                    self.advance();
+                    receiver = Some(Receiver::Shared);
+                }
+                (Token::Ident(kw), Token::Ident(name)) if kw == "mut" && name == "self" => {
+                    self.advance();
+                    self.advance();
+                    receiver = Some(Receiver::Mut);
+                }
+                _ => {}
+            }
+            if receiver.is_some() && !self.eat(&Token::Comma) {
+                self.expect(&Token::RParen)?;
+                return Ok((receiver, Vec::new()));
+            }
+        }
+        let mut params = Vec::new();
+        while !self.eat(&Token::RParen) {
+            let line = self.line();
+            let (name, pattern) = self.parse_param_name(params.len())?;
+            self.expect(&Token::Colon)?;
+            let ty = self.parse_type()?;
+            params.push(Param {
+                name,
+                ty,
+                line,
+                captured: false,
+                pattern,
+            });
+            if !self.eat(&Token::Comma) {
+                self.expect(&Token::RParen)?;
+                break;
+            }
+        }
+        Ok((receiver, params))
+    }
+
+    /// A parameter's name, or the hidden name of the parameter at `position`
+    /// with the pattern that destructures it.
+    fn parse_param_name(&mut self, position: usize) -> Result<(String, Option<BindingPattern>)> {
+        match self.parse_binding_pattern()? {
+            BindingPattern::Name(name) => Ok((name, None)),
+            pattern => Ok((format!("vts_arg{}", position), Some(pattern))),
+        }
+    }
+
+    fn parse_block(&mut self) -> Result<Block> {
+        self.expect(&Token::LBrace)?;
+        let mut stmts = Vec::new();
+        loop {
+            self.skip_semicolons();
+            if self.eat(&Token::RBrace) {
+                break;
+            }
+            let line = self.line();
+            stmts.push(Spanned {
+                value: self.parse_stmt()?,
+                line,
+            });
+        }
+        Ok(stmts)
+    }
+
+    fn parse_stmt(&mut self) -> Result<Stmt> {
+        if self.eat(&Token::Await) {
+            let inner = self.parse_stmt()?;
+            return Ok(Stmt::Await(Box::new(inner)));
+        }
+
+        match self.peek() {
+            Token::If => self.parse_if(),
+            Token::Match => {
+                let (scrutinee, arms) = self.parse_match(|p| {
+                    if p.check(&Token::LBrace) {
+                        p.parse_block()
+                    } else {
+                        let line = p.line();
+                        Ok(vec![Spanned {
+                            value: p.parse_stmt()?,
+                            line,
+                        }])
+                    }
+                })?;
+                Ok(Stmt::Match { scrutinee, arms })
+            }
+            Token::While | Token::For => self.parse_loop(None),
+            Token::Ident(label) if self.peek_at(1) == &Token::Colon => {
+                let label = label.clone();
+                self.advance();
+                self.advance();
+                if !matches!(self.peek(), Token::While | Token::For) {
+                    return Err(self.unexpected("ラベルの後には while または for"));
+                }
+                self.parse_loop(Some(label))
+            }
+            Token::Break | Token::Continue => {
+                let is_break = self.advance() == Token::Break;
+                // A label must be on the same line as the keyword.
+                let label = match self.peek() {
+                    Token::Ident(name) if self.line() == self.prev_line() => {
+                        let name = name.clone();
+                        self.advance();
+                        Some(name)
+                    }
+                    _ => None,
+                };
+                Ok(if is_break {
+                    Stmt::Break(label)
+                } else {
+                    Stmt::Continue(label)
+                })
+            }
+            Token::Return => {
+                self.advance();
+                // A value must start on the same line as `return`.
+                let ends_here =
+                    matches!(self.peek(), Token::RBrace | Token::Semicolon | Token::Eof)
+                        || self.line() != self.prev_line();
+                let value = if ends_here {
+                    None
+                } else {
+                    Some(self.parse_expr()?)
+                };
+                Ok(Stmt::Return(value))
+            }
+            Token::Let | Token::Const => self.parse_let(),
+            Token::Ident(_) => self.parse_ident_stmt(),
+            _ => self.parse_expr_stmt(),
+        }
+    }
+
+    /// A type name, a string literal type (`"ok"`) or a union of those
+    /// (`int | string`). Unions are normalized by the checker.
+    fn parse_type(&mut self) -> Result<TypeAnnotation> {
+        let mut members = vec![self.parse_type_member()?];
+        while self.eat(&Token::Pipe) {
+            members.push(self.parse_type_member()?);
+        }
+        if members.len() == 1 {
+            return Ok(members.remove(0));
+        }
+        Ok(TypeAnnotation::Union(members))
+    }
+
+    /// `int`, `"ok"`, a parenthesized type or a tuple type `(int, string)`,
+    /// each optionally followed by `?` (nullable) and `[]` (array) suffixes; or
+    /// a function type `(int, string) => bool`, whose return type extends as
+    /// far as possible.
+    fn parse_type_member(&mut self) -> Result<TypeAnnotation> {
+        let mut ty = match self.peek().clone() {
+            Token::Str(text) => {
+                self.advance();
+                TypeAnnotation::Literal(text)
+            }
+            Token::LParen => {
+                self.advance();
+                let mut types = Vec::new();
+                while !self.check(&Token::RParen) {
+                    // parameter names are allowed and ignored: `(x: int) => int`
+                    if matches!(self.peek(), Token::Ident(_)) && self.peek_at(1) == &Token::Colon {
+                        self.advance();
+                        self.advance();
+                    }
+                    types.push(self.parse_type()?);
+                    if !self.eat(&Token::Comma) {
+                        break;
+                    }
+                }
+                self.expect(&Token::RParen)?;
+                if self.eat(&Token::FatArrow) {
+                    let ret = self.parse_type()?;
+                    return Ok(TypeAnnotation::Function(types, Box::new(ret)));
+                }
+                match types.len() {
+                    0 => return Err(self.unexpected("=>")),
+                    1 => types.remove(0),
+                    _ => TypeAnnotation::Tuple(types),
+                }
+            }
+            Token::LBrace => {
+                self.advance();
+                self.parse_object_type()?
+            }
+            _ => {
+                let name = self.expect_ident()?;
+                match name.as_str() {
+                    "Result" => self.parse_result_type()?,
+                    "Array" | "Map" | "Set" => {
+                        self.expect(&Token::Lt)?;
+                        self.parse_generic_type(&name)?
+                    }
+                    _ if self.eat(&Token::Lt) => {
+                        let mut args = Vec::new();
+                        loop {
+                            args.push(self.parse_type()?);
+                            if !self.eat(&Token::Comma) {
+                                break;
+                            }
+                        }
+                        self.expect(&Token::Gt)?;
+                        TypeAnnotation::Generic(name, args)
+                    }
+                    _ => parse_type_annotation(&name),
+                }
+            }
+        };
+        loop {
+            if self.eat(&Token::Question) {
+                ty = TypeAnnotation::Nullable(Box::new(ty));
+            } else if self.check(&Token::LBracket) && self.peek_at(1) == &Token::RBracket {
+                self.advance();
+                self.advance();
+                ty = TypeAnnotation::Array(Box::new(ty));
+            } else {
+                return Ok(ty);
+            }
+        }
+    }
+
+    /// `name: type, ...}` after the opening brace of an object type; fields are
+    /// separated by `,` or `;` like in a struct declaration.
+    fn parse_object_type(&mut self) -> Result<TypeAnnotation> {
+        let mut fields = Vec::new();
+        loop {
+            while self.eat(&Token::Comma) || self.eat(&Token::Semicolon) {}
+            if self.eat(&Token::RBrace) {
+                break;
+            }
+            let name = self.expect_ident()?;
+            self.expect(&Token::Colon)?;
+            fields.push((name, self.parse_type()?));
+            if !matches!(self.peek(), Token::Comma | Token::Semicolon | Token::RBrace)
+                && self.line() == self.prev_line()
+            {
+                return Err(self.unexpected("',' もしくは '}'"));
+            }
+        }
+        Ok(TypeAnnotation::Object(fields))
+    }
+
+    /// The type arguments of `Array<T>`, `Map<K, V>` or `Set<T>` after the `<`.
+    fn parse_generic_type(&mut self, name: &str) -> Result<TypeAnnotation> {
+        let first = Box::new(self.parse_type()?);
+        let ty = match name {
+            "Map" => {
+                self.expect(&Token::Comma)?;
+                TypeAnnotation::Map(first, Box::new(self.parse_type()?))
+            }
+            "Set" => TypeAnnotation::Set(first),
+            _ => TypeAnnotation::Array(first),
+        };
+        self.expect(&Token::Gt)?;
+        Ok(ty)
+    }
+
+    /// `new Map<K, V>()` / `new Set<T>()` after `new`; the type arguments may be
+    /// left to the checker (`const m: Map<string, int> = new Map()`).
+    fn parse_new(&mut self) -> Result<ExprKind> {
+        let name = self.expect_ident()?;
+        if name != "Map" && name != "Set" {
+            return Err(anyhow!(
+                "行 {}: new で作れるのは Map と Set だけです: {}",
+                self.prev_line(),
+                name
+            ));
+        }
+        let ty = if self.eat(&Token::Lt) {
+            self.parse_generic_type(&name)?
+        } else if name == "Map" {
+            TypeAnnotation::Map(
+                Box::new(TypeAnnotation::None),
+                Box::new(TypeAnnotation::None),
+            )
+        } else {
+            TypeAnnotation::Set(Box::new(TypeAnnotation::None))
+        };
+        self.expect(&Token::LParen)?;
+        self.expect(&Token::RParen)?;
+        Ok(ExprKind::NewCollection(ty))
+    }
+
+    /// `<T, E>` after `Result`.
+    fn parse_result_type(&mut self) -> Result<TypeAnnotation> {
+        if !self.eat(&Token::Lt) {
+            return Err(anyhow!(
+                "行 {}: Result には型引数が必要です (例: Result<int, string>)",
+                self.line()
+            ));
+        }
+        let value = self.parse_type()?;
+        self.expect(&Token::Comma)?;
+        let error = self.parse_type()?;
+        self.expect(&Token::Gt)?;
+        Ok(TypeAnnotation::Result(Box::new(value), Box::new(error)))
+    }
+
+    fn parse_let(&mut self) -> Result<Stmt> {
+        let mutable = match self.advance() {
+            Token::Let => true,
+            Token::Const => false,
+            _ => unreachable!("parse_let is only called on let/const"),
+        };
+        let pattern = self.parse_binding_pattern()?;
+        let ty = if self.eat(&Token::Colon) {
+            Some(self.parse_type()?)
+        } else {
+            None
+        };
+        self.expect(&Token::Eq)?;
+        let value = self.parse_expr()?;
+        let BindingPattern::Name(name) = pattern else {
+            return Ok(Stmt::Destructure {
+                pattern,
+                mutable,
+                ty,
+                value,
+            });
+        };
+        Ok(Stmt::Let {
+            name,
+            mutable,
+            ty,
+            value,
+            captured: false,
+        })
+    }
+
+    /// `name`, `(a, b)`, `[a, b]` or `{ x, y: other }`, nested freely: what
+    /// `let` / `const`, a parameter or a `for .. of` loop binds.
+    fn parse_binding_pattern(&mut self) -> Result<BindingPattern> {
+        let close = match self.peek() {
+            Token::LParen => Token::RParen,
+            Token::LBracket => Token::RBracket,
+            Token::LBrace => Token::RBrace,
+            _ => return Ok(BindingPattern::Name(self.expect_ident()?)),
+        };
+        self.advance();
+        if close == Token::RBrace {
+            let mut fields = Vec::new();
+            while !self.check(&Token::RBrace) {
+                let field = self.expect_ident()?;
+                let pattern = if self.eat(&Token::Colon) {
+                    self.parse_binding_pattern()?
+                } else {
+                    BindingPattern::Name(field.clone())
+                };
+                fields.push((field, pattern));
+                if !self.eat(&Token::Comma) {
+                    break;
+                }
+            }
+            self.expect(&Token::RBrace)?;
+            return Ok(BindingPattern::Record(fields));
+        }
+        let mut items = Vec::new();
+        while !self.check(&close) {
+            items.push(self.parse_binding_pattern()?);
+            if !self.eat(&Token::Comma) {
+                break;
+            }
+        }
+        self.expect(&close)?;
+        Ok(match close {
+            Token::RParen => BindingPattern::Tuple(items),
+            _ => BindingPattern::Array(items),
+        })
+    }
+
+    /// Expression statement, or an assignment when followed by `=` / `op=`.
+    fn parse_expr_stmt(&mut self) -> Result<Stmt> {
+        let expr = self.parse_expr()?;
+        let op = match self.peek() {
+            Token::Eq => None,
+            Token::PlusEq => Some(BinaryOp::Add),
+            Token::MinusEq => Some(BinaryOp::Sub),
+            Token::StarEq => Some(BinaryOp::Mul),
+            Token::SlashEq => Some(BinaryOp::Div),
+            Token::PercentEq => Some(BinaryOp::Rem),
+            _ => return Ok(Stmt::Expr(expr)),
+        };
+        self.advance();
+        let value = self.parse_expr()?;
+        Ok(Stmt::Assign {
+            target: expr,
+            op,
+            value,
+        })
+    }
+
+    fn parse_if(&mut self) -> Result<Stmt> {
+        self.expect(&Token::If)?;
+        let condition = self.parse_head_expr()?;
+        let then_branch = self.parse_block()?;
+        let else_branch = if self.eat(&Token::Else) {
+            if self.check(&Token::If) {
+                // `else if` is sugar for an else block holding a single `if`
+                let line = self.line();
+                vec![Spanned {
+                    value: self.parse_if()?,
+                    line,
+                }]
+            } else {
+                self.parse_block()?
+            }
+        } else {
+            Vec::new()
+        };
+        Ok(Stmt::If {
+            condition,
+            then_branch,
+            else_branch,
+        })
+    }
+
+    /// `match scrutinee { pattern => body, ... }`; arms are separated by `,` or
+    /// newlines and `parse_body` reads what follows `=>`.
+    fn parse_match<T>(
+        &mut self,
+        mut parse_body: impl FnMut(&mut Self) -> Result<T>,
+    ) -> Result<(Expr, Vec<MatchArm<T>>)> {
+        self.expect(&Token::Match)?;
+        let scrutinee = self.parse_head_expr()?;
+        self.expect(&Token::LBrace)?;
+        let mut arms = Vec::new();
+        loop {
+            while self.eat(&Token::Comma) || self.eat(&Token::Semicolon) {}
+            if self.eat(&Token::RBrace) {
+                break;
+            }
+            let line = self.line();
+            let pattern = self.parse_pattern()?;
+            self.expect(&Token::FatArrow)?;
+            let body = self.with_struct_literals(true, &mut parse_body)?;
+            arms.push(MatchArm {
+                pattern,
+                body,
+                line,
+            });
+        }
+        Ok((scrutinee, arms))
+    }
+
+    fn parse_pattern(&mut self) -> Result<Pattern> {
+        if let Token::Str(text) = self.peek().clone() {
+            self.advance();
+            return Ok(Pattern::Literal(text));
+        }
+        let first = self.expect_ident()?;
+        if first == "_" {
+            return Ok(Pattern::Wildcard);
+        }
+        let (enum_name, name) = if self.eat(&Token::Dot) {
+            (Some(first), self.expect_ident()?)
+        } else {
+            (None, first)
+        };
+        let mut bindings = Vec::new();
+        if self.eat(&Token::LParen) {
+            while !self.check(&Token::RParen) {
+                bindings.push(self.expect_ident()?);
+                if !self.eat(&Token::Comma) {
+                    break;
+                }
+            }
+            self.expect(&Token::RParen)?;
+        }
+        Ok(Pattern::Variant {
+            enum_name,
+            name,
+            bindings,
+        })
+    }
+
+    fn parse_loop(&mut self, label: Option<String>) -> Result<Stmt> {
+        if self.eat(&Token::While) {
+            let condition = self.parse_head_expr()?;
+            let body = self.parse_block()?;
+            return Ok(Stmt::While {
+                label,
+                condition,
+                body,
+            });
+        }
+        self.parse_for(label)
+    }
+
+    fn parse_for(&mut self, label: Option<String>) -> Result<Stmt> {
+        self.expect(&Token::For)?;
+        let var = self.parse_binding_pattern()?;
+        if matches!(self.peek(), Token::Ident(word) if word == "of") {
+            self.advance();
+            let iterable = self.parse_head_expr()?;
+            let body = self.parse_block()?;
+            return Ok(Stmt::ForOf {
+                label,
+                var,
+                iterable,
+                body,
+            });
+        }
+        let BindingPattern::Name(var) = var else {
+            return Err(self.unexpected("of"));
+        };
+        self.expect(&Token::In)?;
+        let range = self.with_struct_literals(false, Self::parse_range)?;
+        let body = self.parse_block()?;
+        Ok(Stmt::ForRange {
+            label,
+            var,
+            range: Box::new(range),
+            body,
+        })
+    }
+
+    /// `a..b`, `a..=b`, or a parenthesized range followed by `.step(n)` / `.rev()`.
+    fn parse_range(&mut self) -> Result<Range> {
+        let checkpoint = self.pos;
+        if self.eat(&Token::LParen) {
+            let start = self.with_struct_literals(true, Self::parse_expr)?;
+            if matches!(self.peek(), Token::DotDot | Token::DotDotEq) {
+                let mut range = self.with_struct_literals(true, |p| p.parse_range_tail(start))?;
+                self.expect(&Token::RParen)?;
+                while self.eat(&Token::Dot) {
+                    let line = self.line();
+                    match self.expect_ident()?.as_str() {
+                        "step" if range.reverse => {
+                            return Err(anyhow!("行 {}: step は rev より前に書いてください", line));
+                        }
+                        "step" if range.step.is_none() => {
+                            self.expect(&Token::LParen)?;
+                            range.step = Some(self.parse_expr()?);
+                            self.expect(&Token::RParen)?;
+                        }
+                        "rev" if !range.reverse => {
+                            self.expect(&Token::LParen)?;
+                            self.expect(&Token::RParen)?;
+                            range.reverse = true;
+                        }
+                        other => {
+                            return Err(anyhow!(
+                                "行 {}: range に使えるのは step(n) と rev() だけです: {}",
+                                line,
+                                other
+                            ));
+                        }
+                    }
+                }
+                return Ok(range);
+            }
+            // `(a + b)..c`: the parenthesis only wrapped the start expression
+            self.pos = checkpoint;
+        }
+        let start = self.parse_expr()?;
+        self.parse_range_tail(start)
+    }
+
+    fn parse_range_tail(&mut self, start: Expr) -> Result<Range> {
+        let inclusive = match self.peek() {
+            Token::DotDot => false,
+            Token::DotDotEq => true,
+            _ => return Err(self.unexpected("'..' または '..='")),
+        };
+        self.advance();
+        let end = self.parse_expr()?;
+        Ok(Range {
+            start,
+            end,
+            inclusive,
+            step: None,
+            reverse: false,
+        })
+    }
+
+    /// Parses statements that start with an identifier: `print(..)` or an
+    /// expression statement. `log.*(..)` / `time.*(..)` / `fs.*(..)` parse as
+    /// method calls; the checker turns them into the std calls.
+    fn parse_ident_stmt(&mut self) -> Result<Stmt> {
+        let is_print = matches!(self.peek(), Token::Ident(name) if name == "print")
+            && self.peek_at(1) == &Token::LParen;
+        if !is_print {
+            return self.parse_expr_stmt();
+        }
+        self.advance();
+        self.expect(&Token::LParen)?;
+        let stmt = Stmt::Print(self.parse_expr()?);
+        self.expect(&Token::RParen)?;
+        Ok(stmt)
+    }
+
+    // --- expressions (precedence climbing) ---
+
+    fn parse_expr(&mut self) -> Result<Expr> {
+        self.parse_binary(0)
+    }
+
+    /// Expression in a statement head followed by a block (`if x {`, `while x {`).
+    fn parse_head_expr(&mut self) -> Result<Expr> {
+        self.with_struct_literals(false, Self::parse_expr)
+    }
+
+    fn with_struct_literals<T>(
+        &mut self,
+        allowed: bool,
+        parse: impl FnOnce(&mut Self) -> Result<T>,
+    ) -> Result<T> {
+        let saved = std::mem::replace(&mut self.no_struct_literal, !allowed);
+        let result = parse(self);
+        self.no_struct_literal = saved;
+        result
+    }
+
+    fn binary_op(&self) -> Option<BinaryOp> {
+        let op = match self.peek() {
+            Token::QuestionQuestion => BinaryOp::Coalesce,
+            Token::OrOr => BinaryOp::Or,
+            Token::AndAnd => BinaryOp::And,
+            Token::EqEq => BinaryOp::Eq,
+            Token::BangEq => BinaryOp::Ne,
+            Token::Lt => BinaryOp::Lt,
+            Token::Le => BinaryOp::Le,
+            Token::Gt => BinaryOp::Gt,
+            Token::Ge => BinaryOp::Ge,
+            Token::Plus => BinaryOp::Add,
+            Token::Minus => BinaryOp::Sub,
+            Token::Star => BinaryOp::Mul,
+            Token::Slash => BinaryOp::Div,
+            Token::Percent => BinaryOp::Rem,
+            _ => return None,
+        };
+        Some(op)
+    }
+
+    fn parse_binary(&mut self, min_prec: u8) -> Result<Expr> {
+        let mut lhs = self.parse_unary()?;
+        loop {
+            // `x is T` binds like the relational operators; `is` is contextual
+            if matches!(self.peek(), Token::Ident(kw) if kw == "is")
+                && self.line() == self.prev_line()
+                && IS_PRECEDENCE > min_prec
+            {
+                let line = self.line();
+                self.advance();
+                let ty = self.parse_type_member()?;
+                lhs = Expr::new(
+                    ExprKind::Is {
+                        expr: Box::new(lhs),
+                        ty,
+                    },
+                    line,
+                );
+                continue;
+            }
+            let Some(op) = self.binary_op() else {
+                break;
+            };
+            let prec = op.precedence();
+            if prec <= min_prec {
+                break;
+            }
+            let line = self.line();
+            self.advance();
+            let rhs = self.parse_binary(prec)?;
+            lhs = Expr::new(
+                ExprKind::Binary {
+                    op,
+                    lhs: Box::new(lhs),
+                    rhs: Box::new(rhs),
+                },
+                line,
+            );
+        }
+        Ok(lhs)
+    }
+
+    fn parse_unary(&mut self) -> Result<Expr> {
+        let line = self.line();
+        if self.eat(&Token::Await) {
+            let expr = self.parse_unary()?;
+            return Ok(Expr::new(ExprKind::Await(Box::new(expr)), line));
+        }
+        let op = match self.peek() {
+            Token::Minus => UnaryOp::Neg,
+            Token::Bang => UnaryOp::Not,
+            _ => return self.parse_primary(),
+        };
+        self.advance();
+        let expr = self.parse_unary()?;
+        Ok(Expr::new(
+            ExprKind::Unary {
+                op,
+                expr: Box::new(expr),
+            },
+            line,
+        ))
+    }
+
+    fn parse_primary(&mut self) -> Result<Expr> {
+        let line = self.line();
+        let kind = match self.peek().clone() {
+            Token::Int(v) => {
+                self.advance();
+                ExprKind::Int(v)
+            }
+            Token::Float(v) => {
+                self.advance();
+                ExprKind::Float(v)
+            }
+            Token::Str(text) => {
+                self.advance();
+                ExprKind::Str(text)
+            }
+            Token::Template(parts) => {
+                self.advance();
+                ExprKind::Template(parse_template(parts)?)
+            }
+            Token::True => {
+                self.advance();
+                ExprKind::Bool(true)
+            }
+            Token::False => {
+                self.advance();
+                ExprKind::Bool(false)
+            }
+            Token::None => {
+                self.advance();
+                ExprKind::None
+            }
+            Token::LParen if self.at_closure() => {
+                let params = self.parse_closure_params()?;
+                let return_type = if self.eat(&Token::Colon) {
+                    Some(self.parse_type()?)
+                } else {
+                    None
+                };
+                self.expect(&Token::FatArrow)?;
+                return self.parse_closure_body(params, return_type, line);
+            }
+            Token::Ident(name) if self.peek_at(1) == &Token::FatArrow => {
+                self.advance();
+                self.advance();
+                let param = Param {
+                    name,
+                    ty: TypeAnnotation::None,
+                    line,
+                    captured: false,
+                    pattern: None,
+                };
+                return self.parse_closure_body(vec![param], None, line);
+            }
+            Token::LParen => {
+                self.advance();
+                let inner = self.with_struct_literals(true, Self::parse_expr)?;
+                if !self.check(&Token::Comma) {
+                    self.expect(&Token::RParen)?;
+                    return self.parse_postfix(inner);
+                }
+                let mut elems = vec![inner];
+                while self.eat(&Token::Comma) {
+                    elems.push(self.with_struct_literals(true, Self::parse_expr)?);
+                }
+                self.expect(&Token::RParen)?;
+                ExprKind::TupleLit(elems)
+            }
+            Token::LBracket => {
+                self.advance();
+                let mut elems = Vec::new();
+                while !self.check(&Token::RBracket) {
+                    elems.push(self.with_struct_literals(true, Self::parse_expr)?);
+                    if !self.eat(&Token::Comma) {
+                        break;
+                    }
+                }
+                self.expect(&Token::RBracket)?;
+                ExprKind::ArrayLit(elems)
+            }
+            Token::LBrace if self.at_object_literal() => {
+                self.advance();
+                let fields = self.with_struct_literals(true, Self::parse_struct_fields)?;
+                ExprKind::ObjectLit(fields)
+            }
+            Token::Match => {
+                let (scrutinee, arms) = self.parse_match(Self::parse_expr)?;
+                ExprKind::Match {
+                    scrutinee: Box::new(scrutinee),
+                    arms,
+                }
+            }
+            // `new` is contextual, like `is` and `of`
+            Token::Ident(name)
+                if name == "new"
+                    && matches!(self.peek_at(1), Token::Ident(_))
+                    && self.tokens[self.pos + 1].line == line =>
+            {
+                self.advance();
+                self.parse_new()?
+            }
+            Token::Ident(name) if self.at_struct_literal() => {
+                self.advance();
+                self.advance();
+                let fields = self.with_struct_literals(true, Self::parse_struct_fields)?;
+                ExprKind::StructLit { name, fields }
+            }
+            Token::Ident(name) => {
+                self.advance();
+                // A call's `(` has to stay on the callee's line so that a new
+                // statement starting with `(` is not swallowed as arguments.
+                if self.check(&Token::LParen) && self.line() == self.prev_line() {
+                    self.advance();
+                    let args = self.parse_args()?;
+                    ExprKind::Call {
+                        callee: name,
+                        args,
+                        type_args: Vec::new(),
+                    }
+                } else {
+                    ExprKind::Ident(name)
+                }
+            }
+            _ => return Err(self.unexpected("式")),
+        };
+        self.parse_postfix(Expr::new(kind, line))
+    }
+
+    /// `(..)` followed by `=>` (or by `:` and a return type) starts a closure.
+    fn at_closure(&self) -> bool {
+        let mut depth = 0;
+        for (offset, token) in self.tokens[self.pos..].iter().enumerate() {
+            match token.value {
+                Token::LParen => depth += 1,
+                Token::RParen => {
+                    depth -= 1;
+                    if depth == 0 {
+                        return matches!(self.peek_at(offset + 1), Token::FatArrow | Token::Colon);
+                    }
+                }
+                _ => {}
+            }
+        }
+        false
+    }
+
+    /// `(a: int, b)`: closure parameters, whose types may be left to the
+    /// checker; like function parameters, they may be destructured.
+    fn parse_closure_params(&mut self) -> Result<Vec<Param>> {
+        self.expect(&Token::LParen)?;
+        let mut params = Vec::new();
+        while !self.eat(&Token::RParen) {
+            let line = self.line();
+            let (name, pattern) = self.parse_param_name(params.len())?;
+            let ty = if self.eat(&Token::Colon) {
+                self.parse_type()?
+            } else {
+                TypeAnnotation::None
+            };
+            params.push(Param {
+                name,
+                ty,
+                line,
+                captured: false,
+                pattern,
+            });
+            if !self.eat(&Token::Comma) {
+                self.expect(&Token::RParen)?;
+                break;
+            }
+        }
+        Ok(params)
+    }
+
+    /// The body after a closure's `=>`: a block, or a single expression
+    /// extending as far as possible (`x => x + 1`).
+    fn parse_closure_body(
+        &mut self,
+        params: Vec<Param>,
+        return_type: Option<TypeAnnotation>,
+        line: usize,
+    ) -> Result<Expr> {
+        let body = self.with_struct_literals(true, |p| {
+            if p.check(&Token::LBrace) {
+                p.parse_block().map(ClosureBody::Block)
+            } else {
+                p.parse_expr().map(ClosureBody::Expr)
+            }
+        })?;
+        let closure = Closure {
+            params,
+            return_type,
+            body,
+            captures: Vec::new(),
+        };
+        Ok(Expr::new(ExprKind::Closure(Box::new(closure)), line))
+    }
+
+    /// `.field` accesses, `.method(args)` calls, `[index]`, `?` and calls of
+    /// a function value (`make(1)(2)`) following a primary expression.
+    fn parse_postfix(&mut self, mut expr: Expr) -> Result<Expr> {
+        loop {
+            // like a named call's `(`, the `(` has to stay on the same line
+            if self.check(&Token::LParen) && self.line() == self.prev_line() {
+                let line = self.line();
+                self.advance();
+                let args = self.parse_args()?;
+                let kind = ExprKind::CallValue {
+                    callee: Box::new(expr),
+                    args,
+                };
+                expr = Expr::new(kind, line);
+                continue;
+            }
+            // like a call's `(`, the `[` has to stay on the same line
+            if self.check(&Token::LBracket) && self.line() == self.prev_line() {
+                let line = self.line();
+                self.advance();
+                let index = self.with_struct_literals(true, Self::parse_expr)?;
+                self.expect(&Token::RBracket)?;
+                let kind = ExprKind::Index {
+                    expr: Box::new(expr),
+                    index: Box::new(index),
+                };
+                expr = Expr::new(kind, line);
+                continue;
+            }
+            if self.check(&Token::Question) && self.line() == self.prev_line() {
+                let line = self.line();
+                self.advance();
+                expr = Expr::new(ExprKind::Try(Box::new(expr)), line);
+                continue;
+            }
+            if !matches!(self.peek(), Token::Dot | Token::QuestionDot) {
+                break;
+            }
+            let optional = self.advance() == Token::QuestionDot;
+            let line = self.line();
+            // `pair.0`: a tuple element
+            let name = match self.peek().clone() {
+                Token::Int(position) => {
+                    self.advance();
+                    position.to_string()
+                }
+                _ => self.expect_ident()?,
+            };
+            let kind = if optional && self.check(&Token::LParen) && self.line() == self.prev_line()
+            {
+                self.advance();
+                let receiver = Expr::new(ExprKind::Ident(OPTIONAL_RECEIVER.to_string()), line);
+                let call = ExprKind::MethodCall {
+                    receiver: Box::new(receiver),
+                    method: name,
+                    args: self.parse_args()?,
+                };
+                ExprKind::OptionalCall {
+                    expr: Box::new(expr),
+                    call: Box::new(Expr::new(call, line)),
+                }
+            } else if optional {
+                ExprKind::OptionalField {
+                    expr: Box::new(expr),
+                    name,
+                }
+            } else if self.check(&Token::LParen) && self.line() == self.prev_line() {
+                self.advance();
+                ExprKind::MethodCall {
+                    receiver: Box::new(expr),
+                    method: name,
+                    args: self.parse_args()?,
+                }
+            } else {
+                ExprKind::Field {
+                    expr: Box::new(expr),
+                    name,
+                }
+            };
+            expr = Expr::new(kind, line);
+        }
+        Ok(expr)
+    }
+
+    /// `Name {` followed by `}` or `field:` starts a struct literal.
+    fn at_struct_literal(&self) -> bool {
+        !self.no_struct_literal
+            && self.peek_at(1) == &Token::LBrace
+            && match self.peek_at(2) {
+                Token::RBrace => true,
+                Token::Ident(_) => self.peek_at(3) == &Token::Colon,
+                _ => false,
+            }
+    }
+
+    /// `{` followed by `}` or `field:` starts an object literal; like struct
+    /// literals, not in front of a block.
+    fn at_object_literal(&self) -> bool {
+        !self.no_struct_literal
+            && match self.peek_at(1) {
+                Token::RBrace => true,
+                Token::Ident(_) => self.peek_at(2) == &Token::Colon,
+                _ => false,
+            }
+    }
+
+    /// `field: expr, ...}` after the opening brace of a struct or object literal.
+    fn parse_struct_fields(&mut self) -> Result<Vec<(String, Expr)>> {
+        let mut fields = Vec::new();
+        while !self.check(&Token::RBrace) {
+            let name = self.expect_ident()?;
+            self.expect(&Token::Colon)?;
+            fields.push((name, self.parse_expr()?));
+            if !self.eat(&Token::Comma) {
+                break;
+            }
+        }
+        self.expect(&Token::RBrace)?;
+        Ok(fields)
+    }
+
+    /// Parses a comma separated argument list after the opening `(`.
+    fn parse_args(&mut self) -> Result<Vec<Expr>> {
+        let mut args = Vec::new();
+        while !self.check(&Token::RParen) {
+            args.push(self.with_struct_literals(true, Self::parse_expr)?);
+            if !self.eat(&Token::Comma) {
+                break;
+            }
+        }
+        self.expect(&Token::RParen)?;
+        Ok(args)
+    }
+}
@@ -0,0 +1,4485 @@
+// --- Type checker: validates a parsed program and annotates expression types ---
+use std::cell::RefCell;
+use std::collections::{HashMap, HashSet};
+
+use anyhow::{Result, anyhow};
+
+use crate::ast::{
+    BinaryOp, BindingPattern, Block, Capture, Closure, ClosureBody, EnumDecl, Expr, ExprKind,
+    Function, InterfaceDecl, InterfaceMethod, LogLevel, OPTIONAL_RECEIVER, Param, Pattern, Program,
+    Receiver, Spanned, Stmt, StructDecl, TemplatePart, TypeAlias, TypeAnnotation, UnaryOp,
+    quote_str, walk_block,
+};
+
+/// Checks `program` and returns a copy whose expressions carry their resolved
+/// types (`Expr::ty`). Code generation consumes the returned program; `fmt`
+/// keeps formatting the untouched parse result.
+///
+/// Generic functions are checked once with opaque type parameters and then
+/// once per instance (`first<int>`) the program calls; the returned program
+/// holds the instances in their place, as well as one struct instance per
+/// generic struct type it uses (`Box<int>`). Functions with interface-typed
+/// parameters are instantiated the same way, once per combination of struct
+/// types they are called with.
+pub fn type_check(program: &Program) -> Result<Program> {
+    let mut checked = program.clone();
+    let types = TypeNames::new(&checked)?;
+    types.resolve_declarations(&mut checked)?;
+    let checker = Checker::new(&checked, types)?;
+    let mut functions = Vec::new();
+    for func in &checked.functions {
+        let mut func = func.clone();
+        checker.check_function(&mut func)?;
+        if !checker.functions[&func.full_name()].is_generic() {
+            functions.push(func);
+        }
+    }
+    // checking an instance may call for more instances
+    let mut next = 0;
+    loop {
+        let instance = checker.instances.borrow().get(next).cloned();
+        let Some((name, type_args)) = instance else {
+            break;
+        };
+        next += 1;
+        let template = checked
+            .functions
+            .iter()
+            .find(|func| func.full_name() == name)
+            .expect("generic function");
+        functions.push(checker.instantiate_function(template, type_args)?);
+    }
+    checked.functions = functions;
+    checker.instantiate_structs(&mut checked)?;
+    Ok(checked)
+}
+
+struct FnSig {
+    type_params: Vec<String>,
+    /// Excludes the `self` of a method.
+    params: Vec<TypeAnnotation>,
+    ret: TypeAnnotation,
+    /// `None` for a free function and a static method.
+    receiver: Option<Receiver>,
+}
+
+impl FnSig {
+    /// Generic functions and functions taking an interface are instantiated
+    /// per call instead of being used as they are.
+    fn is_generic(&self) -> bool {
+        !self.type_params.is_empty()
+            || self
+                .params
+                .iter()
+                .any(|param| matches!(param, TypeAnnotation::Interface(_)))
+    }
+}
+
+/// The type of an interface method, as a function type without `self`.
+fn method_type(method: &InterfaceMethod) -> TypeAnnotation {
+    TypeAnnotation::Function(
+        method.params.iter().map(|param| param.ty.clone()).collect(),
+        Box::new(method.return_type.clone().unwrap_or(TypeAnnotation::Void)),
+    )
+}
+
+/// `write(s: string): void`: an interface method as written.
+fn method_signature(method: &InterfaceMethod) -> String {
+    let params = method
+        .params
+        .iter()
+        .map(|param| format!("{}: {}", param.name, param.ty))
+        .collect::<Vec<_>>()
+        .join(", ");
+    let ret = method.return_type.clone().unwrap_or(TypeAnnotation::Void);
+    format!("{}({}): {}", method.name, params, ret)
+}
+
+/// Interface types only describe function parameters (`w: Writer`), whose
+/// values are whatever struct the caller passes; they cannot be stored.
+fn reject_interface(ty: &TypeAnnotation, line: usize) -> Result<()> {
+    let mut found = None;
+    visit_types(ty, &mut |inner| {
+        if let TypeAnnotation::Interface(name) = inner {
+            found.get_or_insert_with(|| name.clone());
+        }
+    });
+    match found {
+        Some(name) => Err(anyhow!(
+            "行 {}: インターフェース {} は関数の引数の型としてだけ使えます",
+            line,
+            name
+        )),
+        None => Ok(()),
+    }
+}
+
+/// Validates where the method `func` of `owner` is declared: on a plain
+/// struct, without type parameters, and not named like one of its fields.
+fn check_method(
+    func: &Function,
+    owner: &str,
+    structs: &HashMap<String, StructDecl>,
+    enums: &HashMap<String, EnumDecl>,
+) -> Result<()> {
+    let Some(decl) = structs.get(owner) else {
+        if enums.contains_key(owner) {
+            return Err(anyhow!(
+                "行 {}: {} は構造体ではないため、メソッドを定義できません",
+                func.line,
+                owner
+            ));
+        }
+        return Err(anyhow!("行 {}: 未定義の型 '{}' です", func.line, owner));
+    };
+    if !decl.type_params.is_empty() {
+        return Err(anyhow!(
+            "行 {}: ジェネリック構造体 {} にはまだメソッドを定義できません",
+            func.line,
+            owner
+        ));
+    }
+    if !func.type_params.is_empty() {
+        return Err(anyhow!(
+            "行 {}: メソッド {}.{} はまだ型パラメータを取れません",
+            func.line,
+            owner,
+            func.name
+        ));
+    }
+    if let Some(param) = func
+        .params
+        .iter()
+        .find(|param| matches!(param.ty, TypeAnnotation::Interface(_)))
+    {
+        return Err(anyhow!(
+            "行 {}: メソッド {}.{} はまだインターフェース型の引数を取れません",
+            param.line,
+            owner,
+            func.name
+        ));
+    }
+    if decl.fields.iter().any(|field| field.name == func.name) {
+        return Err(anyhow!(
+            "行 {}: 構造体 {} にはフィールド '{}' があるため、同名のメソッドは定義できません",
+            func.line,
+            owner,
+            func.name
+        ));
+    }
+    Ok(())
+}
+
+/// Instances of one generic function beyond this many mean the type arguments
+/// keep growing through recursion (`f<T>` calling `f<T[]>`).
+const MAX_INSTANCES: usize = 64;
+
+/// The type names a program declares: structs, enums and interfaces, plus
+/// `type` aliases (stored fully expanded).
+struct TypeNames {
+    declared: HashSet<String>,
+    interfaces: HashSet<String>,
+    aliases: HashMap<String, TypeAnnotation>,
+    /// Generic structs with their number of type parameters.
+    generics: HashMap<String, usize>,
+}
+
+impl TypeNames {
+    fn new(program: &Program) -> Result<Self> {
+        let declared: HashSet<String> = program
+            .structs
+            .iter()
+            .map(|s| s.name.clone())
+            .chain(program.enums.iter().map(|e| e.name.clone()))
+            .chain(program.interfaces.iter().map(|i| i.name.clone()))
+            .collect();
+        let interfaces = program.interfaces.iter().map(|i| i.name.clone()).collect();
+        let generics = program
+            .structs
+            .iter()
+            .filter(|s| !s.type_params.is_empty())
+            .map(|s| (s.name.clone(), s.type_params.len()))
+            .collect();
+        let mut names = Self {
+            declared,
+            interfaces,
+            aliases: HashMap::new(),
+            generics,
+        };
+        for alias in &program.aliases {
+            let builtin = !matches!(
+                crate::parser::parse_type_annotation(&alias.name),
+                TypeAnnotation::Named(_)
+            );
+            if builtin
+                || names.declared.contains(&alias.name)
+                || program
+                    .aliases
+                    .iter()
+                    .filter(|other| other.name == alias.name)
+                    .count()
+                    > 1
+            {
+                return Err(anyhow!(
+                    "行 {}: 型 {} が重複して宣言されています",
+                    alias.line,
+                    alias.name
+                ));
+            }
+        }
+        for alias in &program.aliases {
+            names.expand_alias(alias, &program.aliases, &mut Vec::new())?;
+        }
+        Ok(names)
+    }
+
+    /// Expands `alias` after the aliases it refers to; `path` detects cycles.
+    fn expand_alias(
+        &mut self,
+        alias: &TypeAlias,
+        all: &[TypeAlias],
+        path: &mut Vec<String>,
+    ) -> Result<()> {
+        if self.aliases.contains_key(&alias.name) {
+            return Ok(());
+        }
+        if path.contains(&alias.name) {
+            return Err(anyhow!(
+                "行 {}: 型エイリアス {} が循環しています ({} -> {})",
+                alias.line,
+                alias.name,
+                path.join(" -> "),
+                alias.name
+            ));
+        }
+        path.push(alias.name.clone());
+        let mut referenced = Vec::new();
+        named_parts(&alias.ty, &mut referenced);
+        for name in referenced {
+            if let Some(inner) = all.iter().find(|other| other.name == name) {
+                self.expand_alias(inner, all, path)?;
+            }
+        }
+        path.pop();
+        let mut ty = alias.ty.clone();
+        self.resolve(&mut ty, alias.line)?;
+        self.aliases.insert(alias.name.clone(), ty);
+        Ok(())
+    }
+
+    /// Resolves `Named` types in declarations (struct fields, enum payloads,
+    /// interface methods, parameters and return types).
+    /// Type parameters of generic declarations become `Param` types.
+    fn resolve_declarations(&self, program: &mut Program) -> Result<()> {
+        for decl in &mut program.structs {
+            let params = type_param_map(&decl.type_params, decl.line)?;
+            for field in &mut decl.fields {
+                substitute_names(&mut field.ty, &params);
+                self.resolve(&mut field.ty, field.line)?;
+                reject_interface(&field.ty, field.line)?;
+            }
+        }
+        for decl in &mut program.enums {
+            for variant in &mut decl.variants {
+                for ty in &mut variant.fields {
+                    self.resolve(ty, variant.line)?;
+                    reject_interface(ty, variant.line)?;
+                }
+            }
+        }
+        for decl in &mut program.interfaces {
+            for method in &mut decl.methods {
+                for param in &mut method.params {
+                    self.resolve(&mut param.ty, param.line)?;
+                    reject_interface(&param.ty, param.line)?;
+                }
+                if let Some(ret) = &mut method.return_type {
+                    self.resolve(ret, method.line)?;
+                    reject_interface(ret, method.line)?;
+                }
+            }
+        }
+        for func in &mut program.functions {
+            let params = type_param_map(&func.type_params, func.line)?;
+            for param in &mut func.params {
+                substitute_names(&mut param.ty, &params);
+                self.resolve(&mut param.ty, param.line)?;
+                // `w: Writer`, but not `ws: Writer[]`
+                if !matches!(param.ty, TypeAnnotation::Interface(_)) {
+                    reject_interface(&param.ty, param.line)?;
+                }
+            }
+            if let Some(ret) = &mut func.return_type {
+                substitute_names(ret, &params);
+                self.resolve(ret, func.line)?;
+                reject_interface(ret, func.line)?;
+            }
+        }
+        Ok(())
+    }
+
+    /// Replaces aliases by their definition and normalizes unions. Names that
+    /// are neither declared nor aliases are reported and become opaque.
+    fn resolve(&self, ty: &mut TypeAnnotation, line: usize) -> Result<()> {
+        match ty {
+            TypeAnnotation::Named(name) if self.generics.contains_key(name.as_str()) => {
+                return Err(anyhow!(
+                    "行 {}: 型 {} には型引数が必要です (例: {}<int>)",
+                    line,
+                    name,
+                    name
+                ));
+            }
+            TypeAnnotation::Generic(name, args) => {
+                let Some(&count) = self.generics.get(name.as_str()) else {
+                    if self.declared.contains(name.as_str()) || self.aliases.contains_key(name) {
+                        return Err(anyhow!("行 {}: 型 {} は型引数を取りません", line, name));
+                    }
+                    return Err(anyhow!("行 {}: 未定義の型 '{}' です", line, name));
+                };
+                if args.len() != count {
+                    return Err(anyhow!(
+                        "行 {}: 型 {} は {} 個の型引数を取りますが {} 個指定されました",
+                        line,
+                        name,
+                        count,
+                        args.len()
+                    ));
+                }
+                for arg in args.iter_mut() {
+                    self.resolve(arg, line)?;
+                    if *arg == TypeAnnotation::Void {
+                        return Err(anyhow!("行 {}: 型引数に void は使えません", line));
+                    }
+                }
+            }
+            TypeAnnotation::Named(name) => {
+                if self.interfaces.contains(name.as_str()) {
+                    *ty = TypeAnnotation::Interface(name.clone());
+                } else if let Some(target) = self.aliases.get(name.as_str()) {
+                    *ty = target.clone();
+                } else if !self.declared.contains(name.as_str()) {
+                    return Err(anyhow!("行 {}: 未定義の型 '{}' です", line, name));
+                }
+            }
+            TypeAnnotation::Union(members) => {
+                for member in members.iter_mut() {
+                    self.resolve(member, line)?;
+                }
+                *ty = union_of(std::mem::take(members), line)?;
+            }
+            TypeAnnotation::Nullable(inner) => {
+                self.resolve(inner, line)?;
+                *ty = nullable(std::mem::replace(&mut **inner, TypeAnnotation::Void), line)?;
+            }
+            TypeAnnotation::Array(elem) => {
+                self.resolve(elem, line)?;
+                if **elem == TypeAnnotation::Void {
+                    return Err(anyhow!("行 {}: 配列の要素型に void は使えません", line));
+                }
+            }
+            TypeAnnotation::Map(key, value) => {
+                self.resolve(key, line)?;
+                self.resolve(value, line)?;
+                check_key_type(key, "Map のキー", line)?;
+                if **value == TypeAnnotation::Void {
+                    return Err(anyhow!("行 {}: Map の値の型に void は使えません", line));
+                }
+            }
+            TypeAnnotation::Set(elem) => {
+                self.resolve(elem, line)?;
+                check_key_type(elem, "Set の要素", line)?;
+            }
+            TypeAnnotation::Tuple(elems) => {
+                for elem in elems.iter_mut() {
+                    self.resolve(elem, line)?;
+                    if *elem == TypeAnnotation::Void {
+                        return Err(anyhow!("行 {}: タプルの要素に void は使えません", line));
+                    }
+                }
+            }
+            TypeAnnotation::Object(fields) => {
+                let mut seen = HashSet::new();
+                for (name, field_ty) in fields.iter_mut() {
+                    if !seen.insert(name.clone()) {
+                        return Err(anyhow!(
+                            "行 {}: フィールド '{}' が重複しています",
+                            line,
+                            name
+                        ));
+                    }
+                    self.resolve(field_ty, line)?;
+                    if *field_ty == TypeAnnotation::Void {
+                        return Err(anyhow!(
+                            "行 {}: フィールド '{}' の型に void は使えません",
+                            line,
+                            name
+                        ));
+                    }
+                }
+                *ty = object_type(std::mem::take(fields));
+            }
+            TypeAnnotation::Function(params, ret) => {
+                for param in params.iter_mut() {
+                    self.resolve(param, line)?;
+                    if *param == TypeAnnotation::Void {
+                        return Err(anyhow!("行 {}: 関数型の引数に void は使えません", line));
+                    }
+                }
+                self.resolve(ret, line)?;
+            }
+            TypeAnnotation::Result(value, error) => {
+                self.resolve(value, line)?;
+                self.resolve(error, line)?;
+                if **error == TypeAnnotation::Void {
+                    return Err(anyhow!(
+                        "行 {}: Result のエラー型に void は使えません",
+                        line
+                    ));
+                }
+            }
+            _ => {}
+        }
+        Ok(())
+    }
+}
+
+/// Builds a normalized union: nested unions are flattened, duplicates removed
+/// and members sorted, so `string | int` and `int | string` are the same type.
+/// A single remaining member is returned as is.
+fn union_of(members: Vec<TypeAnnotation>, line: usize) -> Result<TypeAnnotation> {
+    let mut flat: Vec<TypeAnnotation> = Vec::new();
+    // `int? | string` is `(int | string)?`
+    let mut has_none = false;
+    for member in members {
+        let member = match member {
+            TypeAnnotation::Nullable(inner) => {
+                has_none = true;
+                *inner
+            }
+            other => other,
+        };
+        let parts = match member {
+            TypeAnnotation::Union(inner) => inner,
+            other => vec![other],
+        };
+        for part in parts {
+            if !flat.contains(&part) {
+                flat.push(part);
+            }
+        }
+    }
+    if flat.contains(&TypeAnnotation::Void) {
+        return Err(anyhow!("行 {}: union に void は含められません", line));
+    }
+    let literals = flat
+        .iter()
+        .filter(|m| matches!(m, TypeAnnotation::Literal(_)))
+        .count();
+    if literals > 0 && literals < flat.len() {
+        return Err(anyhow!(
+            "行 {}: 文字列リテラル型と他の型を同じ union に混在させることはできません",
+            line
+        ));
+    }
+    flat.sort_by_key(|m| m.to_string());
+    let union = if flat.len() == 1 {
+        flat.remove(0)
+    } else {
+        TypeAnnotation::Union(flat)
+    };
+    if has_none {
+        return nullable(union, line);
+    }
+    Ok(union)
+}
+
+/// `inner?`; `T??` is the same as `T?`.
+/// The type of the value `?.name` reads from: `T` for a `T?`.
+fn optional_value<'t>(
+    ty: &'t TypeAnnotation,
+    name: &str,
+    line: usize,
+) -> Result<&'t TypeAnnotation> {
+    match ty {
+        TypeAnnotation::Nullable(value_ty) => Ok(value_ty),
+        TypeAnnotation::Result(..) => Err(anyhow!(
+            "行 {}: Result の値に ?. は使えません。(value?).{} のように括弧で囲んでください",
+            line,
+            name
+        )),
+        _ => Err(anyhow!(
+            "行 {}: ?. は nullable な値にしか使えませんが {} が渡されました",
+            line,
+            ty
+        )),
+    }
+}
+
+fn nullable(inner: TypeAnnotation, line: usize) -> Result<TypeAnnotation> {
+    match inner {
+        TypeAnnotation::Void => Err(anyhow!("行 {}: void は nullable にできません", line)),
+        TypeAnnotation::Nullable(_) => Ok(inner),
+        other => Ok(TypeAnnotation::Nullable(Box::new(other))),
+    }
+}
+
+/// Collects the names referenced by `ty` (including union members).
+fn named_parts(ty: &TypeAnnotation, out: &mut Vec<String>) {
+    collect_names(ty, true, out);
+}
+
+/// Collects the names `ty` stores by value: array and Map / Set contents
+/// live behind a pointer, so `struct Node { children: Node[] }` is fine.
+fn value_parts(ty: &TypeAnnotation, out: &mut Vec<String>) {
+    collect_names(ty, false, out);
+}
+
+fn collect_names(ty: &TypeAnnotation, through_refs: bool, out: &mut Vec<String>) {
+    match ty {
+        TypeAnnotation::Named(name) => out.push(name.clone()),
+        TypeAnnotation::Nullable(inner) => collect_names(inner, through_refs, out),
+        TypeAnnotation::Array(elem) | TypeAnnotation::Set(elem) if through_refs => {
+            collect_names(elem, through_refs, out)
+        }
+        TypeAnnotation::Map(key, value) if through_refs => {
+            collect_names(key, through_refs, out);
+            collect_names(value, through_refs, out);
+        }
+        TypeAnnotation::Result(value, error) => {
+            collect_names(value, through_refs, out);
+            collect_names(error, through_refs, out);
+        }
+        TypeAnnotation::Union(members) | TypeAnnotation::Tuple(members) => {
+            for member in members {
+                collect_names(member, through_refs, out);
+            }
+        }
+        TypeAnnotation::Object(fields) => {
+            for (_, ty) in fields {
+                collect_names(ty, through_refs, out);
+            }
+        }
+        // the arguments may be stored by value as well
+        TypeAnnotation::Generic(name, args) => {
+            out.push(name.clone());
+            for arg in args {
+                collect_names(arg, through_refs, out);
+            }
+        }
+        // the C function pointer type names its parameter and return types,
+        // which therefore have to be complete before it
+        TypeAnnotation::Function(params, ret) => {
+            for param in params {
+                collect_names(param, through_refs, out);
+            }
+            collect_names(ret, through_refs, out);
+        }
+        _ => {}
+    }
+}
+
+/// Maps the type parameters of a generic declaration to `Param` types.
+fn type_param_map(params: &[String], line: usize) -> Result<HashMap<String, TypeAnnotation>> {
+    let mut map = HashMap::new();
+    for name in params {
+        if map
+            .insert(name.clone(), TypeAnnotation::Param(name.clone()))
+            .is_some()
+        {
+            return Err(anyhow!("行 {}: 型引数 {} が重複しています", line, name));
+        }
+    }
+    Ok(map)
+}
+
+/// Replaces type parameter names (`Named("T")` as written, `Param("T")` once
+/// resolved) by the types they are bound to. The result still has to be
+/// resolved to normalize unions and nullables (`T?` with `T = int?`).
+fn substitute_names(ty: &mut TypeAnnotation, bindings: &HashMap<String, TypeAnnotation>) {
+    match ty {
+        TypeAnnotation::Named(name) | TypeAnnotation::Param(name) => {
+            if let Some(bound) = bindings.get(name.as_str()) {
+                *ty = bound.clone();
+            }
+        }
+        TypeAnnotation::Nullable(inner)
+        | TypeAnnotation::Array(inner)
+        | TypeAnnotation::Set(inner) => substitute_names(inner, bindings),
+        TypeAnnotation::Result(a, b) | TypeAnnotation::Map(a, b) => {
+            substitute_names(a, bindings);
+            substitute_names(b, bindings);
+        }
+        TypeAnnotation::Union(members)
+        | TypeAnnotation::Generic(_, members)
+        | TypeAnnotation::Tuple(members) => {
+            for member in members {
+                substitute_names(member, bindings);
+            }
+        }
+        TypeAnnotation::Object(fields) => {
+            for (_, field_ty) in fields {
+                substitute_names(field_ty, bindings);
+            }
+        }
+        TypeAnnotation::Function(params, ret) => {
+            for param in params {
+                substitute_names(param, bindings);
+            }
+            substitute_names(ret, bindings);
+        }
+        _ => {}
+    }
+}
+
+/// Binds the type parameters in `param` by matching it against the argument
+/// type `actual`; the first binding of a parameter wins. Open types (`none`,
+/// `[]`) bind nothing.
+fn infer_type_args(
+    param: &TypeAnnotation,
+    actual: &TypeAnnotation,
+    bindings: &mut HashMap<String, TypeAnnotation>,
+) {
+    match (param, actual) {
+        (TypeAnnotation::Param(name), actual)
+            if !is_undetermined(actual) && !bindings.contains_key(name) =>
+        {
+            bindings.insert(name.clone(), actual.clone());
+        }
+        (TypeAnnotation::Nullable(param), TypeAnnotation::Nullable(actual))
+        | (TypeAnnotation::Array(param), TypeAnnotation::Array(actual))
+        | (TypeAnnotation::Set(param), TypeAnnotation::Set(actual)) => {
+            infer_type_args(param, actual, bindings)
+        }
+        // a `T` passed for a `T?`
+        (TypeAnnotation::Nullable(param), actual) => infer_type_args(param, actual, bindings),
+        (TypeAnnotation::Result(pa, pb), TypeAnnotation::Result(aa, ab))
+        | (TypeAnnotation::Map(pa, pb), TypeAnnotation::Map(aa, ab)) => {
+            infer_type_args(pa, aa, bindings);
+            infer_type_args(pb, ab, bindings);
+        }
+        (TypeAnnotation::Generic(name, params), TypeAnnotation::Generic(other, actuals))
+            if name == other =>
+        {
+            for (param, actual) in params.iter().zip(actuals) {
+                infer_type_args(param, actual, bindings);
+            }
+        }
+        (TypeAnnotation::Tuple(params), TypeAnnotation::Tuple(actuals))
+            if params.len() == actuals.len() =>
+        {
+            for (param, actual) in params.iter().zip(actuals) {
+                infer_type_args(param, actual, bindings);
+            }
+        }
+        (TypeAnnotation::Object(params), TypeAnnotation::Object(actuals)) => {
+            for (name, param) in params {
+                if let Some((_, actual)) = actuals.iter().find(|(other, _)| other == name) {
+                    infer_type_args(param, actual, bindings);
+                }
+            }
+        }
+        (TypeAnnotation::Function(params, ret), TypeAnnotation::Function(actuals, actual_ret))
+            if params.len() == actuals.len() =>
+        {
+            for (param, actual) in params.iter().zip(actuals) {
+                infer_type_args(param, actual, bindings);
+            }
+            infer_type_args(ret, actual_ret, bindings);
+        }
+        _ => {}
+    }
+}
+
+/// A type mentioning a type parameter or an interface, i.e. one inside a
+/// generic declaration or a function taking an interface.
+fn has_type_param(ty: &TypeAnnotation) -> bool {
+    let mut found = false;
+    visit_types(ty, &mut |inner| {
+        found |= matches!(
+            inner,
+            TypeAnnotation::Param(_) | TypeAnnotation::Interface(_)
+        );
+    });
+    found
+}
+
+/// Calls `visit` on `ty` and every type nested in it.
+fn visit_types(ty: &TypeAnnotation, visit: &mut dyn FnMut(&TypeAnnotation)) {
+    visit(ty);
+    match ty {
+        TypeAnnotation::Nullable(inner)
+        | TypeAnnotation::Array(inner)
+        | TypeAnnotation::Set(inner) => visit_types(inner, visit),
+        TypeAnnotation::Result(a, b) | TypeAnnotation::Map(a, b) => {
+            visit_types(a, visit);
+            visit_types(b, visit);
+        }
+        TypeAnnotation::Union(members)
+        | TypeAnnotation::Generic(_, members)
+        | TypeAnnotation::Tuple(members) => {
+            for member in members {
+                visit_types(member, visit);
+            }
+        }
+        TypeAnnotation::Object(fields) => {
+            for (_, field_ty) in fields {
+                visit_types(field_ty, visit);
+            }
+        }
+        TypeAnnotation::Function(params, ret) => {
+            for param in params {
+                visit_types(param, visit);
+            }
+            visit_types(ret, visit);
+        }
+        _ => {}
+    }
+}
+
+/// The type of an object with the given fields, which are kept sorted by name
+/// so that `{ a: int, b: int }` and `{ b: int, a: int }` are the same type.
+fn object_type(mut fields: Vec<(String, TypeAnnotation)>) -> TypeAnnotation {
+    fields.sort_by(|(a, _), (b, _)| a.cmp(b));
+    TypeAnnotation::Object(fields)
+}
+
+/// Map keys and Set elements are hashed, so only int and string are allowed.
+fn check_key_type(ty: &TypeAnnotation, what: &str, line: usize) -> Result<()> {
+    // `None` is the side `new Map()` leaves open
+    if matches!(ty, TypeAnnotation::Int | TypeAnnotation::None) || ty.is_string() {
+        return Ok(());
+    }
+    Err(anyhow!(
+        "行 {}: {}に使えるのは int か string だけです ({} が指定されました)",
+        line,
+        what,
+        ty
+    ))
+}
+
+/// Members of a union of non-literal types (`int | string`), which is
+/// represented as a tagged union.
+fn tagged_members(ty: &TypeAnnotation) -> Option<&[TypeAnnotation]> {
+    match ty {
+        TypeAnnotation::Union(members) if !ty.is_string() => Some(members),
+        _ => None,
+    }
+}
+
+struct Checker {
+    types: TypeNames,
+    structs: HashMap<String, StructDecl>,
+    enums: HashMap<String, EnumDecl>,
+    interfaces: HashMap<String, InterfaceDecl>,
+    functions: HashMap<String, FnSig>,
+    /// Instances of generic functions called so far (name, type arguments),
+    /// in the order they were first called.
+    instances: RefCell<Vec<(String, Vec<TypeAnnotation>)>>,
+}
+
+#[derive(Clone, Copy, PartialEq, Eq)]
+enum Binding {
+    Let,
+    Const,
+    Param,
+    LoopVar,
+    /// `self` of a method taking `self`: read-only.
+    SelfRef,
+    /// `self` of a method taking `mut self`: its fields may be assigned.
+    SelfMut,
+}
+
+#[derive(Clone)]
+struct Local {
+    ty: TypeAnnotation,
+    binding: Binding,
+    /// Set when `ty` was narrowed (`if x is int`): the variable itself still
+    /// has this union type.
+    narrowed_from: Option<TypeAnnotation>,
+    /// Identifies the variable within the function; a narrowed local keeps
+    /// the id of the variable it narrows.
+    id: usize,
+}
+
+/// A closure being checked inside the function.
+struct ClosureFrame {
+    /// Scopes from this index on belong to the closure.
+    base: usize,
+    /// Variables with an id below this one are declared outside the closure.
+    first_id: usize,
+    captures: RefCell<Vec<Capture>>,
+}
+
+/// Per-function state: the signature being checked plus the lexical scopes
+/// of local bindings (innermost last).
+struct FnContext<'a> {
+    name: &'a str,
+    ret: &'a TypeAnnotation,
+    /// The type parameters in scope: `Param` types while checking a generic
+    /// function, the type arguments while checking one of its instances.
+    type_args: HashMap<String, TypeAnnotation>,
+    scopes: Vec<HashMap<String, Local>>,
+    /// Enclosing loops (innermost last) with their optional labels.
+    loops: Vec<Option<String>>,
+    /// Closures being checked (innermost last).
+    closures: Vec<ClosureFrame>,
+    /// Id of the next declared variable.
+    next_id: usize,
+    /// Ids of the `let` variables and parameters a closure captures; they
+    /// live in heap cells shared with the closure.
+    boxed: RefCell<HashSet<usize>>,
+}
+
+impl<'a> FnContext<'a> {
+    fn new(
+        name: &'a str,
+        ret: &'a TypeAnnotation,
+        type_args: HashMap<String, TypeAnnotation>,
+    ) -> Self {
+        FnContext {
+            name,
+            ret,
+            type_args,
+            scopes: vec![HashMap::new()],
+            loops: Vec::new(),
+            closures: Vec::new(),
+            next_id: 0,
+            boxed: RefCell::new(HashSet::new()),
+        }
+    }
+
+    /// Finds the variable `name`. Inside a closure, a variable declared
+    /// outside it is recorded as captured; a captured `let` variable or
+    /// parameter reads with its declared type, since the closure may run
+    /// after the variable changed.
+    fn lookup(&self, name: &str) -> Option<Local> {
+        let (depth, local) = self
+            .scopes
+            .iter()
+            .enumerate()
+            .rev()
+            .find_map(|(depth, scope)| scope.get(name).map(|local| (depth, local)))?;
+        let mut local = local.clone();
+        let Some(frame) = self.closures.last() else {
+            return Some(local);
+        };
+        if local.id >= frame.first_id {
+            return Some(local);
+        }
+        let by_ref = matches!(local.binding, Binding::Let | Binding::Param);
+        if by_ref
+            && depth < frame.base
+            && let Some(declared) = local.narrowed_from.take()
+        {
+            local.ty = declared;
+        }
+        let capture = Capture {
+            name: name.to_string(),
+            ty: local
+                .narrowed_from
+                .clone()
+                .unwrap_or_else(|| local.ty.clone()),
+            by_ref,
+        };
+        for frame in self.closures.iter().filter(|f| local.id < f.first_id) {
+            let mut captures = frame.captures.borrow_mut();
+            if !captures.iter().any(|c| c.name == name) {
+                captures.push(capture.clone());
+            }
+        }
+        if by_ref {
+            self.boxed.borrow_mut().insert(local.id);
+        }
+        Some(local)
+    }
+
+    /// Ends the narrowing of the variable `id` in every scope.
+    fn drop_narrowing(&mut self, id: usize) {
+        for local in self.scopes.iter_mut().flat_map(|scope| scope.values_mut()) {
+            if local.id == id
+                && let Some(declared) = local.narrowed_from.take()
+            {
+                local.ty = declared;
+            }
+        }
+    }
+
+    /// Ends the narrowing of the variable `name` refers to.
+    fn drop_narrowing_of(&mut self, name: &str) {
+        if let Some(id) = self
+            .scopes
+            .iter()
+            .rev()
+            .find_map(|scope| scope.get(name))
+            .map(|local| local.id)
+        {
+            self.drop_narrowing(id);
+        }
+    }
+
+    /// The number of narrowed variables in scope.
+    fn narrowed_count(&self) -> usize {
+        self.scopes
+            .iter()
+            .flat_map(|scope| scope.values())
+            .filter(|local| local.narrowed_from.is_some())
+            .count()
+    }
+
+    /// `local` (found by `lookup`) is declared outside the closure being checked.
+    fn is_captured(&self, local: &Local) -> bool {
+        self.closures.last().is_some_and(|f| local.id < f.first_id)
+    }
+
+    /// The variable `name` declared in `scope` lives in a heap cell.
+    fn is_boxed(&self, scope: &HashMap<String, Local>, name: &str) -> bool {
+        scope
+            .get(name)
+            .is_some_and(|local| self.boxed.borrow().contains(&local.id))
+    }
+
+    /// Declares a binding in the innermost scope. Shadowing an outer binding is
+    /// allowed; redeclaring a name in the same block is not.
+    fn declare(
+        &mut self,
+        name: &str,
+        ty: TypeAnnotation,
+        binding: Binding,
+        line: usize,
+    ) -> Result<()> {
+        let id = self.next_id;
+        let scope = self.scopes.last_mut().expect("function scope");
+        if scope.contains_key(name) {
+            return Err(anyhow!(
+                "行 {}: '{}' はこのブロックで既に宣言されています",
+                line,
+                name
+            ));
+        }
+        let local = Local {
+            ty,
+            binding,
+            narrowed_from: None,
+            id,
+        };
+        scope.insert(name.to_string(), local);
+        self.next_id += 1;
+        Ok(())
+    }
+}
+
+fn stmt_contains_return(stmt: &Stmt) -> bool {
+    match stmt {
+        Stmt::Return(_) => true,
+        Stmt::If {
+            then_branch,
+            else_branch,
+            ..
+        } => block_contains_return(then_branch) || block_contains_return(else_branch),
+        Stmt::While { body, .. } | Stmt::ForRange { body, .. } | Stmt::ForOf { body, .. } => {
+            block_contains_return(body)
+        }
+        Stmt::Match { arms, .. } => arms.iter().any(|arm| block_contains_return(&arm.body)),
+        Stmt::Await(inner) => stmt_contains_return(inner),
+        _ => false,
+    }
+}
+
+fn block_contains_return(block: &Block) -> bool {
+    block.iter().any(|stmt| stmt_contains_return(&stmt.value))
+}
+
+/// `block` has a `return` with a value.
+fn block_returns_value(block: &Block) -> bool {
+    block.iter().any(|stmt| match &stmt.value {
+        Stmt::Return(value) => value.is_some(),
+        Stmt::If {
+            then_branch,
+            else_branch,
+            ..
+        } => block_returns_value(then_branch) || block_returns_value(else_branch),
+        Stmt::While { body, .. } | Stmt::ForRange { body, .. } | Stmt::ForOf { body, .. } => {
+            block_returns_value(body)
+        }
+        Stmt::Match { arms, .. } => arms.iter().any(|arm| block_returns_value(&arm.body)),
+        Stmt::Await(inner) => matches!(**inner, Stmt::Return(Some(_))),
+        _ => false,
+    })
+}
+
+/// Collects the variables `stmt` assigns as a whole (`x = ..`),
+/// looking into nested blocks but not into closures.
+fn assigned_names(stmt: &Stmt, out: &mut Vec<String>) {
+    let mut block = |block: &Block| {
+        for stmt in block {
+            assigned_names(&stmt.value, out);
+        }
+    };
+    match stmt {
+        Stmt::Assign { target, .. } => {
+            if let ExprKind::Ident(name) = &target.kind {
+                out.push(name.clone());
+            }
+        }
+        Stmt::If {
+            then_branch,
+            else_branch,
+            ..
+        } => {
+            block(then_branch);
+            block(else_branch);
+        }
+        Stmt::While { body, .. } | Stmt::ForRange { body, .. } | Stmt::ForOf { body, .. } => {
+            block(body)
+        }
+        Stmt::Match { arms, .. } => arms.iter().for_each(|arm| block(&arm.body)),
+        Stmt::Await(inner) => assigned_names(inner, out),
+        _ => {}
+    }
+}
+
+/// The statements destructuring the parameters written as patterns, which
+/// the parser gave hidden names.
+fn destructure_params(params: &[Param]) -> Block {
+    params
+        .iter()
+        .filter_map(|param| {
+            let pattern = param.pattern.clone()?;
+            let value = Expr::new(ExprKind::Ident(param.name.clone()), param.line);
+            let destructure = Stmt::Destructure {
+                pattern,
+                mutable: true,
+                ty: None,
+                value,
+            };
+            Some(Spanned {
+                value: destructure,
+                line: param.line,
+            })
+        })
+        .collect()
+}
+
+/// `print`, the Result constructors and the numeric conversions, which calls
+/// always reach; user functions cannot take their names.
+fn is_builtin_function(name: &str) -> bool {
+    matches!(name, "print" | "ok" | "err")
+        || crate::parser::parse_type_annotation(name).is_numeric()
+}
+
+fn is_printable(ty: &TypeAnnotation) -> bool {
+    ty.is_numeric() || *ty == TypeAnnotation::Bool || ty.is_string()
+}
+
+/// Value of an integer literal (possibly negated), used for compile-time checks.
+fn const_int(expr: &Expr) -> Option<i128> {
+    match &expr.kind {
+        ExprKind::Int(v) => Some(i128::from(*v)),
+        ExprKind::Unary {
+            op: UnaryOp::Neg,
+            expr,
+        } => const_int(expr).map(|v| -v),
+        _ => None,
+    }
+}
+
+/// `value` is within the range of the numeric type `ty`.
+fn literal_fits(value: i128, ty: &TypeAnnotation) -> bool {
+    match ty {
+        TypeAnnotation::Int => i32::try_from(value).is_ok(),
+        TypeAnnotation::I64 => i64::try_from(value).is_ok(),
+        TypeAnnotation::U64 => u64::try_from(value).is_ok(),
+        TypeAnnotation::U8 => u8::try_from(value).is_ok(),
+        _ => true,
+    }
+}
+
+/// Type of an integer literal without context: the first of `int`, `i64` and
+/// `u64` that holds it.
+fn literal_type(value: i128, line: usize) -> Result<TypeAnnotation> {
+    [
+        TypeAnnotation::Int,
+        TypeAnnotation::I64,
+        TypeAnnotation::U64,
+    ]
+    .into_iter()
+    .find(|ty| literal_fits(value, ty))
+    .ok_or_else(|| anyhow!("行 {}: 整数リテラル {} は i64 の範囲外です", line, value))
+}
+
+/// The numeric type an integer literal takes where `expected` is required
+/// (`let big: i64 = 1`, `let b: u8? = 1`); `None` keeps the literal's own type.
+fn literal_target(expected: &TypeAnnotation) -> Option<TypeAnnotation> {
+    match expected {
+        TypeAnnotation::Nullable(inner) => literal_target(inner),
+        TypeAnnotation::Union(members) if !members.contains(&TypeAnnotation::Int) => {
+            members.iter().find(|m| m.is_numeric()).cloned()
+        }
+        ty if ty.is_numeric() => Some(ty.clone()),
+        _ => None,
+    }
+}
+
+/// Gives the integer literal `expr` (possibly negated) the numeric type `ty`.
+/// `Ok(false)` when `expr` is not an integer literal.
+fn retype_literal(expr: &mut Expr, ty: &TypeAnnotation) -> Result<bool> {
+    let Some(value) = const_int(expr) else {
+        return Ok(false);
+    };
+    if !literal_fits(value, ty) {
+        return Err(anyhow!(
+            "行 {}: 整数リテラル {} は {} の範囲外です",
+            expr.line,
+            value,
+            ty
+        ));
+    }
+    let mut node = expr;
+    loop {
+        node.ty = Some(ty.clone());
+        match &mut node.kind {
+            ExprKind::Unary { expr, .. } => node = expr,
+            _ => return Ok(true),
+        }
+    }
+}
+
+/// For a condition that tests a variable (`x is T`, `x != none`, `x == none`),
+/// the locals that narrow `x` when the condition holds and when it does not.
+type Narrowed = Option<(String, Local)>;
+
+fn narrowing(condition: &Expr, ctx: &FnContext, line: usize) -> Result<(Narrowed, Narrowed)> {
+    // (tested variable, tested member for `is`, whether `== none` tests for none)
+    let (expr, member, tests_none) = match &condition.kind {
+        ExprKind::Is { expr, ty } => (expr, Some(ty), false),
+        ExprKind::Binary {
+            op: op @ (BinaryOp::Eq | BinaryOp::Ne),
+            lhs,
+            rhs,
+        } => match (&lhs.kind, &rhs.kind) {
+            (_, ExprKind::None) => (lhs, None, *op == BinaryOp::Eq),
+            (ExprKind::None, _) => (rhs, None, *op == BinaryOp::Eq),
+            _ => return Ok((None, None)),
+        },
+        _ => return Ok((None, None)),
+    };
+    // an already narrowed variable reads as `Coerce(x)`
+    let variable = match &expr.kind {
+        ExprKind::Coerce(inner) => inner,
+        _ => expr,
+    };
+    let ExprKind::Ident(name) = &variable.kind else {
+        return Ok((None, None));
+    };
+    let local = ctx.lookup(name).expect("checked variable");
+    let base = local
+        .narrowed_from
+        .clone()
+        .unwrap_or_else(|| local.ty.clone());
+    let narrowed = |ty: TypeAnnotation| {
+        let local = Local {
+            ty,
+            binding: local.binding,
+            narrowed_from: Some(base.clone()),
+            id: local.id,
+        };
+        Some((name.clone(), local))
+    };
+    match (member, &local.ty) {
+        (Some(ty), TypeAnnotation::Union(members)) => {
+            let rest: Vec<TypeAnnotation> = members.iter().filter(|m| *m != ty).cloned().collect();
+            let otherwise = if rest.is_empty() {
+                None
+            } else {
+                narrowed(union_of(rest, line)?)
+            };
+            Ok((narrowed(ty.clone()), otherwise))
+        }
+        (None, TypeAnnotation::Nullable(inner)) => {
+            let present = narrowed((**inner).clone());
+            if tests_none {
+                Ok((None, present))
+            } else {
+                Ok((present, None))
+            }
+        }
+        _ => Ok((None, None)),
+    }
+}
+
+/// Binds the payload names of a match arm as read-only locals.
+fn declare_bindings(
+    ctx: &mut FnContext,
+    bindings: Vec<(String, TypeAnnotation)>,
+    line: usize,
+) -> Result<()> {
+    for (name, ty) in bindings {
+        ctx.declare(&name, ty, Binding::Const, line)?;
+    }
+    Ok(())
+}
+
+/// `break` / `continue` must sit inside a loop, and a label must name an enclosing loop.
+fn check_jump(keyword: &str, label: Option<&str>, line: usize, ctx: &FnContext) -> Result<()> {
+    if ctx.loops.is_empty() {
+        return Err(anyhow!(
+            "行 {}: {} はループの外では使えません",
+            line,
+            keyword
+        ));
+    }
+    if let Some(label) = label
+        && !ctx.loops.iter().any(|l| l.as_deref() == Some(label))
+    {
+        return Err(anyhow!(
+            "行 {}: {} のラベル '{}' に対応するループがありません",
+            line,
+            keyword,
+            label
+        ));
+    }
+    Ok(())
+}
+
+/// Rejects structs and enums that contain themselves by value (directly or
+/// through other types); such a C type would have infinite size.
+fn check_type_cycles(
+    structs: &HashMap<String, StructDecl>,
+    enums: &HashMap<String, EnumDecl>,
+) -> Result<()> {
+    // (declaration line, type names stored by value)
+    let members = |name: &str| -> (usize, Vec<String>) {
+        let (line, types): (usize, Vec<&TypeAnnotation>) =
+            match (structs.get(name), enums.get(name)) {
+                (Some(decl), _) => (decl.line, decl.fields.iter().map(|f| &f.ty).collect()),
+                (None, Some(decl)) => (
+                    decl.line,
+                    decl.variants.iter().flat_map(|v| &v.fields).collect(),
+                ),
+                (None, None) => (0, Vec::new()),
+            };
+        let mut names = Vec::new();
+        for ty in types {
+            value_parts(ty, &mut names);
+        }
+        (line, names)
+    };
+
+    fn visit(
+        name: &str,
+        members: &dyn Fn(&str) -> (usize, Vec<String>),
+        path: &mut Vec<String>,
+    ) -> Result<()> {
+        let (line, inner) = members(name);
+        if path.iter().any(|seen| seen == name) {
+            return Err(anyhow!(
+                "行 {}: 型 {} は自分自身を値として含んでいます ({} -> {})",
+                line,
+                name,
+                path.join(" -> "),
+                name
+            ));
+        }
+        path.push(name.to_string());
+        for inner in &inner {
+            visit(inner, members, path)?;
+        }
+        path.pop();
+        Ok(())
+    }
+
+    let mut names: Vec<&String> = structs.keys().chain(enums.keys()).collect();
+    names.sort();
+    for name in names {
+        visit(name, &members, &mut Vec::new())?;
+    }
+    Ok(())
+}
+
+impl Checker {
+    fn new(program: &Program, types: TypeNames) -> Result<Self> {
+        let mut structs = HashMap::new();
+        for decl in &program.structs {
+            let mut seen = HashSet::new();
+            for field in &decl.fields {
+                if !seen.insert(field.name.as_str()) {
+                    return Err(anyhow!(
+                        "行 {}: 構造体 {} のフィールド '{}' が重複しています",
+                        field.line,
+                        decl.name,
+                        field.name
+                    ));
+                }
+                if matches!(field.ty, TypeAnnotation::Void) {
+                    return Err(anyhow!(
+                        "行 {}: フィールド '{}' に void 型は使えません",
+                        field.line,
+                        field.name
+                    ));
+                }
+            }
+            if structs.insert(decl.name.clone(), decl.clone()).is_some() {
+                return Err(anyhow!(
+                    "行 {}: 構造体 {} が重複して宣言されています",
+                    decl.line,
+                    decl.name
+                ));
+            }
+        }
+        let mut enums = HashMap::new();
+        for decl in &program.enums {
+            if decl.variants.is_empty() {
+                return Err(anyhow!(
+                    "行 {}: enum {} には少なくとも 1 つのバリアントが必要です",
+                    decl.line,
+                    decl.name
+                ));
+            }
+            let mut seen = HashSet::new();
+            for variant in &decl.variants {
+                if !seen.insert(variant.name.as_str()) {
+                    return Err(anyhow!(
+                        "行 {}: enum {} のバリアント '{}' が重複しています",
+                        variant.line,
+                        decl.name,
+                        variant.name
+                    ));
+                }
+                if variant.fields.contains(&TypeAnnotation::Void) {
+                    return Err(anyhow!(
+                        "行 {}: バリアント '{}' に void 型の値は持たせられません",
+                        variant.line,
+                        variant.name
+                    ));
+                }
+            }
+            if structs.contains_key(&decl.name)
+                || enums.insert(decl.name.clone(), decl.clone()).is_some()
+            {
+                return Err(anyhow!(
+                    "行 {}: 型 {} が重複して宣言されています",
+                    decl.line,
+                    decl.name
+                ));
+            }
+        }
+        check_type_cycles(&structs, &enums)?;
+        let mut interfaces = HashMap::new();
+        for decl in &program.interfaces {
+            let mut seen = HashSet::new();
+            for method in &decl.methods {
+                if !seen.insert(method.name.as_str()) {
+                    return Err(anyhow!(
+                        "行 {}: インターフェース {} のメソッド '{}' が重複しています",
+                        method.line,
+                        decl.name,
+                        method.name
+                    ));
+                }
+            }
+            if structs.contains_key(&decl.name)
+                || enums.contains_key(&decl.name)
+                || interfaces.insert(decl.name.clone(), decl.clone()).is_some()
+            {
+                return Err(anyhow!(
+                    "行 {}: 型 {} が重複して宣言されています",
+                    decl.line,
+                    decl.name
+                ));
+            }
+        }
+
+        let mut functions = HashMap::new();
+        for func in &program.functions {
+            let params = func.params.iter().map(|param| param.ty.clone()).collect();
+            let ret = func.return_type.clone().unwrap_or(TypeAnnotation::Void);
+            let sig = FnSig {
+                type_params: func.type_params.clone(),
+                params,
+                ret,
+                receiver: func.receiver,
+            };
+            let name = func.full_name();
+            // functions merged from imported modules name their file
+            let origin = match &func.source {
+                Some(path) => format!(" ({} から import)", path.display()),
+                None => String::new(),
+            };
+            if let Some(owner) = &func.owner {
+                check_method(func, owner, &structs, &enums)?;
+                if functions.contains_key(&name) {
+                    return Err(anyhow!(
+                        "行 {}: メソッド {} が重複して宣言されています{}",
+                        func.line,
+                        name,
+                        origin
+                    ));
+                }
+            } else if is_builtin_function(&name) {
+                return Err(anyhow!(
+                    "行 {}: {} は組み込み関数の名前なので、関数名には使えません{}",
+                    func.line,
+                    name,
+                    origin
+                ));
+            } else if functions.contains_key(&name) {
+                return Err(anyhow!(
+                    "行 {}: 関数 {} が重複して宣言されています{}",
+                    func.line,
+                    name,
+                    origin
+                ));
+            }
+            functions.insert(name, sig);
+        }
+        Ok(Self {
+            types,
+            structs,
+            enums,
+            interfaces,
+            functions,
+            instances: RefCell::new(Vec::new()),
+        })
+    }
+
+    /// `ty` with the type parameters bound by `bindings`, normalized.
+    fn instantiate(
+        &self,
+        ty: &TypeAnnotation,
+        bindings: &HashMap<String, TypeAnnotation>,
+        line: usize,
+    ) -> Result<TypeAnnotation> {
+        let mut ty = ty.clone();
+        substitute_names(&mut ty, bindings);
+        self.types.resolve(&mut ty, line)?;
+        Ok(ty)
+    }
+
+    /// Checks the instance of the generic function `template` for the given
+    /// type arguments: those of its type parameters, followed by the struct
+    /// types passed for its interface-typed parameters.
+    fn instantiate_function(
+        &self,
+        template: &Function,
+        type_args: Vec<TypeAnnotation>,
+    ) -> Result<Function> {
+        let bindings: HashMap<String, TypeAnnotation> = template
+            .type_params
+            .iter()
+            .cloned()
+            .zip(type_args.iter().cloned())
+            .collect();
+        let mut implementations = type_args[template.type_params.len()..].iter();
+        let mut func = template.clone();
+        for param in &mut func.params {
+            param.ty = match param.ty {
+                TypeAnnotation::Interface(_) => implementations
+                    .next()
+                    .expect("type argument per interface parameter")
+                    .clone(),
+                _ => self.instantiate(&param.ty, &bindings, param.line)?,
+            };
+        }
+        if let Some(ret) = &mut func.return_type {
+            *ret = self.instantiate(ret, &bindings, func.line)?;
+        }
+        func.type_args = type_args;
+        self.check_function(&mut func)?;
+        Ok(func)
+    }
+
+    /// Replaces the generic structs of `program` by one instance per generic
+    /// struct type it uses.
+    fn instantiate_structs(&self, program: &mut Program) -> Result<()> {
+        fn add(ty: &TypeAnnotation, used: &mut Vec<TypeAnnotation>) {
+            visit_types(ty, &mut |inner| {
+                if let TypeAnnotation::Generic(..) = inner
+                    && !has_type_param(inner)
+                    && !is_undetermined(inner)
+                    && !used.contains(inner)
+                {
+                    used.push(inner.clone());
+                }
+            })
+        }
+        let mut used = Vec::new();
+        program.structs.retain(|decl| decl.type_params.is_empty());
+        for decl in &program.structs {
+            decl.fields
+                .iter()
+                .for_each(|field| add(&field.ty, &mut used));
+        }
+        for decl in &program.enums {
+            decl.variants
+                .iter()
+                .flat_map(|v| &v.fields)
+                .for_each(|ty| add(ty, &mut used));
+        }
+        for func in &program.functions {
+            func.params
+                .iter()
+                .for_each(|param| add(&param.ty, &mut used));
+            if let Some(ret) = &func.return_type {
+                add(ret, &mut used);
+            }
+            walk_block(&func.body, &mut |expr| {
+                if let Some(ty) = &expr.ty {
+                    add(ty, &mut used);
+                }
+            });
+        }
+        // instances may use further instances in their fields
+        let mut next = 0;
+        while let Some(TypeAnnotation::Generic(name, type_args)) = used.get(next).cloned() {
+            next += 1;
+            let template = &self.structs[&name];
+            let bindings: HashMap<String, TypeAnnotation> = template
+                .type_params
+                .iter()
+                .cloned()
+                .zip(type_args.iter().cloned())
+                .collect();
+            let mut decl = template.clone();
+            for field in &mut decl.fields {
+                field.ty = self.instantiate(&field.ty, &bindings, field.line)?;
+                add(&field.ty, &mut used);
+            }
+            decl.type_params.clear();
+            decl.type_args = type_args;
+            program.structs.push(decl);
+        }
+        Ok(())
+    }
+
+    /// `actual` can be used where `expected` is required. Structs are compared
+    /// structurally: two struct types with the same field names and compatible
+    /// field types are interchangeable. A union accepts each of its members and
+    /// any union made of its members; string literal types are strings. `T?`
+    /// accepts `none`, `T` and nullable values of a compatible type. Results
+    /// must match exactly, except for the side `ok(..)` / `err(..)` leaves open;
+    /// so must array element types, since arrays are shared by reference.
+    fn compatible(&self, expected: &TypeAnnotation, actual: &TypeAnnotation) -> bool {
+        if expected == actual {
+            return true;
+        }
+        match (expected, actual) {
+            (TypeAnnotation::Nullable(_), TypeAnnotation::None) => true,
+            (TypeAnnotation::Nullable(expected), TypeAnnotation::Nullable(actual)) => {
+                self.compatible(expected, actual)
+            }
+            (TypeAnnotation::Nullable(expected), actual) => self.compatible(expected, actual),
+            (
+                TypeAnnotation::Result(value, error),
+                TypeAnnotation::Result(actual_value, actual_error),
+            ) => {
+                // the side `ok(..)` / `err(..)` leaves open fits any type
+                let fits = |expected: &TypeAnnotation, actual: &TypeAnnotation| {
+                    *actual == TypeAnnotation::None || expected == actual
+                };
+                fits(value, actual_value) && fits(error, actual_error)
+            }
+            (TypeAnnotation::Array(_), TypeAnnotation::Array(actual_elem)) => {
+                // the empty literal `[]` fits any array type
+                **actual_elem == TypeAnnotation::None
+            }
+            // and `new Map()` / `new Set()` any Map / Set type
+            (TypeAnnotation::Map(..), TypeAnnotation::Map(actual_key, _))
+            | (TypeAnnotation::Set(_), TypeAnnotation::Set(actual_key)) => {
+                **actual_key == TypeAnnotation::None
+            }
+            (TypeAnnotation::String, actual) => actual.is_string(),
+            // tuples are values, so their elements convert one by one
+            (TypeAnnotation::Tuple(elems), TypeAnnotation::Tuple(actual_elems)) => {
+                elems.len() == actual_elems.len()
+                    && elems
+                        .iter()
+                        .zip(actual_elems)
+                        .all(|(elem, actual)| self.compatible(elem, actual))
+            }
+            (TypeAnnotation::Union(members), TypeAnnotation::Union(actual_members)) => {
+                actual_members.iter().all(|m| members.contains(m))
+            }
+            (TypeAnnotation::Union(members), actual) => {
+                members.iter().any(|m| self.compatible(m, actual))
+            }
+            (
+                TypeAnnotation::Named(_) | TypeAnnotation::Generic(..) | TypeAnnotation::Object(_),
+                actual,
+            ) => {
+                let (Some(expected_fields), Some(actual_fields)) =
+                    (self.record_fields(expected), self.record_fields(actual))
+                else {
+                    return false;
+                };
+                // a struct takes exactly its fields, an object type also
+                // values with more of them (width subtyping)
+                (matches!(expected, TypeAnnotation::Object(_))
+                    || expected_fields.len() == actual_fields.len())
+                    && expected_fields.iter().all(|(name, ty)| {
+                        actual_fields
+                            .iter()
+                            .any(|(other, other_ty)| other == name && self.compatible(ty, other_ty))
+                    })
+            }
+            _ => false,
+        }
+    }
+
+    /// Checks that values of type `actual` have every method the interface
+    /// `name` requires, with the same parameter and return types. The error
+    /// lists every method that is missing or does not match.
+    fn check_satisfies(&self, actual: &TypeAnnotation, name: &str, line: usize) -> Result<()> {
+        let mut problems = Vec::new();
+        for method in &self.interfaces[name].methods {
+            let wanted = method_type(method);
+            let found = match actual {
+                TypeAnnotation::Named(owner) => self
+                    .functions
+                    .get(format!("{}.{}", owner, method.name).as_str())
+                    .filter(|sig| sig.receiver.is_some())
+                    .map(|sig| {
+                        TypeAnnotation::Function(sig.params.clone(), Box::new(sig.ret.clone()))
+                    }),
+                TypeAnnotation::Interface(other) => self.interfaces[other]
+                    .methods
+                    .iter()
+                    .find(|m| m.name == method.name)
+                    .map(method_type),
+                _ => None,
+            };
+            match found {
+                Some(found) if found == wanted => {}
+                Some(found) => problems.push(format!(
+                    "{} の型が {} ではなく {} です",
+                    method.name, wanted, found
+                )),
+                None => problems.push(format!("{} がありません", method_signature(method))),
+            }
+        }
+        if problems.is_empty() {
+            return Ok(());
+        }
+        Err(anyhow!(
+            "行 {}: {} はインターフェース {} を満たしていません: {}",
+            line,
+            actual,
+            name,
+            problems.join(", ")
+        ))
+    }
+
+    /// Infers the type arguments of a call to the generic function `callee`
+    /// from the (already checked) arguments and records the instance. The
+    /// argument types of interface-typed parameters come last.
+    fn infer_call(
+        &self,
+        callee: &str,
+        sig: &FnSig,
+        args: &[Expr],
+        line: usize,
+    ) -> Result<Vec<TypeAnnotation>> {
+        let mut bindings = HashMap::new();
+        for (arg, param) in args.iter().zip(&sig.params) {
+            infer_type_args(param, arg.ty(), &mut bindings);
+        }
+        let type_args = sig
+            .type_params
+            .iter()
+            .map(|name| {
+                bindings.remove(name).ok_or_else(|| {
+                    anyhow!(
+                        "行 {}: 関数 {} の型引数 {} を引数から推論できません",
+                        line,
+                        callee,
+                        name
+                    )
+                })
+            })
+            .collect::<Result<Vec<_>>>()?;
+        let mut type_args = type_args;
+        for (arg, param) in args.iter().zip(&sig.params) {
+            if let TypeAnnotation::Interface(name) = param {
+                self.check_satisfies(arg.ty(), name, arg.line)?;
+                type_args.push(arg.ty().clone());
+            }
+        }
+        // calls inside a generic function are instantiated with the caller
+        if !type_args.iter().any(has_type_param) {
+            let mut instances = self.instances.borrow_mut();
+            let instance = (callee.to_string(), type_args.clone());
+            if !instances.contains(&instance) {
+                if instances.iter().filter(|(name, _)| name == callee).count() >= MAX_INSTANCES {
+                    return Err(anyhow!(
+                        "行 {}: 関数 {} の型引数が際限なく増えています (再帰呼び出しで型引数を大きくしていないか確認してください)",
+                        line,
+                        callee
+                    ));
+                }
+                instances.push(instance);
+            }
+        }
+        Ok(type_args)
+    }
+
+    /// Resolves a type written inside a function body, where the function's
+    /// type parameters are in scope.
+    fn resolve_local(&self, ty: &mut TypeAnnotation, ctx: &FnContext, line: usize) -> Result<()> {
+        substitute_names(ty, &ctx.type_args);
+        self.types.resolve(ty, line)?;
+        reject_interface(ty, line)
+    }
+
+    /// The fields of a struct or object type.
+    fn record_fields(&self, ty: &TypeAnnotation) -> Option<Vec<(String, TypeAnnotation)>> {
+        match ty {
+            TypeAnnotation::Named(name) => self.structs.get(name).map(|decl| {
+                decl.fields
+                    .iter()
+                    .map(|f| (f.name.clone(), f.ty.clone()))
+                    .collect()
+            }),
+            TypeAnnotation::Generic(name, type_args) => {
+                let decl = self.structs.get(name)?;
+                let bindings: HashMap<String, TypeAnnotation> = decl
+                    .type_params
+                    .iter()
+                    .cloned()
+                    .zip(type_args.iter().cloned())
+                    .collect();
+                decl.fields
+                    .iter()
+                    .map(|f| {
+                        Some((
+                            f.name.clone(),
+                            self.instantiate(&f.ty, &bindings, f.line).ok()?,
+                        ))
+                    })
+                    .collect()
+            }
+            TypeAnnotation::Object(fields) => Some(fields.clone()),
+            _ => None,
+        }
+    }
+
+    /// Checks that the already-typed `expr` fits `expected`, wrapping it in a
+    /// `Coerce` node when the C representation differs (structurally
+    /// compatible structs and objects, values stored into a tagged union or a
+    /// nullable).
+    fn coerce(&self, expr: &mut Expr, expected: &TypeAnnotation) -> bool {
+        // `ok(..)` / `err(..)` take the expected Result type
+        if let TypeAnnotation::Result(value_ty, error_ty) = expected {
+            let fits = match &mut expr.kind {
+                ExprKind::ResultOk(None) => **value_ty == TypeAnnotation::Void,
+                ExprKind::ResultOk(Some(value)) => {
+                    **value_ty != TypeAnnotation::Void && self.coerce(value, value_ty)
+                }
+                ExprKind::ResultErr(error) => self.coerce(error, error_ty),
+                _ => return self.coerce_value(expr, expected),
+            };
+            if fits {
+                expr.ty = Some(expected.clone());
+            }
+            return fits;
+        }
+        // an array literal takes the expected element type (`let xs: int?[] = [1]`)
+        if let (TypeAnnotation::Array(elem), ExprKind::ArrayLit(elems)) = (expected, &mut expr.kind)
+        {
+            let fits = elems.iter_mut().all(|value| self.coerce(value, elem));
+            if fits {
+                expr.ty = Some(expected.clone());
+            }
+            return fits;
+        }
+        // and so does a tuple literal (`let p: (int, int?) = (1, none)`)
+        if let (TypeAnnotation::Tuple(expected_elems), ExprKind::TupleLit(elems)) =
+            (expected, &mut expr.kind)
+        {
+            let fits = elems.len() == expected_elems.len()
+                && elems
+                    .iter_mut()
+                    .zip(expected_elems)
+                    .all(|(value, elem)| self.coerce(value, elem));
+            if fits {
+                expr.ty = Some(expected.clone());
+            }
+            return fits;
+        }
+        // an object literal takes the expected field types (`{ x: none }` for a
+        // `{ x: int? }`); extra fields are dropped by the conversion below. So
+        // does a generic struct literal (`Box { value: none }` for a `Box<int?>`)
+        let literal_fields = match &mut expr.kind {
+            ExprKind::ObjectLit(fields) => Some(fields),
+            ExprKind::StructLit { name, fields } if matches!(expected, TypeAnnotation::Generic(generic, _) if generic == name) => {
+                Some(fields)
+            }
+            _ => None,
+        };
+        if let Some(fields) = literal_fields
+            && let Some(expected_fields) = self.record_fields(expected)
+        {
+            let mut fits = true;
+            for (name, ty) in &expected_fields {
+                match fields.iter_mut().find(|(field, _)| field == name) {
+                    Some((_, value)) => fits &= self.coerce(value, ty),
+                    None => fits = false,
+                }
+            }
+            if !fits {
+                return false;
+            }
+            if fields.len() == expected_fields.len() {
+                expr.ty = Some(expected.clone());
+                return true;
+            }
+            let actual = fields
+                .iter()
+                .map(|(name, value)| (name.clone(), value.ty().clone()))
+                .collect();
+            expr.ty = Some(object_type(actual));
+            return self.coerce_value(expr, expected);
+        }
+        // so does `new Map()` / `new Set()` without type arguments
+        if let ExprKind::NewCollection(_) = expr.kind
+            && matches!(expected, TypeAnnotation::Map(..) | TypeAnnotation::Set(_))
+        {
+            let fits = self.compatible(expected, expr.ty());
+            if fits {
+                expr.ty = Some(expected.clone());
+            }
+            return fits;
+        }
+        self.coerce_value(expr, expected)
+    }
+
+    fn coerce_value(&self, expr: &mut Expr, expected: &TypeAnnotation) -> bool {
+        // an integer literal takes the numeric type expected of it
+        if let Some(target) = literal_target(expected)
+            && retype_literal(expr, &target).is_err()
+        {
+            return false;
+        }
+        // a string literal takes its literal type where one is expected
+        if let ExprKind::Str(text) = &expr.kind {
+            let literal = TypeAnnotation::Literal(text.clone());
+            let target = match expected {
+                TypeAnnotation::Nullable(inner) => inner,
+                other => other,
+            };
+            let accepts = match target {
+                TypeAnnotation::Union(members) => members.contains(&literal),
+                other => other == &literal,
+            };
+            if accepts {
+                expr.ty = Some(literal);
+            }
+        }
+        let actual = expr.ty().clone();
+        if !self.compatible(expected, &actual) {
+            return false;
+        }
+        if let TypeAnnotation::Nullable(inner) = expected
+            && !matches!(actual, TypeAnnotation::Nullable(_) | TypeAnnotation::None)
+        {
+            // wrap the value converted to the inner type
+            self.coerce(expr, inner);
+        }
+        if let Some(members) = tagged_members(expected)
+            && !matches!(actual, TypeAnnotation::Union(_))
+            && !members.contains(&actual)
+        {
+            // store through the structurally equal member
+            let member = members
+                .iter()
+                .find(|m| self.compatible(m, &actual))
+                .expect("compatible with some member")
+                .clone();
+            self.coerce(expr, &member);
+        }
+        if &actual != expected {
+            let line = expr.line;
+            let inner = std::mem::replace(expr, Expr::new(ExprKind::Bool(false), line));
+            *expr = Expr {
+                kind: ExprKind::Coerce(Box::new(inner)),
+                line,
+                ty: Some(expected.clone()),
+            };
+        }
+        true
+    }
+
+    fn check_function(&self, func: &mut Function) -> Result<()> {
+        let name = func.full_name();
+        if name == "main" {
+            match func.return_type {
+                Some(TypeAnnotation::Int) | Some(TypeAnnotation::Void) => {}
+                Some(ref other) => {
+                    return Err(anyhow!(
+                        "行 {}: main は戻り値型として int もしくは void を明示してください (指定: {})",
+                        func.line,
+                        other
+                    ));
+                }
+                None => {
+                    return Err(anyhow!(
+                        "行 {}: main は戻り値型として int もしくは void を明示してください",
+                        func.line
+                    ));
+                }
+            }
+        }
+
+        if name == "main" && !func.params.is_empty() {
+            return Err(anyhow!("行 {}: main は引数を取れません", func.line));
+        }
+
+        let expected = func.return_type.clone().unwrap_or(TypeAnnotation::Void);
+        // a generic function is checked with opaque type parameters, an
+        // instance with its type arguments
+        let type_args = if func.type_args.is_empty() {
+            type_param_map(&func.type_params, func.line)?
+        } else {
+            func.type_params
+                .iter()
+                .cloned()
+                .zip(func.type_args.iter().cloned())
+                .collect()
+        };
+        let mut ctx = FnContext::new(&name, &expected, type_args);
+        if let (Some(owner), Some(receiver)) = (&func.owner, func.receiver) {
+            let binding = match receiver {
+                Receiver::Shared => Binding::SelfRef,
+                Receiver::Mut => Binding::SelfMut,
+            };
+            ctx.declare(
+                "self",
+                TypeAnnotation::Named(owner.clone()),
+                binding,
+                func.line,
+            )?;
+        }
+        for param in &func.params {
+            if matches!(param.ty, TypeAnnotation::Void) {
+                return Err(anyhow!(
+                    "行 {}: 引数 '{}' に void 型は使えません",
+                    param.line,
+                    param.written_name()
+                ));
+            }
+            ctx.declare(&param.name, param.ty.clone(), Binding::Param, param.line)?;
+        }
+        func.body.splice(0..0, destructure_params(&func.params));
+        self.check_block(&mut func.body, &mut ctx)?;
+        for param in &mut func.params {
+            param.captured = ctx.is_boxed(&ctx.scopes[0], &param.name);
+        }
+
+        if name != "main"
+            && !matches!(expected, TypeAnnotation::Void)
+            && !block_contains_return(&func.body)
+        {
+            return Err(anyhow!(
+                "行 {}: 関数 {} は {} を返す必要がありますが、return が見つかりません",
+                func.line,
+                name,
+                expected
+            ));
+        }
+
+        Ok(())
+    }
+
+    fn check_block(&self, block: &mut Block, ctx: &mut FnContext) -> Result<()> {
+        self.check_block_then(block, ctx, |_| Ok(()))
+    }
+
+    /// Checks `block` and then `tail` with the variables of the block still in
+    /// scope (the expression body of a closure after its destructured
+    /// parameters).
+    fn check_block_then<T>(
+        &self,
+        block: &mut Block,
+        ctx: &mut FnContext,
+        tail: impl FnOnce(&mut FnContext) -> Result<T>,
+    ) -> Result<T> {
+        ctx.scopes.push(HashMap::new());
+        let mut result = Ok(());
+        let mut index = 0;
+        while index < block.len() && result.is_ok() {
+            let line = block[index].line;
+            result = match &mut block[index].value {
+                stmt @ (Stmt::While { .. } | Stmt::ForRange { .. } | Stmt::ForOf { .. }) => {
+                    self.check_loop_stmt(stmt, line, ctx)
+                }
+                Stmt::Destructure { .. } => self
+                    .expand_destructure(&mut block[index].value, line, ctx)
+                    .map(|parts| {
+                        let parts = parts.into_iter().map(|value| Spanned { value, line });
+                        block.splice(index + 1..index + 1, parts);
+                    }),
+                stmt => self.check_stmt(stmt, line, ctx),
+            };
+            index += 1;
+        }
+        let result = result.and_then(|_| tail(ctx));
+        let scope = ctx.scopes.pop().expect("block scope");
+        for stmt in block.iter_mut() {
+            if let Stmt::Let { name, captured, .. } = &mut stmt.value {
+                *captured = ctx.is_boxed(&scope, name);
+            }
+        }
+        result
+    }
+
+    /// Replaces the destructuring `stmt` by a hidden variable holding the
+    /// value and returns the statements binding its parts, which come right
+    /// after it (nested patterns are expanded again when they are reached).
+    fn expand_destructure(
+        &self,
+        stmt: &mut Stmt,
+        line: usize,
+        ctx: &mut FnContext,
+    ) -> Result<Vec<Stmt>> {
+        let Stmt::Destructure {
+            pattern,
+            mutable,
+            ty,
+            value,
+        } = stmt
+        else {
+            unreachable!("not a destructuring");
+        };
+        let (pattern, mutable) = (pattern.clone(), *mutable);
+        let value_ty = self.check_let_value(&pattern.to_string(), ty, value, line, ctx)?;
+        let hidden = format!("vts_destructure{}", ctx.next_id);
+        ctx.declare(&hidden, value_ty.clone(), Binding::Const, line)?;
+        let value = std::mem::replace(value, Expr::new(ExprKind::Bool(false), line));
+        *stmt = Stmt::Let {
+            name: hidden.clone(),
+            mutable: false,
+            ty: Some(value_ty.clone()),
+            value,
+            captured: false,
+        };
+
+        let source = || Box::new(Expr::new(ExprKind::Ident(hidden.clone()), line));
+        let field = |name: String| {
+            Expr::new(
+                ExprKind::Field {
+                    expr: source(),
+                    name,
+                },
+                line,
+            )
+        };
+        let parts: Vec<(BindingPattern, Expr)> = match (&pattern, &value_ty) {
+            (BindingPattern::Tuple(items), TypeAnnotation::Tuple(elems)) => {
+                if items.len() != elems.len() {
+                    return Err(anyhow!(
+                        "行 {}: {} 型の値は {} 個の要素を持ちますが、{} は {} 個の要素を取り出します",
+                        line,
+                        value_ty,
+                        elems.len(),
+                        pattern,
+                        items.len()
+                    ));
+                }
+                items
+                    .iter()
+                    .enumerate()
+                    .map(|(i, item)| (item.clone(), field(i.to_string())))
+                    .collect()
+            }
+            (BindingPattern::Array(items), TypeAnnotation::Array(_)) => items
+                .iter()
+                .enumerate()
+                .map(|(i, item)| {
+                    let index = Box::new(Expr::new(ExprKind::Int(i as u64), line));
+                    let kind = ExprKind::Index {
+                        expr: source(),
+                        index,
+                    };
+                    (item.clone(), Expr::new(kind, line))
+                })
+                .collect(),
+            (BindingPattern::Record(items), _) if self.record_fields(&value_ty).is_some() => {
+                let fields = self.record_fields(&value_ty).expect("record type");
+                let mut parts = Vec::new();
+                for (name, item) in items {
+                    if !fields.iter().any(|(field, _)| field == name) {
+                        return Err(anyhow!(
+                            "行 {}: {} 型にフィールド '{}' はありません",
+                            line,
+                            value_ty,
+                            name
+                        ));
+                    }
+                    parts.push((item.clone(), field(name.clone())));
+                }
+                parts
+            }
+            _ => {
+                let expected = match pattern {
+                    BindingPattern::Tuple(_) => "タプル",
+                    BindingPattern::Array(_) => "配列",
+                    _ => "struct もしくはオブジェクト",
+                };
+                return Err(anyhow!(
+                    "行 {}: {} 型の値は {} として分割代入できません ({}が必要です)",
+                    line,
+                    value_ty,
+                    pattern,
+                    expected
+                ));
+            }
+        };
+        Ok(parts
+            .into_iter()
+            .filter_map(|(item, value)| match item {
+                BindingPattern::Name(name) if name == "_" => None,
+                BindingPattern::Name(name) => Some(Stmt::Let {
+                    name,
+                    mutable,
+                    ty: None,
+                    value,
+                    captured: false,
+                }),
+                pattern => Some(Stmt::Destructure {
+                    pattern,
+                    mutable,
+                    ty: None,
+                    value,
+                }),
+            })
+            .collect())
+    }
+
+    /// Checks the value bound to `name` (or to a destructuring pattern) by a
+    /// `let` / `const` and returns the type of the binding.
+    fn check_let_value(
+        &self,
+        name: &str,
+        ty: &mut Option<TypeAnnotation>,
+        value: &mut Expr,
+        line: usize,
+        ctx: &mut FnContext,
+    ) -> Result<TypeAnnotation> {
+        let value_ty = match ty {
+            Some(declared) => {
+                self.resolve_local(declared, ctx, line)?;
+                self.expect_type(value, declared, ctx)?;
+                declared.clone()
+            }
+            None => self.check_expr(value, ctx)?,
+        };
+        if matches!(value_ty, TypeAnnotation::Void) {
+            return Err(anyhow!(
+                "行 {}: '{}' に void の値は束縛できません",
+                line,
+                name
+            ));
+        }
+        if matches!(value_ty, TypeAnnotation::None) {
+            return Err(anyhow!(
+                "行 {}: none だけでは '{}' の型を決められません (例: let {}: int? = none)",
+                line,
+                name,
+                name
+            ));
+        }
+        if let TypeAnnotation::Array(elem) = &value_ty
+            && **elem == TypeAnnotation::None
+        {
+            return Err(anyhow!(
+                "行 {}: 空の配列だけでは '{}' の型を決められません (例: let {}: int[] = [])",
+                line,
+                name,
+                name
+            ));
+        }
+        if let TypeAnnotation::Map(key, _) | TypeAnnotation::Set(key) = &value_ty
+            && **key == TypeAnnotation::None
+        {
+            return Err(anyhow!(
+                "行 {}: 型引数のない {} だけでは '{}' の型を決められません (例: let {}: Map<string, int> = new Map())",
+                line,
+                value_ty,
+                name,
+                name
+            ));
+        }
+        if matches!(value_ty, TypeAnnotation::Object(_)) && is_undetermined(&value_ty) {
+            return Err(anyhow!(
+                "行 {}: オブジェクトのフィールドの型を決められません。'{}' に型注釈を書いてください (例: let {}: {{ x: int? }} = {{ x: none }})",
+                line,
+                name,
+                name
+            ));
+        }
+        if let TypeAnnotation::Generic(generic, _) = &value_ty
+            && is_undetermined(&value_ty)
+        {
+            return Err(anyhow!(
+                "行 {}: フィールドの値だけでは {} の型引数を決められません。'{}' に型注釈を書いてください (例: let {}: {}<int?> = ..)",
+                line,
+                generic,
+                name,
+                name,
+                generic
+            ));
+        }
+        if matches!(value_ty, TypeAnnotation::Tuple(_)) && is_undetermined(&value_ty) {
+            return Err(anyhow!(
+                "行 {}: タプルの要素の型を決められません。'{}' に型注釈を書いてください (例: let {}: (int, int?) = (1, none))",
+                line,
+                name,
+                name
+            ));
+        }
+        if is_undetermined(&value_ty) {
+            return Err(anyhow!(
+                "行 {}: ok(..) / err(..) だけでは '{}' の型を決められません (例: let {}: Result<int, string> = ok(1))",
+                line,
+                name,
+                name
+            ));
+        }
+        Ok(value_ty)
+    }
+
+    fn check_stmt(&self, stmt: &mut Stmt, line: usize, ctx: &mut FnContext) -> Result<()> {
+        self.rewrite_std_statement(stmt, ctx)?;
+        match stmt {
+            Stmt::Await(inner) => self.check_stmt(inner, line, ctx),
+            Stmt::Print(expr) => {
+                let ty = self.check_expr(expr, ctx)?;
+                if !is_printable(&ty) {
+                    return Err(anyhow!(
+                        "行 {}: print に {} 型の値は渡せません",
+                        expr.line,
+                        ty
+                    ));
+                }
+                Ok(())
+            }
+            Stmt::Log { message, .. } => self.expect_type(message, &TypeAnnotation::String, ctx),
+            Stmt::SleepMs(ms) => self.expect_type(ms, &TypeAnnotation::Int, ctx),
+            Stmt::Expr(expr) => {
+                if let TypeAnnotation::Result(..) = self.check_expr(expr, ctx)? {
+                    return Err(anyhow!(
+                        "行 {}: Result の値が使われていません。? でエラーを返すか match で処理してください",
+                        line
+                    ));
+                }
+                Ok(())
+            }
+            Stmt::Let {
+                name,
+                mutable,
+                ty,
+                value,
+                ..
+            } => {
+                let value_ty = self.check_let_value(name, ty, value, line, ctx)?;
+                *ty = Some(value_ty.clone());
+                let binding = if *mutable {
+                    Binding::Let
+                } else {
+                    Binding::Const
+                };
+                ctx.declare(name, value_ty, binding, line)
+            }
+            Stmt::Destructure { .. } => unreachable!("check_block expands destructuring"),
+            Stmt::Assign { target, op, value } => self.check_assign(target, op, value, line, ctx),
+            Stmt::Return(value) => self.check_return(value.as_mut(), line, ctx),
+            Stmt::If {
+                condition,
+                then_branch,
+                else_branch,
+            } => {
+                self.check_condition(condition, ctx)?;
+                let (then_narrowed, else_narrowed) = narrowing(condition, ctx, line)?;
+                self.check_narrowed_block(then_branch, then_narrowed, ctx)?;
+                // `if x is T { return }` narrows `x` for the rest of the block
+                let exits = matches!(
+                    then_branch.last().map(|stmt| &stmt.value),
+                    Some(Stmt::Return(_) | Stmt::Break(_) | Stmt::Continue(_))
+                );
+                if exits
+                    && else_branch.is_empty()
+                    && let Some((name, local)) = else_narrowed
+                {
+                    let scope = ctx.scopes.last_mut().expect("block scope");
+                    scope.insert(name, local);
+                    return Ok(());
+                }
+                self.check_narrowed_block(else_branch, else_narrowed, ctx)
+            }
+            Stmt::While {
+                label,
+                condition,
+                body,
+            } => {
+                self.check_condition(condition, ctx)?;
+                self.check_loop_body(label.as_deref(), None, body, line, ctx)
+            }
+            Stmt::ForRange {
+                label,
+                var,
+                range,
+                body,
+            } => {
+                let start_ty = self.check_expr(&mut range.start, ctx)?;
+                let end_ty = self.check_expr(&mut range.end, ctx)?;
+                // a literal bound takes the type of the other one (`0..n` for an `i64` n)
+                let ty = if const_int(&range.start).is_some() {
+                    end_ty
+                } else {
+                    start_ty
+                };
+                if !ty.is_integer() {
+                    return Err(anyhow!(
+                        "行 {}: for の範囲には整数が必要ですが {} が渡されました",
+                        line,
+                        ty
+                    ));
+                }
+                self.expect_checked(&mut range.start, &ty)?;
+                self.expect_checked(&mut range.end, &ty)?;
+                if let Some(step) = &mut range.step {
+                    self.expect_type(step, &ty, ctx)?;
+                    if const_int(step).is_some_and(|v| v <= 0) {
+                        return Err(anyhow!(
+                            "行 {}: for の step は正の整数である必要があります",
+                            step.line
+                        ));
+                    }
+                }
+                let binding = (var.as_str(), ty);
+                self.check_loop_body(label.as_deref(), Some(binding), body, line, ctx)
+            }
+            Stmt::ForOf {
+                label,
+                var,
+                iterable,
+                body,
+            } => {
+                let ty = self.check_expr(iterable, ctx)?;
+                let elem = match &ty {
+                    TypeAnnotation::Array(elem) | TypeAnnotation::Set(elem) => (**elem).clone(),
+                    // a Map yields its entries as `(key, value)` tuples
+                    TypeAnnotation::Map(key, value) => {
+                        TypeAnnotation::Tuple(vec![(**key).clone(), (**value).clone()])
+                    }
+                    _ => {
+                        return Err(anyhow!(
+                            "行 {}: for ... of には配列か Map か Set が必要ですが {} が渡されました",
+                            line,
+                            ty
+                        ));
+                    }
+                };
+                require_determined(&ty, line)?;
+                // `for (k, v) of m` binds a hidden loop variable and
+                // destructures it at the start of the body
+                if !matches!(var, BindingPattern::Name(_)) {
+                    let hidden = format!("vts_item{}", ctx.next_id);
+                    let pattern = std::mem::replace(var, BindingPattern::Name(hidden.clone()));
+                    let value = Expr::new(ExprKind::Ident(hidden), line);
+                    let destructure = Stmt::Destructure {
+                        pattern,
+                        mutable: false,
+                        ty: None,
+                        value,
+                    };
+                    body.insert(
+                        0,
+                        Spanned {
+                            value: destructure,
+                            line,
+                        },
+                    );
+                }
+                let BindingPattern::Name(name) = var else {
+                    unreachable!("loop variable is a name");
+                };
+                let binding = (name.as_str(), elem);
+                self.check_loop_body(label.as_deref(), Some(binding), body, line, ctx)
+            }
+            Stmt::Match { scrutinee, arms } => {
+                let scrutinee_ty = self.check_expr(scrutinee, ctx)?;
+                let patterns = arms
+                    .iter_mut()
+                    .map(|arm| (&mut arm.pattern, arm.line))
+                    .collect();
+                let bindings = self.check_patterns(&scrutinee_ty, patterns, line)?;
+                for (arm, bindings) in arms.iter_mut().zip(bindings) {
+                    ctx.scopes.push(HashMap::new());
+                    let result = declare_bindings(ctx, bindings, arm.line)
+                        .and_then(|_| self.check_block(&mut arm.body, ctx));
+                    ctx.scopes.pop();
+                    result?;
+                }
+                Ok(())
+            }
+            Stmt::Break(label) => check_jump("break", label.as_deref(), line, ctx),
+            Stmt::Continue(label) => check_jump("continue", label.as_deref(), line, ctx),
+        }
+    }
+
+    /// Checks a loop statement. An assignment in the loop may end the
+    /// narrowing of a variable from outside it (`x = none` after `if x !=
+    /// none`), and the code before that assignment runs again on the next
+    /// iteration. When the loop fails to check or ends a narrowing, it is
+    /// checked again from an unchecked copy with the variables it assigns no
+    /// longer narrowed.
+    fn check_loop_stmt(&self, stmt: &mut Stmt, line: usize, ctx: &mut FnContext) -> Result<()> {
+        let unchecked = stmt.clone();
+        let (scopes, loops) = (ctx.scopes.len(), ctx.loops.len());
+        let narrowed = ctx.narrowed_count();
+        let result = self.check_stmt(stmt, line, ctx);
+        if result.is_ok() && ctx.narrowed_count() == narrowed {
+            return Ok(());
+        }
+        ctx.scopes.truncate(scopes);
+        ctx.loops.truncate(loops);
+        let mut assigned = Vec::new();
+        assigned_names(&unchecked, &mut assigned);
+        for name in &assigned {
+            ctx.drop_narrowing_of(name);
+        }
+        if result.is_err() && ctx.narrowed_count() == narrowed {
+            return result;
+        }
+        *stmt = unchecked;
+        self.check_stmt(stmt, line, ctx)
+    }
+
+    /// Checks a loop body with `label` in scope for jumps and the loop variable
+    /// (if any) bound read-only around the body.
+    fn check_loop_body(
+        &self,
+        label: Option<&str>,
+        var: Option<(&str, TypeAnnotation)>,
+        body: &mut Block,
+        line: usize,
+        ctx: &mut FnContext,
+    ) -> Result<()> {
+        if let Some(label) = label
+            && ctx.loops.iter().any(|l| l.as_deref() == Some(label))
+        {
+            return Err(anyhow!(
+                "行 {}: ループラベル '{}' が入れ子で重複しています",
+                line,
+                label
+            ));
+        }
+        ctx.loops.push(label.map(str::to_string));
+        ctx.scopes.push(HashMap::new());
+        if let Some((name, ty)) = var {
+            ctx.declare(name, ty, Binding::LoopVar, line)?;
+        }
+        let result = self.check_block(body, ctx);
+        ctx.scopes.pop();
+        ctx.loops.pop();
+        result
+    }
+
+    fn check_narrowed_expr(
+        &self,
+        expr: &mut Expr,
+        narrowed: Narrowed,
+        ctx: &mut FnContext,
+    ) -> Result<TypeAnnotation> {
+        let Some((name, local)) = narrowed else {
+            return self.check_expr(expr, ctx);
+        };
+        ctx.scopes.push(HashMap::from([(name, local)]));
+        let result = self.check_expr(expr, ctx);
+        ctx.scopes.pop();
+        result
+    }
+
+    /// Checks `block` with `narrowed` (a variable and its narrowed local) in scope.
+    fn check_narrowed_block(
+        &self,
+        block: &mut Block,
+        narrowed: Option<(String, Local)>,
+        ctx: &mut FnContext,
+    ) -> Result<()> {
+        let Some((name, local)) = narrowed else {
+            return self.check_block(block, ctx);
+        };
+        ctx.scopes.push(HashMap::from([(name, local)]));
+        let result = self.check_block(block, ctx);
+        ctx.scopes.pop();
+        result
+    }
+
+    fn check_condition(&self, condition: &mut Expr, ctx: &mut FnContext) -> Result<()> {
+        let ty = self.check_expr(condition, ctx)?;
+        if ty != TypeAnnotation::Bool {
+            return Err(anyhow!(
+                "行 {}: 条件式は bool である必要がありますが {} が渡されました",
+                condition.line,
+                ty
+            ));
+        }
+        Ok(())
+    }
+
+    /// Checks an assignment and rewrites `x op= v` into `x = x op v` for codegen.
+    fn check_assign(
+        &self,
+        target: &mut Expr,
+        op: &mut Option<BinaryOp>,
+        value: &mut Expr,
+        line: usize,
+        ctx: &mut FnContext,
+    ) -> Result<()> {
+        // the target is a variable or a field/element path rooted at one
+        // (`p.pos.x`, `xs[i].x`)
+        let mut root = &*target;
+        while let ExprKind::Field { expr, .. } | ExprKind::Index { expr, .. } = &root.kind {
+            root = expr;
+        }
+        let ExprKind::Ident(name) = &root.kind else {
+            return Err(anyhow!(
+                "行 {}: 代入先は変数かそのフィールド・要素である必要があります",
+                line
+            ));
+        };
+        let name = name.clone();
+        let local = ctx
+            .lookup(&name)
+            .ok_or_else(|| anyhow!("行 {}: 未定義の変数 '{}' です", line, name))?;
+        let binding = local.binding;
+        let declared = local.narrowed_from.clone().unwrap_or(local.ty.clone());
+        // a closure may run between a test of the variable (`x != none`) and
+        // the code that test guards, so it must not change what the test saw
+        if matches!(target.kind, ExprKind::Ident(_))
+            && ctx.is_captured(&local)
+            && (matches!(declared, TypeAnnotation::Nullable(_))
+                || tagged_members(&declared).is_some())
+        {
+            return Err(anyhow!(
+                "行 {}: 外側の変数 '{}' は {} 型なので、クロージャの中からは代入できません (none チェックや is による絞り込みが成り立たなくなるため)",
+                line,
+                name,
+                declared
+            ));
+        }
+        let target_ty = self.check_expr(target, ctx)?;
+        // array elements are shared: storing into one does not rebind the variable
+        let mut through_array = false;
+        let mut part = &*target;
+        while let ExprKind::Field { expr, .. } | ExprKind::Index { expr, .. } = &part.kind {
+            match (&part.kind, expr.ty()) {
+                (ExprKind::Index { .. }, TypeAnnotation::Array(_)) => through_array = true,
+                (ExprKind::Index { .. }, _) => {
+                    return Err(anyhow!("行 {}: 文字列の要素は読み取り専用です", line));
+                }
+                (ExprKind::Field { name, .. }, ty) if ty.is_string() => {
+                    return Err(anyhow!("行 {}: 文字列の {} は読み取り専用です", line, name));
+                }
+                (ExprKind::Field { name, .. }, TypeAnnotation::Array(_)) => {
+                    return Err(anyhow!("行 {}: 配列の {} は読み取り専用です", line, name));
+                }
+                (
+                    ExprKind::Field { name, .. },
+                    ty @ (TypeAnnotation::Map(..) | TypeAnnotation::Set(_)),
+                ) => {
+                    return Err(anyhow!(
+                        "行 {}: {} の {} は読み取り専用です",
+                        line,
+                        ty,
+                        name
+                    ));
+                }
+                _ => {}
+            }
+            part = expr;
+        }
+        match binding {
+            _ if through_array => {}
+            Binding::Let | Binding::Param => {}
+            Binding::Const => {
+                return Err(anyhow!(
+                    "行 {}: const '{}' には再代入できません",
+                    line,
+                    name
+                ));
+            }
+            Binding::LoopVar => {
+                return Err(anyhow!(
+                    "行 {}: ループ変数 '{}' には再代入できません",
+                    line,
+                    name
+                ));
+            }
+            Binding::SelfRef => {
+                return Err(anyhow!(
+                    "行 {}: self は読み取り専用です。フィールドを変更するメソッドは mut self で宣言してください",
+                    line
+                ));
+            }
+            Binding::SelfMut if matches!(target.kind, ExprKind::Ident(_)) => {
+                return Err(anyhow!(
+                    "行 {}: self そのものには代入できません。フィールドに代入してください",
+                    line
+                ));
+            }
+            // a closure holds a copy of `self`
+            Binding::SelfMut if ctx.is_captured(&local) => {
+                return Err(anyhow!(
+                    "行 {}: クロージャの中からは self のフィールドに代入できません",
+                    line
+                ));
+            }
+            Binding::SelfMut => {}
+        }
+
+        if let Some(op) = op.take() {
+            let placeholder = Expr::new(ExprKind::Bool(false), value.line);
+            let rhs = std::mem::replace(value, placeholder);
+            *value = Expr::new(
+                ExprKind::Binary {
+                    op,
+                    lhs: Box::new(target.clone()),
+                    rhs: Box::new(rhs),
+                },
+                line,
+            );
+        }
+        // a narrowed variable (`Coerce(x)`) is assigned as a whole and the
+        // value is stored into the variable's declared type. A value of the
+        // narrowed type keeps the narrowing; any other value of the declared
+        // type (`x = none`) ends it for the rest of the enclosing blocks
+        if let ExprKind::Coerce(variable) = &target.kind
+            && matches!(variable.kind, ExprKind::Ident(_))
+        {
+            let variable = (**variable).clone();
+            let base = variable.ty().clone();
+            *target = variable;
+            self.check_expected(value, &base, ctx)?;
+            let mut narrowed = value.clone();
+            if self.coerce(&mut narrowed, &target_ty) {
+                *value = narrowed;
+                self.coerce(value, &base);
+            } else {
+                self.expect_checked(value, &base)?;
+                ctx.drop_narrowing(local.id);
+            }
+            return Ok(());
+        }
+        self.expect_type(value, &target_ty, ctx)
+    }
+
+    fn check_return(
+        &self,
+        value: Option<&mut Expr>,
+        line: usize,
+        ctx: &mut FnContext,
+    ) -> Result<()> {
+        let ret = ctx.ret;
+        let is_void = matches!(ret, TypeAnnotation::Void);
+        match value {
+            Some(expr) => {
+                let actual = self.check_expected(expr, ret, ctx)?;
+                if is_void || !self.coerce(expr, ret) {
+                    return Err(anyhow!(
+                        "行 {}: 関数 {} は {} を返すべきなのに、{} を返しています",
+                        line,
+                        ctx.name,
+                        ctx.ret,
+                        actual
+                    ));
+                }
+            }
+            None => {
+                if !is_void {
+                    return Err(anyhow!(
+                        "行 {}: 関数 {} は {} を返す必要がありますが、値のない return があります",
+                        line,
+                        ctx.name,
+                        ctx.ret
+                    ));
+                }
+            }
+        }
+        Ok(())
+    }
+
+    fn expect_type(
+        &self,
+        expr: &mut Expr,
+        expected: &TypeAnnotation,
+        ctx: &mut FnContext,
+    ) -> Result<()> {
+        self.check_expected(expr, expected, ctx)?;
+        self.expect_checked(expr, expected)
+    }
+
+    /// `check_expr` for a value that must have type `expected`: a closure
+    /// takes its untyped parameters and return type from it.
+    fn check_expected(
+        &self,
+        expr: &mut Expr,
+        expected: &TypeAnnotation,
+        ctx: &mut FnContext,
+    ) -> Result<TypeAnnotation> {
+        if let ExprKind::Closure(closure) = &mut expr.kind {
+            let ty = self.check_closure(closure, Some(expected), expr.line, ctx)?;
+            expr.ty = Some(ty.clone());
+            return Ok(ty);
+        }
+        // an array literal checks its elements against the expected element
+        // type, so that literals, objects and closures take it
+        // (`let xs: float[] = [1.5, 2]`)
+        let elem = match expected {
+            TypeAnnotation::Nullable(inner) => match &**inner {
+                TypeAnnotation::Array(elem) => Some(elem),
+                _ => None,
+            },
+            TypeAnnotation::Array(elem) => Some(elem),
+            _ => None,
+        };
+        if let ExprKind::ArrayLit(elems) = &mut expr.kind
+            && let Some(elem) = elem
+            && !is_undetermined(elem)
+            && !has_type_param(elem)
+        {
+            for value in elems.iter_mut() {
+                self.check_expected(value, elem, ctx)?;
+                self.expect_checked(value, elem)?;
+            }
+            let ty = TypeAnnotation::Array(elem.clone());
+            expr.ty = Some(ty.clone());
+            return Ok(ty);
+        }
+        self.check_expr(expr, ctx)
+    }
+
+    /// Checks a closure and returns its function type. Untyped parameters and
+    /// a missing return type come from `expected`, the function type the
+    /// closure is passed or assigned as.
+    fn check_closure(
+        &self,
+        closure: &mut Closure,
+        expected: Option<&TypeAnnotation>,
+        line: usize,
+        ctx: &mut FnContext,
+    ) -> Result<TypeAnnotation> {
+        let expected = match expected {
+            Some(TypeAnnotation::Nullable(inner)) => Some(&**inner),
+            other => other,
+        };
+        let hint = match expected {
+            Some(TypeAnnotation::Function(params, ret)) => Some((params, ret)),
+            _ => None,
+        };
+        let count = closure.params.len();
+        for (index, param) in closure.params.iter_mut().enumerate() {
+            if param.ty != TypeAnnotation::None {
+                self.resolve_local(&mut param.ty, ctx, param.line)?;
+            } else {
+                match hint {
+                    Some((params, _)) if params.len() != count => {
+                        return Err(anyhow!(
+                            "行 {}: {} 個の引数を取る関数が必要ですが、無名関数の引数は {} 個です",
+                            line,
+                            params.len(),
+                            count
+                        ));
+                    }
+                    Some((params, _)) if !is_undetermined(&params[index]) => {
+                        param.ty = params[index].clone();
+                    }
+                    _ => {
+                        return Err(anyhow!(
+                            "行 {}: 引数 '{}' の型を推論できません。型注釈を書いてください (例: ({}: int) => ..)",
+                            line,
+                            param.written_name(),
+                            param.written_name()
+                        ));
+                    }
+                }
+            }
+            if param.ty == TypeAnnotation::Void {
+                return Err(anyhow!(
+                    "行 {}: 引数 '{}' に void 型は使えません",
+                    param.line,
+                    param.written_name()
+                ));
+            }
+        }
+        let ret = match &mut closure.return_type {
+            Some(ty) => {
+                self.resolve_local(ty, ctx, line)?;
+                Some(ty.clone())
+            }
+            None => hint
+                .map(|(_, ret)| (**ret).clone())
+                .filter(|ret| !is_undetermined(ret)),
+        };
+        let ret = match (ret, &closure.body) {
+            (Some(ret), _) => Some(ret),
+            (None, ClosureBody::Block(body)) if block_returns_value(body) => {
+                return Err(anyhow!(
+                    "行 {}: 値を返す無名関数には戻り値型を書いてください (例: (x: int): int => {{ .. }})",
+                    line
+                ));
+            }
+            (None, ClosureBody::Block(_)) => Some(TypeAnnotation::Void),
+            (None, ClosureBody::Expr(_)) => None,
+        };
+
+        // the closure sees the enclosing scopes; variables declared before
+        // this point are captured
+        let declared_ret = ret.clone().unwrap_or(TypeAnnotation::None);
+        let mut inner = FnContext {
+            name: "(無名関数)",
+            ret: &declared_ret,
+            type_args: ctx.type_args.clone(),
+            scopes: std::mem::take(&mut ctx.scopes),
+            loops: Vec::new(),
+            closures: std::mem::take(&mut ctx.closures),
+            next_id: ctx.next_id,
+            boxed: std::mem::take(&mut ctx.boxed),
+        };
+        inner.closures.push(ClosureFrame {
+            base: inner.scopes.len(),
+            first_id: inner.next_id,
+            captures: RefCell::default(),
+        });
+        inner.scopes.push(HashMap::new());
+        let result = self.check_closure_body(closure, ret, line, &mut inner);
+        let params = inner.scopes.pop().expect("closure scope");
+        for param in &mut closure.params {
+            param.captured = inner.is_boxed(&params, &param.name);
+        }
+        let frame = inner.closures.pop().expect("closure frame");
+        closure.captures = frame.captures.into_inner();
+        ctx.scopes = inner.scopes;
+        ctx.closures = inner.closures;
+        ctx.next_id = inner.next_id;
+        ctx.boxed = inner.boxed;
+
+        let ret = result?;
+        closure.return_type = Some(ret.clone());
+        let params = closure.params.iter().map(|p| p.ty.clone()).collect();
+        Ok(TypeAnnotation::Function(params, Box::new(ret)))
+    }
+
+    /// Declares the parameters of `closure` and checks its body; returns the
+    /// return type (`ret`, or the type of the expression body without one).
+    fn check_closure_body(
+        &self,
+        closure: &mut Closure,
+        ret: Option<TypeAnnotation>,
+        line: usize,
+        ctx: &mut FnContext,
+    ) -> Result<TypeAnnotation> {
+        for param in &closure.params {
+            ctx.declare(&param.name, param.ty.clone(), Binding::Param, param.line)?;
+        }
+        let mut prelude = destructure_params(&closure.params);
+        // with a known return type, an expression body after destructured
+        // parameters becomes a block
+        if !prelude.is_empty()
+            && let (ClosureBody::Expr(body), Some(ret)) = (&mut closure.body, &ret)
+        {
+            let body = std::mem::replace(body, Expr::new(ExprKind::Bool(false), line));
+            let stmt = match ret {
+                TypeAnnotation::Void => Stmt::Expr(body),
+                _ => Stmt::Return(Some(body)),
+            };
+            closure.body = ClosureBody::Block(vec![Spanned { value: stmt, line }]);
+        }
+        if let ClosureBody::Block(body) = &mut closure.body {
+            body.splice(0..0, prelude.drain(..));
+        }
+        match (&mut closure.body, ret) {
+            (ClosureBody::Expr(body), None) => {
+                let ty =
+                    self.check_block_then(&mut prelude, ctx, |ctx| self.check_expr(body, ctx))?;
+                if is_undetermined(&ty) {
+                    return Err(anyhow!(
+                        "行 {}: 無名関数の戻り値の型を決められません。戻り値型を書いてください (例: (): int[] => [])",
+                        line
+                    ));
+                }
+                if !prelude.is_empty() {
+                    let body = std::mem::replace(body, Expr::new(ExprKind::Bool(false), line));
+                    prelude.push(Spanned {
+                        value: Stmt::Return(Some(body)),
+                        line,
+                    });
+                    closure.body = ClosureBody::Block(prelude);
+                }
+                Ok(ty)
+            }
+            // the value of the body is discarded
+            (ClosureBody::Expr(body), Some(TypeAnnotation::Void)) => {
+                self.check_expr(body, ctx)?;
+                Ok(TypeAnnotation::Void)
+            }
+            (ClosureBody::Expr(body), Some(ret)) => {
+                self.check_return(Some(body), line, ctx)?;
+                Ok(ret)
+            }
+            (ClosureBody::Block(body), ret) => {
+                let ret = ret.unwrap_or(TypeAnnotation::Void);
+                self.check_block(body, ctx)?;
+                if !matches!(ret, TypeAnnotation::Void) && !block_contains_return(body) {
+                    return Err(anyhow!(
+                        "行 {}: 無名関数は {} を返す必要がありますが、return が見つかりません",
+                        line,
+                        ret
+                    ));
+                }
+                Ok(ret)
+            }
+        }
+    }
+
+    /// Checks a call of the function or method `callee` and returns its result
+    /// type; for a generic callee, fills in the inferred `type_args`.
+    fn check_call(
+        &self,
+        callee: &str,
+        sig: &FnSig,
+        args: &mut [Expr],
+        type_args: &mut Vec<TypeAnnotation>,
+        line: usize,
+        ctx: &mut FnContext,
+    ) -> Result<TypeAnnotation> {
+        // closures are checked once the types they take are known
+        for arg in args.iter_mut() {
+            if !matches!(arg.kind, ExprKind::Closure(_)) {
+                self.check_expr(arg, ctx)?;
+            }
+        }
+        if args.len() != sig.params.len() {
+            return Err(anyhow!(
+                "行 {}: 関数 {} は {} 個の引数を取りますが {} 個渡されました",
+                line,
+                callee,
+                sig.params.len(),
+                args.len()
+            ));
+        }
+        self.check_closure_args(sig, args, ctx)?;
+        let (params, ret) = if !sig.is_generic() {
+            (sig.params.clone(), sig.ret.clone())
+        } else {
+            *type_args = self.infer_call(callee, sig, args, line)?;
+            let bindings: HashMap<String, TypeAnnotation> = sig
+                .type_params
+                .iter()
+                .cloned()
+                .zip(type_args.iter().cloned())
+                .collect();
+            // an interface-typed parameter takes the argument's own type
+            let params = sig
+                .params
+                .iter()
+                .zip(args.iter())
+                .map(|(param, arg)| match param {
+                    TypeAnnotation::Interface(_) => Ok(arg.ty().clone()),
+                    _ => self.instantiate(param, &bindings, line),
+                })
+                .collect::<Result<Vec<_>>>()?;
+            (params, self.instantiate(&sig.ret, &bindings, line)?)
+        };
+        for (index, (arg, param)) in args.iter_mut().zip(&params).enumerate() {
+            let actual = arg.ty().clone();
+            if !self.coerce(arg, param) {
+                return Err(anyhow!(
+                    "行 {}: 関数 {} の第 {} 引数の型が一致しません: {} が必要ですが {} が渡されました",
+                    line,
+                    callee,
+                    index + 1,
+                    param,
+                    actual
+                ));
+            }
+        }
+        Ok(ret)
+    }
+
+    /// Checks the closures among the arguments of a call to `sig`, each
+    /// against its parameter type with the type arguments the other
+    /// arguments bind. The other arguments are already checked.
+    fn check_closure_args(
+        &self,
+        sig: &FnSig,
+        args: &mut [Expr],
+        ctx: &mut FnContext,
+    ) -> Result<()> {
+        let mut bindings = HashMap::new();
+        for (arg, param) in args.iter().zip(&sig.params) {
+            if arg.ty.is_some() {
+                infer_type_args(param, arg.ty(), &mut bindings);
+            }
+        }
+        for (arg, param) in args.iter_mut().zip(&sig.params) {
+            if arg.ty.is_some() {
+                continue;
+            }
+            // type parameters bound by nothing yet are left open
+            let mut known = bindings.clone();
+            for name in &sig.type_params {
+                known.entry(name.clone()).or_insert(TypeAnnotation::None);
+            }
+            let mut hint = param.clone();
+            substitute_names(&mut hint, &known);
+            self.check_expected(arg, &hint, ctx)?;
+            infer_type_args(param, arg.ty(), &mut bindings);
+        }
+        Ok(())
+    }
+
+    /// `expect_type` for an expression that has already been checked.
+    fn expect_checked(&self, expr: &mut Expr, expected: &TypeAnnotation) -> Result<()> {
+        let actual = expr.ty().clone();
+        if !self.coerce(expr, expected) {
+            if let Some(target) = literal_target(expected) {
+                retype_literal(expr, &target)?;
+            }
+            return Err(anyhow!(
+                "行 {}: 型が一致しません: {} が必要ですが {} が渡されました",
+                expr.line,
+                expected,
+                actual
+            ));
+        }
+        Ok(())
+    }
+
+    fn check_expr(&self, expr: &mut Expr, ctx: &mut FnContext) -> Result<TypeAnnotation> {
+        self.rewrite_function_value(expr, ctx);
+        self.rewrite_variant(expr, ctx);
+        self.rewrite_static_method(expr, ctx)?;
+        self.rewrite_std_call(expr, ctx)?;
+        self.rewrite_result_constructor(expr)?;
+        self.rewrite_conversion(expr)?;
+        let line = expr.line;
+        // a narrowed variable reads as its narrowed type
+        if let ExprKind::Ident(name) = &expr.kind
+            && let Some(Local {
+                ty,
+                narrowed_from: Some(base),
+                ..
+            }) = ctx.lookup(name)
+        {
+            let mut variable = Expr::new(ExprKind::Ident(name.clone()), line);
+            variable.ty = Some(base.clone());
+            let ty = ty.clone();
+            expr.kind = ExprKind::Coerce(Box::new(variable));
+            expr.ty = Some(ty.clone());
+            return Ok(ty);
+        }
+        let ty = match &mut expr.kind {
+            ExprKind::Int(v) => literal_type(i128::from(*v), line)?,
+            ExprKind::Float(_) => TypeAnnotation::Float,
+            ExprKind::Convert { expr: inner, to } => {
+                let inner_ty = self.check_expr(inner, ctx)?;
+                if !inner_ty.is_numeric() {
+                    return Err(anyhow!(
+                        "行 {}: {}(..) には数値が必要ですが {} が渡されました",
+                        line,
+                        to,
+                        inner_ty
+                    ));
+                }
+                to.clone()
+            }
+            ExprKind::Str(_) => TypeAnnotation::String,
+            ExprKind::Template(parts) => {
+                for part in parts.iter_mut() {
+                    let TemplatePart::Expr(value) = part else {
+                        continue;
+                    };
+                    let ty = self.check_expr(value, ctx)?;
+                    if !is_printable(&ty) {
+                        return Err(anyhow!(
+                            "行 {}: テンプレート文字列に {} 型の値は埋め込めません",
+                            value.line,
+                            ty
+                        ));
+                    }
+                }
+                TypeAnnotation::String
+            }
+            ExprKind::Bool(_) => TypeAnnotation::Bool,
+            ExprKind::None => TypeAnnotation::None,
+            ExprKind::Ident(name) => match ctx.lookup(name) {
+                Some(local) => local.ty,
+                None => return Err(anyhow!("行 {}: 未定義の変数 '{}' です", line, name)),
+            },
+            ExprKind::FnRef(name) => {
+                let sig = &self.functions[name.as_str()];
+                if sig.is_generic() {
+                    return Err(anyhow!(
+                        "行 {}: ジェネリック関数 {} は値として使えません。無名関数で包んでください (例: (x: int) => {}(x))",
+                        line,
+                        name,
+                        name
+                    ));
+                }
+                TypeAnnotation::Function(sig.params.clone(), Box::new(sig.ret.clone()))
+            }
+            ExprKind::Closure(closure) => self.check_closure(closure, None, line, ctx)?,
+            ExprKind::CallValue { callee, args } => {
+                // a field call rewritten from a method call arrives checked
+                let callee_ty = match &callee.ty {
+                    Some(ty) => ty.clone(),
+                    None => self.check_expr(callee, ctx)?,
+                };
+                let TypeAnnotation::Function(params, ret) = &callee_ty else {
+                    return Err(anyhow!("行 {}: {} 型の値は呼び出せません", line, callee_ty));
+                };
+                if args.len() != params.len() {
+                    return Err(anyhow!(
+                        "行 {}: この関数は {} 個の引数を取りますが {} 個渡されました",
+                        line,
+                        params.len(),
+                        args.len()
+                    ));
+                }
+                for (index, (arg, param)) in args.iter_mut().zip(params).enumerate() {
+                    let actual = self.check_expected(arg, param, ctx)?;
+                    if !self.coerce(arg, param) {
+                        return Err(anyhow!(
+                            "行 {}: 第 {} 引数の型が一致しません: {} が必要ですが {} が渡されました",
+                            line,
+                            index + 1,
+                            param,
+                            actual
+                        ));
+                    }
+                }
+                (**ret).clone()
+            }
+            ExprKind::Call {
+                callee,
+                args,
+                type_args,
+            } => match self.functions.get(callee.as_str()) {
+                Some(sig) if sig.receiver.is_some() => {
+                    let (_, method) = callee.split_once('.').expect("method name");
+                    return Err(anyhow!(
+                        "行 {}: {} は self を取るメソッドなので、値に対して呼び出してください (例: value.{}(..))",
+                        line,
+                        callee,
+                        method
+                    ));
+                }
+                Some(sig) => self.check_call(callee, sig, args, type_args, line, ctx)?,
+                None => {
+                    return Err(anyhow!("行 {}: 未定義の関数 '{}' です", line, callee));
+                }
+            },
+            ExprKind::ResultOk(None) => result_type(TypeAnnotation::Void, TypeAnnotation::None),
+            ExprKind::ResultOk(Some(value)) => {
+                let value_ty = self.check_expr(value, ctx)?;
+                if value_ty == TypeAnnotation::Void {
+                    return Err(anyhow!("行 {}: ok に void の値は渡せません", line));
+                }
+                result_type(value_ty, TypeAnnotation::None)
+            }
+            ExprKind::ResultErr(error) => {
+                let error_ty = self.check_expr(error, ctx)?;
+                if error_ty == TypeAnnotation::Void {
+                    return Err(anyhow!("行 {}: err に void の値は渡せません", line));
+                }
+                result_type(TypeAnnotation::None, error_ty)
+            }
+            ExprKind::Try(inner) => {
+                let inner_ty = self.check_expr(inner, ctx)?;
+                let TypeAnnotation::Result(value_ty, error_ty) = &inner_ty else {
+                    return Err(anyhow!(
+                        "行 {}: ? は Result の値にしか使えませんが {} が渡されました",
+                        line,
+                        inner_ty
+                    ));
+                };
+                require_determined(&inner_ty, line)?;
+                match ctx.ret {
+                    TypeAnnotation::Result(_, ret_error) => {
+                        if !self.compatible(ret_error, error_ty) {
+                            return Err(anyhow!(
+                                "行 {}: ? で返すエラー {} は関数 {} の戻り値 {} のエラー型と一致しません",
+                                line,
+                                error_ty,
+                                ctx.name,
+                                ctx.ret
+                            ));
+                        }
+                    }
+                    // `main` reports the error and exits with status 1
+                    _ if ctx.name == "main" && error_ty.is_string() => {}
+                    _ => {
+                        return Err(anyhow!(
+                            "行 {}: ? は戻り値が Result の関数でしか使えません (関数 {} の戻り値は {})",
+                            line,
+                            ctx.name,
+                            ctx.ret
+                        ));
+                    }
+                }
+                (**value_ty).clone()
+            }
+            ExprKind::Await(inner) => self.check_expr(inner, ctx)?,
+            ExprKind::TimeNow => TypeAnnotation::I64,
+            ExprKind::FsReadFile { path } => {
+                self.expect_type(path, &TypeAnnotation::String, ctx)?;
+                result_type(TypeAnnotation::String, TypeAnnotation::String)
+            }
+            ExprKind::FsWriteFile { path, contents } => {
+                self.expect_type(path, &TypeAnnotation::String, ctx)?;
+                self.expect_type(contents, &TypeAnnotation::String, ctx)?;
+                result_type(TypeAnnotation::Void, TypeAnnotation::String)
+            }
+            ExprKind::Unary {
+                op: UnaryOp::Neg,
+                expr: inner,
+            } if let ExprKind::Int(v) = inner.kind => {
+                // `-9223372036854775808` is an `i64` although its digits are not
+                let ty = literal_type(-i128::from(v), line)?;
+                inner.ty = Some(ty.clone());
+                ty
+            }
+            ExprKind::Unary { op, expr: inner } => {
+                let inner_ty = self.check_expr(inner, ctx)?;
+                let expected = match op {
+                    // unsigned types have no negative values
+                    UnaryOp::Neg
+                        if matches!(
+                            inner_ty,
+                            TypeAnnotation::Int | TypeAnnotation::I64 | TypeAnnotation::Float
+                        ) =>
+                    {
+                        inner_ty.clone()
+                    }
+                    UnaryOp::Neg => TypeAnnotation::Int,
+                    UnaryOp::Not => TypeAnnotation::Bool,
+                };
+                if expected != inner_ty {
+                    return Err(anyhow!(
+                        "行 {}: 単項演算子 {} は {} 型に使えません",
+                        line,
+                        op.as_str(),
+                        inner_ty
+                    ));
+                }
+                expected
+            }
+            ExprKind::Binary {
+                op: BinaryOp::Coalesce,
+                lhs,
+                rhs,
+            } => {
+                let lhs_ty = self.check_expr(lhs, ctx)?;
+                let TypeAnnotation::Nullable(inner) = &lhs_ty else {
+                    return Err(anyhow!(
+                        "行 {}: ?? の左辺は nullable である必要がありますが {} が渡されました",
+                        line,
+                        lhs_ty
+                    ));
+                };
+                // `a ?? b` is non-null when `b` is, nullable otherwise
+                let rhs_ty = self.check_expr(rhs, ctx)?;
+                if matches!(rhs_ty, TypeAnnotation::Nullable(_) | TypeAnnotation::None) {
+                    self.expect_type(rhs, &lhs_ty, ctx)?;
+                    lhs_ty.clone()
+                } else {
+                    let inner = (**inner).clone();
+                    self.expect_type(rhs, &inner, ctx)?;
+                    inner
+                }
+            }
+            ExprKind::Binary { op, lhs, rhs } => {
+                let op = *op;
+                let lhs_ty = self.check_expr(lhs, ctx)?;
+                // `x != none && x > 0`: the right operand sees the narrowed `x`
+                let (when_true, when_false) = narrowing(lhs, ctx, line)?;
+                let narrowed = match op {
+                    BinaryOp::And => when_true,
+                    BinaryOp::Or => when_false,
+                    _ => None,
+                };
+                let mut rhs_ty = self.check_narrowed_expr(rhs, narrowed, ctx)?;
+                // an integer literal takes the numeric type of the other operand (`big + 1`)
+                let mut lhs_ty = lhs_ty;
+                if lhs_ty != rhs_ty {
+                    if rhs_ty.is_numeric() && retype_literal(lhs, &rhs_ty)? {
+                        lhs_ty = rhs_ty.clone();
+                    } else if lhs_ty.is_numeric() && retype_literal(rhs, &lhs_ty)? {
+                        rhs_ty = lhs_ty.clone();
+                    }
+                }
+                binary_result(op, &lhs_ty, &rhs_ty).ok_or_else(|| {
+                    anyhow!(
+                        "行 {}: 演算子 {} は {} と {} の組み合わせに使えません",
+                        line,
+                        op.as_str(),
+                        lhs_ty,
+                        rhs_ty
+                    )
+                })?
+            }
+            ExprKind::Field { expr: inner, name } => {
+                let inner_ty = self.check_expr(inner, ctx)?;
+                if let TypeAnnotation::Nullable(_) = inner_ty {
+                    return Err(anyhow!(
+                        "行 {}: {} 型の値は none の可能性があります。'?.{}' を使うか、none でないことを確認してください",
+                        line,
+                        inner_ty,
+                        name
+                    ));
+                }
+                self.field_type(&inner_ty, name).ok_or_else(|| {
+                    anyhow!(
+                        "行 {}: {} 型にはフィールド '{}' がありません",
+                        line,
+                        inner_ty,
+                        name
+                    )
+                })?
+            }
+            ExprKind::OptionalField { expr: inner, name } => {
+                let inner_ty = self.check_expr(inner, ctx)?;
+                let value_ty = optional_value(&inner_ty, name, line)?;
+                let field_ty = self.field_type(value_ty, name).ok_or_else(|| {
+                    anyhow!(
+                        "行 {}: {} 型にはフィールド '{}' がありません",
+                        line,
+                        value_ty,
+                        name
+                    )
+                })?;
+                nullable(field_ty, line)?
+            }
+            ExprKind::OptionalCall { expr: inner, call } => {
+                let inner_ty = self.check_expr(inner, ctx)?;
+                let ExprKind::MethodCall { method, .. } = &call.kind else {
+                    unreachable!("parsed as a method call");
+                };
+                let value_ty = optional_value(&inner_ty, method, line)?.clone();
+                // the call is checked on the present value
+                ctx.scopes.push(HashMap::new());
+                let declared = ctx.declare(OPTIONAL_RECEIVER, value_ty, Binding::Const, line);
+                let result = declared.and_then(|()| self.check_expr(call, ctx));
+                ctx.scopes.pop();
+                match result? {
+                    TypeAnnotation::Void => TypeAnnotation::Void,
+                    ty => nullable(ty, line)?,
+                }
+            }
+            ExprKind::StructLit { name, fields } => {
+                if let Some(TypeAnnotation::Named(target)) = self.types.aliases.get(name.as_str()) {
+                    *name = target.clone();
+                }
+                let decl = self
+                    .structs
+                    .get(name.as_str())
+                    .ok_or_else(|| anyhow!("行 {}: 未定義の構造体 '{}' です", line, name))?;
+                let mut seen = HashSet::new();
+                for (field, value) in fields.iter_mut() {
+                    let Some(declared) = decl.fields.iter().find(|f| &f.name == field) else {
+                        return Err(anyhow!(
+                            "行 {}: 構造体 {} にフィールド '{}' はありません",
+                            value.line,
+                            name,
+                            field
+                        ));
+                    };
+                    if !seen.insert(field.clone()) {
+                        return Err(anyhow!(
+                            "行 {}: フィールド '{}' が重複しています",
+                            value.line,
+                            field
+                        ));
+                    }
+                    if decl.type_params.is_empty() {
+                        self.expect_type(value, &declared.ty, ctx)?;
+                    } else {
+                        self.check_expr(value, ctx)?;
+                    }
+                }
+                if let Some(missing) = decl.fields.iter().find(|f| !seen.contains(&f.name)) {
+                    return Err(anyhow!(
+                        "行 {}: 構造体 {} のフィールド '{}' が指定されていません",
+                        line,
+                        name,
+                        missing.name
+                    ));
+                }
+                if decl.type_params.is_empty() {
+                    TypeAnnotation::Named(name.clone())
+                } else {
+                    // the type arguments come from the field values; one left
+                    // open (`Box { value: none }`) is taken from the expected type
+                    let mut bindings = HashMap::new();
+                    for (field, value) in fields.iter() {
+                        let declared = decl.fields.iter().find(|f| &f.name == field);
+                        infer_type_args(
+                            &declared.expect("checked field").ty,
+                            value.ty(),
+                            &mut bindings,
+                        );
+                    }
+                    let type_args: Vec<TypeAnnotation> = decl
+                        .type_params
+                        .iter()
+                        .map(|param| bindings.get(param).cloned().unwrap_or(TypeAnnotation::None))
+                        .collect();
+                    let ty = TypeAnnotation::Generic(name.clone(), type_args);
+                    let field_types = self.record_fields(&ty).expect("generic struct");
+                    for (field, value) in fields.iter_mut() {
+                        let (_, field_ty) = field_types
+                            .iter()
+                            .find(|(other, _)| other == field)
+                            .expect("checked field");
+                        let actual = value.ty().clone();
+                        if !self.coerce(value, field_ty) {
+                            return Err(anyhow!(
+                                "行 {}: フィールド '{}' の型が一致しません: {} が必要ですが {} が渡されました",
+                                value.line,
+                                field,
+                                field_ty,
+                                actual
+                            ));
+                        }
+                    }
+                    ty
+                }
+            }
+            ExprKind::MethodCall {
+                receiver,
+                method,
+                args,
+            } => {
+                let receiver_ty = self.check_expr(receiver, ctx)?;
+                // a function stored in a field is called as such
+                if let Some(field_ty @ TypeAnnotation::Function(..)) =
+                    self.field_type(&receiver_ty, method)
+                {
+                    let placeholder = Expr::new(ExprKind::Bool(false), line);
+                    let record = std::mem::replace(&mut **receiver, placeholder);
+                    let mut callee = Expr::new(
+                        ExprKind::Field {
+                            expr: Box::new(record),
+                            name: std::mem::take(method),
+                        },
+                        line,
+                    );
+                    callee.ty = Some(field_ty);
+                    let args = std::mem::take(args);
+                    expr.kind = ExprKind::CallValue {
+                        callee: Box::new(callee),
+                        args,
+                    };
+                    return self.check_expr(expr, ctx);
+                }
+                // methods declared for the struct
+                if let TypeAnnotation::Named(owner) = &receiver_ty
+                    && let callee = format!("{}.{}", owner, method)
+                    && let Some(sig) = self.functions.get(callee.as_str())
+                {
+                    match sig.receiver {
+                        None => {
+                            return Err(anyhow!(
+                                "行 {}: {} は self を取らないメソッドなので、{}.{}(..) の形で呼び出してください",
+                                line,
+                                callee,
+                                owner,
+                                method
+                            ));
+                        }
+                        Some(Receiver::Mut) => {
+                            self.check_mut_receiver(receiver, &callee, line, ctx)?
+                        }
+                        Some(Receiver::Shared) => {}
+                    }
+                    let ty = self.check_call(&callee, sig, args, &mut Vec::new(), line, ctx)?;
+                    expr.ty = Some(ty.clone());
+                    return Ok(ty);
+                }
+                // methods an interface requires; each instance checks the
+                // call again against the struct it was called with
+                if let TypeAnnotation::Interface(name) = &receiver_ty {
+                    let Some(required) = self.interfaces[name]
+                        .methods
+                        .iter()
+                        .find(|m| m.name == *method)
+                    else {
+                        return Err(anyhow!(
+                            "行 {}: インターフェース {} にメソッド '{}' はありません",
+                            line,
+                            name,
+                            method
+                        ));
+                    };
+                    let sig = FnSig {
+                        type_params: Vec::new(),
+                        params: required.params.iter().map(|p| p.ty.clone()).collect(),
+                        ret: required.return_type.clone().unwrap_or(TypeAnnotation::Void),
+                        receiver: Some(Receiver::Shared),
+                    };
+                    let callee = format!("{}.{}", name, method);
+                    let ty = self.check_call(&callee, &sig, args, &mut Vec::new(), line, ctx)?;
+                    expr.ty = Some(ty.clone());
+                    return Ok(ty);
+                }
+                // built-in methods: (parameter types, result type)
+                let signature: Option<(Vec<TypeAnnotation>, TypeAnnotation)> = match (
+                    method.as_str(),
+                    args.len(),
+                    &receiver_ty,
+                ) {
+                    ("slice", 1, ty) if ty.is_string() => {
+                        Some((vec![TypeAnnotation::Int], TypeAnnotation::String))
+                    }
+                    ("slice", _, ty) if ty.is_string() => Some((
+                        vec![TypeAnnotation::Int, TypeAnnotation::Int],
+                        TypeAnnotation::String,
+                    )),
+                    ("toString", _, ty) if ty.is_numeric() || *ty == TypeAnnotation::Bool => {
+                        Some((Vec::new(), TypeAnnotation::String))
+                    }
+                    // `push` returns the new length, `pop` none when empty
+                    ("push", _, TypeAnnotation::Array(elem)) => {
+                        Some((vec![(**elem).clone()], TypeAnnotation::Int))
+                    }
+                    ("pop", _, TypeAnnotation::Array(elem)) => {
+                        Some((Vec::new(), nullable((**elem).clone(), line)?))
+                    }
+                    // callbacks take an element; `map` collects what its callback returns
+                    ("map", 1, TypeAnnotation::Array(elem)) => {
+                        let wanted = TypeAnnotation::Function(
+                            vec![(**elem).clone()],
+                            Box::new(TypeAnnotation::None),
+                        );
+                        let callback = self.check_expected(&mut args[0], &wanted, ctx)?;
+                        match &callback {
+                            TypeAnnotation::Function(params, ret)
+                                if params[..] == [(**elem).clone()]
+                                    && **ret != TypeAnnotation::Void =>
+                            {
+                                Some((vec![callback.clone()], TypeAnnotation::Array(ret.clone())))
+                            }
+                            _ => {
+                                return Err(anyhow!(
+                                    "行 {}: map には ({}) => .. 型の関数が必要ですが {} が渡されました",
+                                    line,
+                                    elem,
+                                    callback
+                                ));
+                            }
+                        }
+                    }
+                    ("filter", _, TypeAnnotation::Array(elem)) => Some((
+                        vec![TypeAnnotation::Function(
+                            vec![(**elem).clone()],
+                            Box::new(TypeAnnotation::Bool),
+                        )],
+                        receiver_ty.clone(),
+                    )),
+                    ("forEach", _, TypeAnnotation::Array(elem)) => Some((
+                        vec![TypeAnnotation::Function(
+                            vec![(**elem).clone()],
+                            Box::new(TypeAnnotation::Void),
+                        )],
+                        TypeAnnotation::Void,
+                    )),
+                    // `set` / `add` return the collection itself for chaining
+                    ("get", _, TypeAnnotation::Map(key, value)) => {
+                        Some((vec![(**key).clone()], nullable((**value).clone(), line)?))
+                    }
+                    ("set", _, TypeAnnotation::Map(key, value)) => Some((
+                        vec![(**key).clone(), (**value).clone()],
+                        receiver_ty.clone(),
+                    )),
+                    ("add", _, TypeAnnotation::Set(elem)) => {
+                        Some((vec![(**elem).clone()], receiver_ty.clone()))
+                    }
+                    (
+                        "has" | "delete",
+                        _,
+                        TypeAnnotation::Map(key, _) | TypeAnnotation::Set(key),
+                    ) => Some((vec![(**key).clone()], TypeAnnotation::Bool)),
+                    // snapshots in insertion order
+                    ("keys", _, TypeAnnotation::Map(key, _))
+                    | ("values", _, TypeAnnotation::Set(key)) => {
+                        Some((Vec::new(), TypeAnnotation::Array(key.clone())))
+                    }
+                    ("values", _, TypeAnnotation::Map(_, value)) => {
+                        Some((Vec::new(), TypeAnnotation::Array(value.clone())))
+                    }
+                    _ => None,
+                };
+                let Some((params, result)) = signature else {
+                    return Err(anyhow!(
+                        "行 {}: {} 型にメソッド '{}' はありません",
+                        line,
+                        receiver_ty,
+                        method
+                    ));
+                };
+                if args.len() != params.len() {
+                    return Err(anyhow!(
+                        "行 {}: メソッド {} は {} 個の引数を取りますが {} 個渡されました",
+                        line,
+                        method,
+                        params.len(),
+                        args.len()
+                    ));
+                }
+                require_determined(&receiver_ty, line)?;
+                for (arg, param) in args.iter_mut().zip(&params) {
+                    if arg.ty.is_none() {
+                        self.check_expected(arg, param, ctx)?;
+                    }
+                    self.expect_checked(arg, param)?;
+                }
+                result
+            }
+            ExprKind::NewCollection(ty) => {
+                self.resolve_local(ty, ctx, line)?;
+                ty.clone()
+            }
+            ExprKind::ObjectLit(fields) => {
+                let mut seen = HashSet::new();
+                let mut types = Vec::new();
+                for (field, value) in fields.iter_mut() {
+                    if !seen.insert(field.clone()) {
+                        return Err(anyhow!(
+                            "行 {}: フィールド '{}' が重複しています",
+                            value.line,
+                            field
+                        ));
+                    }
+                    let ty = self.check_expr(value, ctx)?;
+                    if ty == TypeAnnotation::Void {
+                        return Err(anyhow!(
+                            "行 {}: フィールド '{}' に void の値は使えません",
+                            value.line,
+                            field
+                        ));
+                    }
+                    types.push((field.clone(), ty));
+                }
+                object_type(types)
+            }
+            ExprKind::ArrayLit(elems) => {
+                // the element type is the union of the element types
+                let mut members = Vec::new();
+                let mut has_none = false;
+                for value in elems.iter_mut() {
+                    match self.check_expr(value, ctx)? {
+                        TypeAnnotation::Void => {
+                            return Err(anyhow!(
+                                "行 {}: 配列の要素に void の値は使えません",
+                                value.line
+                            ));
+                        }
+                        TypeAnnotation::None => has_none = true,
+                        ty => members.push(ty),
+                    }
+                }
+                // integer literals take the type of the other numeric elements
+                // (`[1.5, 2]` is a `float[]`), as they do in `f * 2`
+                let mut numeric: Vec<TypeAnnotation> = Vec::new();
+                for ty in &members {
+                    if ty.is_numeric() && *ty != TypeAnnotation::Int && !numeric.contains(ty) {
+                        numeric.push(ty.clone());
+                    }
+                }
+                if let [target] = numeric.as_slice()
+                    && elems.iter().all(|value| {
+                        *value.ty() != TypeAnnotation::Int || const_int(value).is_some()
+                    })
+                {
+                    for value in elems.iter_mut() {
+                        if *value.ty() == TypeAnnotation::Int {
+                            retype_literal(value, target)?;
+                        }
+                    }
+                    members.retain(|ty| *ty != TypeAnnotation::Int);
+                }
+                let elem = match (members.is_empty(), has_none) {
+                    (true, _) => TypeAnnotation::None,
+                    (false, false) => union_of(members, line)?,
+                    (false, true) => nullable(union_of(members, line)?, line)?,
+                };
+                if elem != TypeAnnotation::None {
+                    for value in elems.iter_mut() {
+                        let actual = value.ty().clone();
+                        if !self.coerce(value, &elem) {
+                            return Err(anyhow!(
+                                "行 {}: 配列の要素の型が一致しません: {} が必要ですが {} が渡されました",
+                                value.line,
+                                elem,
+                                actual
+                            ));
+                        }
+                    }
+                }
+                TypeAnnotation::Array(Box::new(elem))
+            }
+            ExprKind::TupleLit(elems) => {
+                let mut types = Vec::new();
+                for value in elems.iter_mut() {
+                    let ty = self.check_expr(value, ctx)?;
+                    if ty == TypeAnnotation::Void {
+                        return Err(anyhow!(
+                            "行 {}: タプルの要素に void の値は使えません",
+                            value.line
+                        ));
+                    }
+                    types.push(ty);
+                }
+                TypeAnnotation::Tuple(types)
+            }
+            ExprKind::Index { expr: inner, index } => {
+                let inner_ty = self.check_expr(inner, ctx)?;
+                let elem_ty = match &inner_ty {
+                    TypeAnnotation::Array(elem) => {
+                        require_determined(&inner_ty, line)?;
+                        (**elem).clone()
+                    }
+                    // a one-byte string, as in TypeScript (for ASCII text)
+                    ty if ty.is_string() => TypeAnnotation::String,
+                    _ => {
+                        return Err(anyhow!(
+                            "行 {}: {} 型の値はインデックスで参照できません",
+                            line,
+                            inner_ty
+                        ));
+                    }
+                };
+                self.expect_type(index, &TypeAnnotation::Int, ctx)?;
+                elem_ty
+            }
+            ExprKind::Variant {
+                enum_name,
+                variant,
+                args,
+            } => {
+                let decl = &self.enums[enum_name.as_str()];
+                let Some(declared) = decl.variants.iter().find(|v| &v.name == variant) else {
+                    return Err(anyhow!(
+                        "行 {}: enum {} にバリアント '{}' はありません",
+                        line,
+                        enum_name,
+                        variant
+                    ));
+                };
+                if args.len() != declared.fields.len() {
+                    return Err(anyhow!(
+                        "行 {}: バリアント {}.{} は {} 個の値を取りますが {} 個渡されました",
+                        line,
+                        enum_name,
+                        variant,
+                        declared.fields.len(),
+                        args.len()
+                    ));
+                }
+                for (arg, field_ty) in args.iter_mut().zip(&declared.fields) {
+                    self.expect_type(arg, field_ty, ctx)?;
+                }
+                TypeAnnotation::Named(enum_name.clone())
+            }
+            ExprKind::Match { scrutinee, arms } => {
+                let scrutinee_ty = self.check_expr(scrutinee, ctx)?;
+                let patterns = arms
+                    .iter_mut()
+                    .map(|arm| (&mut arm.pattern, arm.line))
+                    .collect();
+                let bindings = self.check_patterns(&scrutinee_ty, patterns, line)?;
+                // the first arm decides the type; the others must fit it
+                let mut result: Option<TypeAnnotation> = None;
+                for (arm, bindings) in arms.iter_mut().zip(bindings) {
+                    ctx.scopes.push(HashMap::new());
+                    let checked =
+                        declare_bindings(ctx, bindings, arm.line).and_then(|_| match &result {
+                            Some(expected) => self.expect_type(&mut arm.body, expected, ctx),
+                            None => self.check_expr(&mut arm.body, ctx).map(|ty| {
+                                result = Some(ty);
+                            }),
+                        });
+                    ctx.scopes.pop();
+                    checked?;
+                }
+                let ty = result.expect("match has at least one arm");
+                require_determined(&ty, line)?;
+                if ty == TypeAnnotation::Void {
+                    return Err(anyhow!(
+                        "行 {}: match 式の各アームは値を返す必要があります",
+                        line
+                    ));
+                }
+                ty
+            }
+            ExprKind::Is { expr: inner, ty } => {
+                let inner_ty = self.check_expr(inner, ctx)?;
+                self.resolve_local(ty, ctx, line)?;
+                let TypeAnnotation::Union(members) = &inner_ty else {
+                    return Err(anyhow!(
+                        "行 {}: is は union 型の値にしか使えませんが {} が渡されました",
+                        line,
+                        inner_ty
+                    ));
+                };
+                if !members.contains(ty) {
+                    return Err(anyhow!(
+                        "行 {}: {} は {} のメンバーではありません",
+                        line,
+                        ty,
+                        inner_ty
+                    ));
+                }
+                TypeAnnotation::Bool
+            }
+            ExprKind::Coerce(inner) => {
+                // only produced by the checker itself; re-checking keeps the target type
+                self.check_expr(inner, ctx)?;
+                return Ok(expr.ty().clone());
+            }
+        };
+        expr.ty = Some(ty.clone());
+        Ok(ty)
+    }
+
+    /// Rewrites a call of a variable (`f(x)` with a local `f`) into a call of
+    /// the function value it holds, and a top-level function used as a value
+    /// into `ExprKind::FnRef`.
+    fn rewrite_function_value(&self, expr: &mut Expr, ctx: &FnContext) {
+        match &mut expr.kind {
+            ExprKind::Call { callee, args, .. } if ctx.lookup(callee).is_some() => {
+                let callee = Expr::new(ExprKind::Ident(std::mem::take(callee)), expr.line);
+                let args = std::mem::take(args);
+                expr.kind = ExprKind::CallValue {
+                    callee: Box::new(callee),
+                    args,
+                };
+            }
+            ExprKind::Ident(name)
+                if ctx.lookup(name).is_none() && self.functions.contains_key(name.as_str()) =>
+            {
+                expr.kind = ExprKind::FnRef(std::mem::take(name));
+            }
+            _ => {}
+        }
+    }
+
+    /// Rewrites a call of a method without `self` (`Point.origin()`) into a
+    /// plain call of `Point.origin` when `Point` is a struct that is not
+    /// shadowed by a local.
+    fn rewrite_static_method(&self, expr: &mut Expr, ctx: &FnContext) -> Result<()> {
+        let ExprKind::MethodCall {
+            receiver,
+            method,
+            args,
+        } = &mut expr.kind
+        else {
+            return Ok(());
+        };
+        let ExprKind::Ident(owner) = &receiver.kind else {
+            return Ok(());
+        };
+        if !self.structs.contains_key(owner) || ctx.lookup(owner).is_some() {
+            return Ok(());
+        }
+        let callee = format!("{}.{}", owner, method);
+        if !self.functions.contains_key(callee.as_str()) {
+            return Err(anyhow!(
+                "行 {}: 構造体 {} にメソッド {} はありません",
+                expr.line,
+                owner,
+                method
+            ));
+        }
+        let args = std::mem::take(args);
+        expr.kind = ExprKind::Call {
+            callee,
+            args,
+            type_args: Vec::new(),
+        };
+        Ok(())
+    }
+
+    /// `mut self` methods change the value they are called on, so it must be
+    /// a variable that may change, or a field or element of one (array
+    /// elements are shared and always may).
+    fn check_mut_receiver(
+        &self,
+        receiver: &Expr,
+        callee: &str,
+        line: usize,
+        ctx: &FnContext,
+    ) -> Result<()> {
+        let mut root = receiver;
+        while let ExprKind::Field { expr, .. } | ExprKind::Index { expr, .. } = &root.kind {
+            if matches!(root.kind, ExprKind::Index { .. }) {
+                return Ok(());
+            }
+            root = expr;
+        }
+        let local = match &root.kind {
+            ExprKind::Ident(name) => ctx.lookup(name).map(|local| (name, local)),
+            _ => None,
+        };
+        match local {
+            Some((_, local)) if matches!(local.binding, Binding::Let | Binding::Param) => Ok(()),
+            Some((_, local)) if local.binding == Binding::SelfMut && !ctx.is_captured(&local) => {
+                Ok(())
+            }
+            Some((_, local)) if local.binding == Binding::SelfMut => Err(anyhow!(
+                "行 {}: {} は mut self を取るため、クロージャの中の self には呼び出せません",
+                line,
+                callee
+            )),
+            Some((name, _)) if name == OPTIONAL_RECEIVER => Err(anyhow!(
+                "行 {}: {} は mut self を取るため、?. では呼び出せません。none を確かめてから呼び出してください",
+                line,
+                callee
+            )),
+            Some((_, local)) if local.binding == Binding::SelfRef => Err(anyhow!(
+                "行 {}: {} は mut self を取りますが、self は読み取り専用です。呼び出し元のメソッドも mut self で宣言してください",
+                line,
+                callee
+            )),
+            Some((name, _)) => Err(anyhow!(
+                "行 {}: {} は mut self を取るため、変更できない '{}' には呼び出せません",
+                line,
+                callee,
+                name
+            )),
+            None => Err(anyhow!(
+                "行 {}: {} は mut self を取るため、変数かそのフィールド・要素に対してしか呼び出せません",
+                line,
+                callee
+            )),
+        }
+    }
+
+    /// Rewrites `Enum.Variant(args)` / `Enum.Variant` into `ExprKind::Variant`
+    /// when the receiver names an enum that is not shadowed by a local.
+    fn rewrite_variant(&self, expr: &mut Expr, ctx: &FnContext) {
+        let (receiver, variant) = match &expr.kind {
+            ExprKind::MethodCall {
+                receiver, method, ..
+            } => (receiver, method),
+            ExprKind::Field {
+                expr: receiver,
+                name,
+            } => (receiver, name),
+            _ => return,
+        };
+        let ExprKind::Ident(enum_name) = &receiver.kind else {
+            return;
+        };
+        if !self.enums.contains_key(enum_name) || ctx.lookup(enum_name).is_some() {
+            return;
+        }
+        let enum_name = enum_name.clone();
+        let variant = variant.clone();
+        let args = match &mut expr.kind {
+            ExprKind::MethodCall { args, .. } => std::mem::take(args),
+            _ => Vec::new(),
+        };
+        expr.kind = ExprKind::Variant {
+            enum_name,
+            variant,
+            args,
+        };
+    }
+
+    /// The std module (`log` / `time` / `fs`) a method call is made on, unless
+    /// a local of that name shadows it.
+    fn std_module<'e>(&self, receiver: &'e Expr, ctx: &FnContext) -> Option<&'e str> {
+        match &receiver.kind {
+            ExprKind::Ident(name)
+                if matches!(name.as_str(), "log" | "time" | "fs") && ctx.lookup(name).is_none() =>
+            {
+                Some(name)
+            }
+            _ => None,
+        }
+    }
+
+    /// Rewrites the statements `log.info(message)` (`warn` / `error`) and
+    /// `time.sleep(ms)` into the std statements unless a local shadows the
+    /// module.
+    fn rewrite_std_statement(&self, stmt: &mut Stmt, ctx: &FnContext) -> Result<()> {
+        let Stmt::Expr(expr) = stmt else {
+            return Ok(());
+        };
+        let line = expr.line;
+        let ExprKind::MethodCall {
+            receiver,
+            method,
+            args,
+        } = &mut expr.kind
+        else {
+            return Ok(());
+        };
+        let Some(module) = self.std_module(receiver, ctx) else {
+            return Ok(());
+        };
+        let level = match (module, method.as_str()) {
+            ("log", "info") => Some(LogLevel::Info),
+            ("log", "warn") => Some(LogLevel::Warn),
+            ("log", "error") => Some(LogLevel::Error),
+            ("time", "sleep") => None,
+            _ => return Ok(()),
+        };
+        if args.len() != 1 {
+            return Err(anyhow!(
+                "行 {}: {}.{} は 1 個の引数を取りますが {} 個渡されました",
+                line,
+                module,
+                method,
+                args.len()
+            ));
+        }
+        let arg = args.remove(0);
+        *stmt = match level {
+            Some(level) => Stmt::Log {
+                level,
+                message: arg,
+            },
+            None => Stmt::SleepMs(arg),
+        };
+        Ok(())
+    }
+
+    /// Rewrites `time.now()`, `fs.readFile(path)` and `fs.writeFile(path,
+    /// contents)` into the std calls unless a local shadows the module.
+    fn rewrite_std_call(&self, expr: &mut Expr, ctx: &FnContext) -> Result<()> {
+        let ExprKind::MethodCall {
+            receiver,
+            method,
+            args,
+        } = &mut expr.kind
+        else {
+            return Ok(());
+        };
+        let Some(module) = self.std_module(receiver, ctx) else {
+            return Ok(());
+        };
+        let line = expr.line;
+        let arity = match (module, method.as_str()) {
+            ("time", "now") => 0,
+            ("fs", "readFile") => 1,
+            ("fs", "writeFile") => 2,
+            ("log", "info" | "warn" | "error") | ("time", "sleep") => {
+                return Err(anyhow!(
+                    "行 {}: {}.{}(..) は値を返さないため、文としてしか使えません",
+                    line,
+                    module,
+                    method
+                ));
+            }
+            ("log", _) => {
+                return Err(anyhow!(
+                    "行 {}: unsupported log level 'log.{}'; use log.info/log.warn/log.error",
+                    line,
+                    method
+                ));
+            }
+            ("time", _) => {
+                return Err(anyhow!(
+                    "行 {}: unsupported call: time.{}(); use time.now/time.sleep",
+                    line,
+                    method
+                ));
+            }
+            _ => {
+                return Err(anyhow!(
+                    "行 {}: unsupported call: fs.{}(); use fs.readFile/fs.writeFile",
+                    line,
+                    method
+                ));
+            }
+        };
+        if args.len() != arity {
+            return Err(anyhow!(
+                "行 {}: {}.{} は {} 個の引数を取りますが {} 個渡されました",
+                line,
+                module,
+                method,
+                arity,
+                args.len()
+            ));
+        }
+        let mut args = std::mem::take(args).into_iter().map(Box::new);
+        expr.kind = match (args.next(), args.next()) {
+            (Some(path), Some(contents)) => ExprKind::FsWriteFile { path, contents },
+            (Some(path), None) => ExprKind::FsReadFile { path },
+            _ => ExprKind::TimeNow,
+        };
+        Ok(())
+    }
+
+    /// Rewrites `ok(value)` / `err(error)` into the Result constructors.
+    fn rewrite_result_constructor(&self, expr: &mut Expr) -> Result<()> {
+        let ExprKind::Call { callee, args, .. } = &mut expr.kind else {
+            return Ok(());
+        };
+        let is_ok = match callee.as_str() {
+            "ok" => true,
+            "err" => false,
+            _ => return Ok(()),
+        };
+        let mut args = std::mem::take(args);
+        expr.kind = match (is_ok, args.len()) {
+            (true, 0) => ExprKind::ResultOk(None),
+            (true, 1) => ExprKind::ResultOk(Some(Box::new(args.remove(0)))),
+            (false, 1) => ExprKind::ResultErr(Box::new(args.remove(0))),
+            (_, count) => {
+                return Err(anyhow!(
+                    "行 {}: {} は 1 個の引数を取りますが {} 個渡されました",
+                    expr.line,
+                    if is_ok { "ok" } else { "err" },
+                    count
+                ));
+            }
+        };
+        Ok(())
+    }
+
+    /// Rewrites `float(n)`, `i64(n)`, `u8(n)`, .. into numeric conversions.
+    fn rewrite_conversion(&self, expr: &mut Expr) -> Result<()> {
+        let ExprKind::Call { callee, args, .. } = &mut expr.kind else {
+            return Ok(());
+        };
+        let to = crate::parser::parse_type_annotation(callee);
+        if !to.is_numeric() {
+            return Ok(());
+        }
+        if args.len() != 1 {
+            return Err(anyhow!(
+                "行 {}: {} は 1 個の引数を取りますが {} 個渡されました",
+                expr.line,
+                callee,
+                args.len()
+            ));
+        }
+        expr.kind = ExprKind::Convert {
+            expr: Box::new(args.remove(0)),
+            to,
+        };
+        Ok(())
+    }
+
+    /// Resolves match patterns against the scrutinee: the variants of an enum,
+    /// the member types of a union (`int(n)`) or the strings of a literal union
+    /// (`"ok"`). Every arm must be reachable and together they must cover all
+    /// cases. Union member patterns are rewritten to the canonical member name.
+    /// Returns, per arm, the names bound by the pattern with their types.
+    fn check_patterns(
+        &self,
+        scrutinee_ty: &TypeAnnotation,
+        patterns: Vec<(&mut Pattern, usize)>,
+        line: usize,
+    ) -> Result<Vec<Vec<(String, TypeAnnotation)>>> {
+        require_determined(scrutinee_ty, line)?;
+        // (case name, payload types) in declaration / member order
+        let cases: Vec<(String, Vec<TypeAnnotation>)> = match scrutinee_ty {
+            TypeAnnotation::Result(value, error) => {
+                let value = match **value {
+                    TypeAnnotation::Void => Vec::new(),
+                    _ => vec![(**value).clone()],
+                };
+                vec![
+                    ("ok".to_string(), value),
+                    ("err".to_string(), vec![(**error).clone()]),
+                ]
+            }
+            TypeAnnotation::Named(name) if self.enums.contains_key(name) => self.enums[name]
+                .variants
+                .iter()
+                .map(|v| (v.name.clone(), v.fields.clone()))
+                .collect(),
+            TypeAnnotation::Union(members) if scrutinee_ty.is_string() => members
+                .iter()
+                .map(|m| (m.to_string(), Vec::new()))
+                .collect(),
+            TypeAnnotation::Union(members) => members
+                .iter()
+                .map(|m| (m.to_string(), vec![m.clone()]))
+                .collect(),
+            other => {
+                return Err(anyhow!(
+                    "行 {}: match できるのは enum・union・Result の値だけですが {} が渡されました",
+                    line,
+                    other
+                ));
+            }
+        };
+        let is_enum = matches!(
+            scrutinee_ty,
+            TypeAnnotation::Named(_) | TypeAnnotation::Result(..)
+        );
+
+        let mut covered: HashSet<usize> = HashSet::new();
+        let mut has_wildcard = false;
+        let mut result = Vec::new();
+        for (pattern, arm_line) in patterns {
+            if has_wildcard {
+                return Err(anyhow!(
+                    "行 {}: このアームには到達しません (_ の後にあります)",
+                    arm_line
+                ));
+            }
+            let mismatch = |pattern: &Pattern| {
+                let shown = match pattern {
+                    Pattern::Literal(text) => quote_str(text),
+                    Pattern::Variant {
+                        enum_name: Some(enum_name),
+                        name,
+                        ..
+                    } => format!("{}.{}", enum_name, name),
+                    Pattern::Variant { name, .. } => name.clone(),
+                    Pattern::Wildcard => "_".to_string(),
+                };
+                anyhow!(
+                    "行 {}: パターン {} は {} 型と一致しません",
+                    arm_line,
+                    shown,
+                    scrutinee_ty
+                )
+            };
+            let (index, bindings) = match &mut *pattern {
+                Pattern::Wildcard => {
+                    if covered.len() == cases.len() {
+                        return Err(anyhow!(
+                            "行 {}: このアームには到達しません (すべてのケースが処理済みです)",
+                            arm_line
+                        ));
+                    }
+                    has_wildcard = true;
+                    result.push(Vec::new());
+                    continue;
+                }
+                Pattern::Literal(text) => {
+                    let shown = quote_str(text);
+                    let index = cases
+                        .iter()
+                        .position(|(case, payload)| payload.is_empty() && *case == shown);
+                    (index.ok_or_else(|| mismatch(pattern))?, Vec::new())
+                }
+                Pattern::Variant {
+                    enum_name,
+                    name,
+                    bindings,
+                } => {
+                    if is_enum {
+                        if let Some(enum_name) = enum_name
+                            && enum_name.as_str() != scrutinee_ty.to_string()
+                        {
+                            return Err(mismatch(pattern));
+                        }
+                        let Some(index) = cases.iter().position(|(case, _)| case == name) else {
+                            return Err(anyhow!(
+                                "行 {}: enum {} にバリアント '{}' はありません",
+                                arm_line,
+                                scrutinee_ty,
+                                name
+                            ));
+                        };
+                        let payload = &cases[index].1;
+                        if bindings.len() != payload.len() {
+                            return Err(anyhow!(
+                                "行 {}: バリアント {} は {} 個の値を持ちますが、パターンは {} 個です",
+                                arm_line,
+                                name,
+                                payload.len(),
+                                bindings.len()
+                            ));
+                        }
+                        (index, bindings.clone())
+                    } else {
+                        // union member: `int(n)`, `Point(p)` or an alias of a member
+                        let mut member = TypeAnnotation::Named(name.clone());
+                        if enum_name.is_none() {
+                            member = crate::parser::parse_type_annotation(name);
+                            if let TypeAnnotation::Named(alias) = &member
+                                && let Some(target) = self.types.aliases.get(alias)
+                            {
+                                member = target.clone();
+                            }
+                        }
+                        let shown = member.to_string();
+                        let index = cases
+                            .iter()
+                            .position(|(case, payload)| !payload.is_empty() && *case == shown);
+                        let Some(index) = index.filter(|_| bindings.len() <= 1) else {
+                            return Err(mismatch(pattern));
+                        };
+                        *name = shown;
+                        (index, bindings.clone())
+                    }
+                }
+            };
+            if !covered.insert(index) {
+                return Err(anyhow!(
+                    "行 {}: このアームには到達しません ({} は既に処理されています)",
+                    arm_line,
+                    cases[index].0
+                ));
+            }
+            result.push(
+                bindings
+                    .iter()
+                    .zip(&cases[index].1)
+                    .filter(|(binding, _)| binding.as_str() != "_")
+                    .map(|(binding, ty)| (binding.clone(), ty.clone()))
+                    .collect(),
+            );
+        }
+
+        if !has_wildcard {
+            let missing: Vec<&str> = cases
+                .iter()
+                .enumerate()
+                .filter(|(index, _)| !covered.contains(index))
+                .map(|(_, (case, _))| case.as_str())
+                .collect();
+            if !missing.is_empty() {
+                return Err(anyhow!(
+                    "行 {}: match が網羅的ではありません: {} が処理されていません",
+                    line,
+                    missing.join(", ")
+                ));
+            }
+        }
+        Ok(result)
+    }
+
+    fn field_type(&self, ty: &TypeAnnotation, field: &str) -> Option<TypeAnnotation> {
+        if ty.is_string() || matches!(ty, TypeAnnotation::Array(_)) {
+            return (field == "length").then_some(TypeAnnotation::Int);
+        }
+        if let TypeAnnotation::Map(..) | TypeAnnotation::Set(_) = ty {
+            return (field == "size").then_some(TypeAnnotation::Int);
+        }
+        if let TypeAnnotation::Object(fields) = ty {
+            return fields
+                .iter()
+                .find(|(name, _)| name == field)
+                .map(|(_, ty)| ty.clone());
+        }
+        if let TypeAnnotation::Tuple(elems) = ty {
+            return field
+                .parse::<usize>()
+                .ok()
+                .and_then(|i| elems.get(i).cloned());
+        }
+        self.record_fields(ty)?
+            .into_iter()
+            .find(|(name, _)| name == field)
+            .map(|(_, ty)| ty)
+    }
+}
+
+fn result_type(value: TypeAnnotation, error: TypeAnnotation) -> TypeAnnotation {
+    TypeAnnotation::Result(Box::new(value), Box::new(error))
+}
+
+/// A Result built by `ok(..)` / `err(..)` whose other side is still open, the
+/// empty array literal `[]`, `new Map()` / `new Set()` without type
+/// arguments, or an object or generic struct holding one of them (or `none`).
+fn is_undetermined(ty: &TypeAnnotation) -> bool {
+    match ty {
+        TypeAnnotation::None => true,
+        TypeAnnotation::Result(value, error) => is_undetermined(value) || is_undetermined(error),
+        TypeAnnotation::Array(elem) | TypeAnnotation::Set(elem) => is_undetermined(elem),
+        TypeAnnotation::Map(key, value) => is_undetermined(key) || is_undetermined(value),
+        TypeAnnotation::Object(fields) => fields.iter().any(|(_, ty)| is_undetermined(ty)),
+        TypeAnnotation::Generic(_, args) | TypeAnnotation::Tuple(args) => {
+            args.iter().any(is_undetermined)
+        }
+        TypeAnnotation::Function(params, ret) => {
+            params.iter().any(is_undetermined) || is_undetermined(ret)
+        }
+        _ => false,
+    }
+}
+
+fn require_determined(ty: &TypeAnnotation, line: usize) -> Result<()> {
+    if let TypeAnnotation::Array(elem) = ty
+        && **elem == TypeAnnotation::None
+    {
+        return Err(anyhow!(
+            "行 {}: 空の配列 [] の要素型を決められません。型注釈のある変数・引数・戻り値に使ってください",
+            line
+        ));
+    }
+    if let TypeAnnotation::Map(key, _) | TypeAnnotation::Set(key) = ty
+        && **key == TypeAnnotation::None
+    {
+        return Err(anyhow!(
+            "行 {}: {} の型を決められません。new Map<string, int>() のように型引数を書くか、型注釈のある変数・引数・戻り値に使ってください",
+            line,
+            ty
+        ));
+    }
+    if matches!(ty, TypeAnnotation::Result(..) | TypeAnnotation::Array(_)) && is_undetermined(ty) {
+        return Err(anyhow!(
+            "行 {}: {} の型を決められません。ok(..) / err(..) は型注釈のある変数・引数・戻り値に使ってください",
+            line,
+            ty
+        ));
+    }
+    Ok(())
+}
+
+/// Result type of `lhs op rhs`, or `None` when the operand types do not fit.
+fn binary_result(
+    op: BinaryOp,
+    lhs: &TypeAnnotation,
+    rhs: &TypeAnnotation,
+) -> Option<TypeAnnotation> {
+    use TypeAnnotation::{Bool, String};
+
+    // string literal types behave like plain strings in expressions
+    let widen = |ty: &TypeAnnotation| if ty.is_string() { String } else { ty.clone() };
+    let (lhs, rhs) = (&widen(lhs), &widen(rhs));
+
+    // `x == none` / `x != none`
+    let none_test = |a: &TypeAnnotation, b: &TypeAnnotation| {
+        a == &TypeAnnotation::None
+            && matches!(b, TypeAnnotation::Nullable(_) | TypeAnnotation::None)
+    };
+    if matches!(op, BinaryOp::Eq | BinaryOp::Ne) && (none_test(lhs, rhs) || none_test(rhs, lhs)) {
+        return Some(Bool);
+    }
+
+    match op {
+        // `+` concatenates as soon as one side is a string (ints/bools are stringified).
+        BinaryOp::Add if lhs == &String || rhs == &String => {
+            (is_printable(lhs) && is_printable(rhs)).then_some(String)
+        }
+        // both operands have the same numeric type; `%` is for integers only
+        BinaryOp::Add | BinaryOp::Sub | BinaryOp::Mul | BinaryOp::Div => {
+            (lhs == rhs && lhs.is_numeric()).then(|| lhs.clone())
+        }
+        BinaryOp::Rem => (lhs == rhs && lhs.is_integer()).then(|| lhs.clone()),
+        BinaryOp::Eq | BinaryOp::Ne => {
+            (lhs == rhs && (lhs.is_numeric() || matches!(lhs, String | Bool))).then_some(Bool)
+        }
+        // strings compare lexicographically by bytes
+        BinaryOp::Lt | BinaryOp::Le | BinaryOp::Gt | BinaryOp::Ge => {
+            (lhs == rhs && (lhs.is_numeric() || lhs == &String)).then_some(Bool)
+        }
+        BinaryOp::And | BinaryOp::Or => (lhs == &Bool && rhs == &Bool).then_some(Bool),
+        // checked separately in `check_expr`
+        BinaryOp::Coalesce => None,
+    }
+}
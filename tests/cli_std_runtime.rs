@@ -55,12 +55,85 @@ fn stdlib_showcase_runs_with_relative_imports() {
     cmd.assert()
         .success()
         .stdout(predicate::str::contains("stdlib showcase start"))
-        .stdout(predicate::str::contains("helper tick"))
+        .stdout(predicate::str::contains("helper tick 1: showcase"))
         .stdout(predicate::str::contains("helper finished"))
         .stdout(predicate::str::contains("showcase payload"))
         .stdout(predicate::str::contains("stdlib showcase done"));
 }
 
+#[test]
+fn imported_function_panics_name_their_own_file() {
+    let _guard = BUILD_LOCK
+        .get_or_init(|| Mutex::new(()))
+        .lock()
+        .expect("lock poisoned");
+
+    let manifest_dir = PathBuf::from(env!("CARGO_MANIFEST_DIR"));
+    let dist = manifest_dir.join("dist");
+    if dist.exists() {
+        let _ = fs::remove_dir_all(&dist);
+    }
+
+    let mut cmd = Command::new(env!("CARGO_BIN_EXE_voltts"));
+    cmd.current_dir(&manifest_dir)
+        .arg("run")
+        .arg("tests/import_panic.vts");
+
+    cmd.assert()
+        .failure()
+        .stdout(predicate::str::contains("2"))
+        .stderr(predicate::str::contains(
+            "tests/helpers/bounds.vts:3: インデックス 5 は範囲外です (長さ 3)",
+        ));
+}
+
+#[test]
+fn names_that_clash_with_c_and_libc_still_build() {
+    let _guard = BUILD_LOCK
+        .get_or_init(|| Mutex::new(()))
+        .lock()
+        .expect("lock poisoned");
+
+    let manifest_dir = PathBuf::from(env!("CARGO_MANIFEST_DIR"));
+    let dist = manifest_dir.join("dist");
+    if dist.exists() {
+        let _ = fs::remove_dir_all(&dist);
+    }
+
+    let mut cmd = Command::new(env!("CARGO_BIN_EXE_voltts"));
+    cmd.current_dir(&manifest_dir)
+        .arg("run")
+        .arg("tests/c_names.vts");
+
+    cmd.assert()
+        .success()
+        .stdout(predicate::str::contains("log fn"))
+        .stdout(predicate::str::contains("2 2 3 7 r"));
+}
+
+#[test]
+fn types_only_modules_can_be_imported() {
+    let _guard = BUILD_LOCK
+        .get_or_init(|| Mutex::new(()))
+        .lock()
+        .expect("lock poisoned");
+
+    let manifest_dir = PathBuf::from(env!("CARGO_MANIFEST_DIR"));
+    let dist = manifest_dir.join("dist");
+    if dist.exists() {
+        let _ = fs::remove_dir_all(&dist);
+    }
+
+    let mut cmd = Command::new(env!("CARGO_BIN_EXE_voltts"));
+    cmd.current_dir(&manifest_dir)
+        .arg("run")
+        .arg("tests/import_types.vts");
+
+    cmd.assert()
+        .success()
+        .stdout(predicate::str::contains("solid 12"));
+}
+
 #[test]
 fn fs_runtime_writes_and_reads_files() {
     let _guard = BUILD_LOCK
@@ -83,6 +156,7 @@ fn fs_runtime_writes_and_reads_files() {
         .success()
         .stdout(predicate::str::contains("fs demo start"))
         .stdout(predicate::str::contains("hello fs runtime"))
+        .stdout(predicate::str::contains("nul bytes kept: 3"))
         .stdout(predicate::str::contains("fs demo end"));
 
     let tmp_path = manifest_dir.join("tmp_fs.txt");
@@ -111,7 +185,9 @@ fn std_log_and_time_example_runs() {
 
     cmd.assert()
         .success()
-        .stdout(predicate::str::contains("std log/time demo start"))
+        .stdout(predicate::str::contains("[info] std log/time demo start"))
+        .stdout(predicate::str::contains("[info] slept "))
+        .stdout(predicate::str::contains(">> custom logger"))
         .stdout(predicate::str::contains("std log/time demo end"));
 }
 
@@ -167,6 +243,9 @@ fn control_flow_constructs_emit_expected_output() {
         .stdout(predicate::str::contains("control flow start"))
         .stdout(predicate::str::contains("[info] if/else hit"))
         .stdout(predicate::str::contains("[info] range loop"))
+        .stdout(predicate::str::contains("odd even one"))
+        .stdout(predicate::str::contains("3x2"))
+        .stdout(predicate::str::contains("0123 1234 8;4;0;"))
         .stdout(predicate::str::contains("control flow end"));
 }
 
@@ -192,3 +271,514 @@ fn void_main_example_runs() {
         .success()
         .stdout(predicate::str::contains("hello (void)"));
 }
+
+#[test]
+fn expressions_example_evaluates_operators() {
+    let _guard = BUILD_LOCK
+        .get_or_init(|| Mutex::new(()))
+        .lock()
+        .expect("lock poisoned");
+
+    let manifest_dir = PathBuf::from(env!("CARGO_MANIFEST_DIR"));
+    let dist = manifest_dir.join("dist");
+    if dist.exists() {
+        let _ = fs::remove_dir_all(&dist);
+    }
+
+    let mut cmd = Command::new(env!("CARGO_BIN_EXE_voltts"));
+    cmd.current_dir(&manifest_dir)
+        .arg("run")
+        .arg("examples/expressions.vts");
+
+    cmd.assert()
+        .success()
+        .stdout(predicate::str::contains("7\n"))
+        .stdout(predicate::str::contains("n=2"))
+        .stdout(predicate::str::contains("[info] sum is 42"))
+        .stdout(predicate::str::contains("true"));
+}
+
+#[test]
+fn bindings_example_uses_let_and_const() {
+    let _guard = BUILD_LOCK
+        .get_or_init(|| Mutex::new(()))
+        .lock()
+        .expect("lock poisoned");
+
+    let manifest_dir = PathBuf::from(env!("CARGO_MANIFEST_DIR"));
+    let dist = manifest_dir.join("dist");
+    if dist.exists() {
+        let _ = fs::remove_dir_all(&dist);
+    }
+
+    let mut cmd = Command::new(env!("CARGO_BIN_EXE_voltts"));
+    cmd.current_dir(&manifest_dir)
+        .arg("run")
+        .arg("examples/bindings.vts");
+
+    cmd.assert()
+        .success()
+        .stdout(predicate::str::contains("shadowed\ntotal=42\n"));
+}
+
+#[test]
+fn structs_example_builds_and_passes_values() {
+    let _guard = BUILD_LOCK
+        .get_or_init(|| Mutex::new(()))
+        .lock()
+        .expect("lock poisoned");
+
+    let manifest_dir = PathBuf::from(env!("CARGO_MANIFEST_DIR"));
+    let dist = manifest_dir.join("dist");
+    if dist.exists() {
+        let _ = fs::remove_dir_all(&dist);
+    }
+
+    let mut cmd = Command::new(env!("CARGO_BIN_EXE_voltts"));
+    cmd.current_dir(&manifest_dir)
+        .arg("run")
+        .arg("examples/structs.vts");
+
+    cmd.assert()
+        .success()
+        .stdout(predicate::str::contains("origin=6,0"))
+        .stdout(predicate::str::contains("box area=20"));
+}
+
+#[test]
+fn enums_example_matches_on_variants() {
+    let _guard = BUILD_LOCK
+        .get_or_init(|| Mutex::new(()))
+        .lock()
+        .expect("lock poisoned");
+
+    let manifest_dir = PathBuf::from(env!("CARGO_MANIFEST_DIR"));
+    let dist = manifest_dir.join("dist");
+    if dist.exists() {
+        let _ = fs::remove_dir_all(&dist);
+    }
+
+    let mut cmd = Command::new(env!("CARGO_BIN_EXE_voltts"));
+    cmd.current_dir(&manifest_dir)
+        .arg("run")
+        .arg("examples/enums.vts");
+
+    cmd.assert()
+        .success()
+        .stdout(predicate::str::contains("rect area=20"))
+        .stdout(predicate::str::contains("circle r=2 area=12"))
+        .stdout(predicate::str::contains("total=3"));
+}
+
+#[test]
+fn unions_example_narrows_members() {
+    let _guard = BUILD_LOCK
+        .get_or_init(|| Mutex::new(()))
+        .lock()
+        .expect("lock poisoned");
+
+    let manifest_dir = PathBuf::from(env!("CARGO_MANIFEST_DIR"));
+    let dist = manifest_dir.join("dist");
+    if dist.exists() {
+        let _ = fs::remove_dir_all(&dist);
+    }
+
+    let mut cmd = Command::new(env!("CARGO_BIN_EXE_voltts"));
+    cmd.current_dir(&manifest_dir)
+        .arg("run")
+        .arg("examples/unions.vts");
+
+    cmd.assert()
+        .success()
+        .stdout(predicate::str::contains("int 8"))
+        .stdout(predicate::str::contains("string seven"))
+        .stdout(predicate::str::contains("all good"))
+        .stdout(predicate::str::contains("status=err"))
+        .stdout(predicate::str::contains("12\n5"));
+}
+
+#[test]
+fn nullable_example_checks_for_none() {
+    let _guard = BUILD_LOCK
+        .get_or_init(|| Mutex::new(()))
+        .lock()
+        .expect("lock poisoned");
+
+    let manifest_dir = PathBuf::from(env!("CARGO_MANIFEST_DIR"));
+    let dist = manifest_dir.join("dist");
+    if dist.exists() {
+        let _ = fs::remove_dir_all(&dist);
+    }
+
+    let mut cmd = Command::new(env!("CARGO_BIN_EXE_voltts"));
+    cmd.current_dir(&manifest_dir)
+        .arg("run")
+        .arg("examples/nullable.vts");
+
+    cmd.assert()
+        .success()
+        .stdout(predicate::str::contains("alice (30)"))
+        .stdout(predicate::str::contains("no user 2"))
+        .stdout(predicate::str::contains("hello guest"))
+        .stdout(predicate::str::contains("hi alice\nnobody\n"))
+        .stdout(predicate::str::contains("adult"))
+        .stdout(predicate::str::contains("total=107"))
+        .stdout(predicate::str::contains("maybe=3"));
+}
+
+#[test]
+fn results_example_propagates_errors() {
+    let _guard = BUILD_LOCK
+        .get_or_init(|| Mutex::new(()))
+        .lock()
+        .expect("lock poisoned");
+
+    let manifest_dir = PathBuf::from(env!("CARGO_MANIFEST_DIR"));
+    let dist = manifest_dir.join("dist");
+    if dist.exists() {
+        let _ = fs::remove_dir_all(&dist);
+    }
+
+    let mut cmd = Command::new(env!("CARGO_BIN_EXE_voltts"));
+    cmd.current_dir(&manifest_dir)
+        .arg("run")
+        .arg("examples/results.vts");
+
+    cmd.assert()
+        .success()
+        .stdout(predicate::str::contains("port 80"))
+        .stdout(predicate::str::contains("port 16160"))
+        .stdout(predicate::str::contains("error: invalid port: abc"))
+        .stdout(predicate::str::contains("config=debug"))
+        .stdout(predicate::str::contains(
+            "fs.readFile failed: dist/examples/missing.txt",
+        ));
+}
+
+#[test]
+fn strings_example_panics_on_out_of_range_index() {
+    let _guard = BUILD_LOCK
+        .get_or_init(|| Mutex::new(()))
+        .lock()
+        .expect("lock poisoned");
+
+    let manifest_dir = PathBuf::from(env!("CARGO_MANIFEST_DIR"));
+    let dist = manifest_dir.join("dist");
+    if dist.exists() {
+        let _ = fs::remove_dir_all(&dist);
+    }
+
+    let mut cmd = Command::new(env!("CARGO_BIN_EXE_voltts"));
+    cmd.current_dir(&manifest_dir)
+        .arg("run")
+        .arg("examples/strings.vts");
+
+    cmd.assert()
+        .failure()
+        .stdout(predicate::str::contains(
+            "hello, volt\n11\nht\nvolt\nhello\nvol",
+        ))
+        .stdout(predicate::str::contains("count=42 true"))
+        .stderr(predicate::str::contains(
+            "examples/strings.vts:24: インデックス 100 は範囲外です (長さ 11)",
+        ));
+}
+
+#[test]
+fn templates_example_interpolates_values() {
+    let _guard = BUILD_LOCK
+        .get_or_init(|| Mutex::new(()))
+        .lock()
+        .expect("lock poisoned");
+
+    let manifest_dir = PathBuf::from(env!("CARGO_MANIFEST_DIR"));
+    let dist = manifest_dir.join("dist");
+    if dist.exists() {
+        let _ = fs::remove_dir_all(&dist);
+    }
+
+    let mut cmd = Command::new(env!("CARGO_BIN_EXE_voltts"));
+    cmd.current_dir(&manifest_dir)
+        .arg("run")
+        .arg("examples/templates.vts");
+
+    cmd.assert()
+        .success()
+        .stdout(predicate::str::contains("hello volt, you have 3 items"))
+        .stdout(predicate::str::contains(
+            "[info] count doubled: 6, even: false",
+        ))
+        .stdout(predicate::str::contains("cart: volt has 1 item(s)"))
+        .stdout(predicate::str::contains(
+            "nested: inner 4 \"quoted\" `tick` ${literal}",
+        ))
+        .stdout(predicate::str::contains("multi\nline"));
+}
+
+#[test]
+fn escapes_example_prints_exact_bytes() {
+    let _guard = BUILD_LOCK
+        .get_or_init(|| Mutex::new(()))
+        .lock()
+        .expect("lock poisoned");
+
+    let manifest_dir = PathBuf::from(env!("CARGO_MANIFEST_DIR"));
+    let dist = manifest_dir.join("dist");
+    if dist.exists() {
+        let _ = fs::remove_dir_all(&dist);
+    }
+
+    let mut cmd = Command::new(env!("CARGO_BIN_EXE_voltts"));
+    cmd.current_dir(&manifest_dir)
+        .arg("run")
+        .arg("examples/escapes.vts");
+
+    cmd.assert()
+        .success()
+        .stdout(predicate::str::contains(
+            "tab:\t|quote:\" backslash:\\ percent:%d %s\ntwo\nlines\n",
+        ))
+        .stdout(predicate::str::contains(
+            "unicode: \u{3b1}\u{1F600} 日本語 café",
+        ))
+        .stdout(predicate::str::contains("trigraph??= stays"))
+        .stdout(predicate::str::contains("[warn] 50% done, path C:\\tmp"))
+        .stdout(predicate::str::contains(
+            "read 15 bytes: line1\nline2\t\u{2713}",
+        ))
+        .stdout(predicate::str::contains("template ` ${x} \"quotes\" \\ A"));
+
+    let written = manifest_dir.join("dist/examples/a,b \"escapes\".txt");
+    assert_eq!(
+        fs::read_to_string(written).expect("read escaped path"),
+        "line1\nline2\t\u{2713}"
+    );
+}
+
+#[test]
+fn arrays_example_runs_and_checks_bounds() {
+    let _guard = BUILD_LOCK
+        .get_or_init(|| Mutex::new(()))
+        .lock()
+        .expect("lock poisoned");
+
+    let manifest_dir = PathBuf::from(env!("CARGO_MANIFEST_DIR"));
+    let dist = manifest_dir.join("dist");
+    if dist.exists() {
+        let _ = fs::remove_dir_all(&dist);
+    }
+
+    let mut cmd = Command::new(env!("CARGO_BIN_EXE_voltts"));
+    cmd.current_dir(&manifest_dir)
+        .arg("run")
+        .arg("examples/arrays.vts");
+
+    cmd.assert()
+        .failure()
+        .stdout(predicate::str::contains("4\n10\n10 7 4\npopped 4\n3\n"))
+        .stdout(predicate::str::contains("squares: 1 16 (5)"))
+        .stdout(predicate::str::contains(
+            "volt 4\nts 2\narrays 6\n32\n4\nempty\n2\n2.25\n",
+        ))
+        .stderr(predicate::str::contains(
+            "examples/arrays.vts:62: インデックス 7 は範囲外です (長さ 3)",
+        ));
+}
+
+#[test]
+fn collections_example_runs() {
+    let _guard = BUILD_LOCK
+        .get_or_init(|| Mutex::new(()))
+        .lock()
+        .expect("lock poisoned");
+
+    let manifest_dir = PathBuf::from(env!("CARGO_MANIFEST_DIR"));
+    let dist = manifest_dir.join("dist");
+    if dist.exists() {
+        let _ = fs::remove_dir_all(&dist);
+    }
+
+    let mut cmd = Command::new(env!("CARGO_BIN_EXE_voltts"));
+    cmd.current_dir(&manifest_dir)
+        .arg("run")
+        .arg("examples/collections.vts");
+
+    cmd.assert()
+        .success()
+        .stdout(predicate::str::contains(
+            "3\n3\n-1\nvolt: 3\nts: 1\nmap: 1\n",
+        ))
+        .stdout(predicate::str::contains(
+            "100\n1764\ntrue\nfalse\nfalse\n326531\n",
+        ))
+        .stdout(predicate::str::contains("2\ntrue\nseen b\ntrue\nnone\n"));
+}
+
+#[test]
+fn objects_example_runs() {
+    let _guard = BUILD_LOCK
+        .get_or_init(|| Mutex::new(()))
+        .lock()
+        .expect("lock poisoned");
+
+    let manifest_dir = PathBuf::from(env!("CARGO_MANIFEST_DIR"));
+    let dist = manifest_dir.join("dist");
+    if dist.exists() {
+        let _ = fs::remove_dir_all(&dist);
+    }
+
+    let mut cmd = Command::new(env!("CARGO_BIN_EXE_voltts"));
+    cmd.current_dir(&manifest_dir)
+        .arg("run")
+        .arg("examples/objects.vts");
+
+    cmd.assert().success().stdout(predicate::str::contains(
+        "volt (4)\nhello volt\nts\nx (7)\n5\nno note\n1: one\n2: TWO\n",
+    ));
+}
+
+#[test]
+fn generics_example_runs() {
+    let _guard = BUILD_LOCK
+        .get_or_init(|| Mutex::new(()))
+        .lock()
+        .expect("lock poisoned");
+
+    let manifest_dir = PathBuf::from(env!("CARGO_MANIFEST_DIR"));
+    let dist = manifest_dir.join("dist");
+    if dist.exists() {
+        let _ = fs::remove_dir_all(&dist);
+    }
+
+    let mut cmd = Command::new(env!("CARGO_BIN_EXE_voltts"));
+    cmd.current_dir(&manifest_dir)
+        .arg("run")
+        .arg("examples/generics.vts");
+
+    cmd.assert().success().stdout(predicate::str::contains(
+        "3\nvolt\n-1\n43\nbox\n0\none 1\n3\n7\n",
+    ));
+}
+
+#[test]
+fn numbers_example_checks_overflow() {
+    let _guard = BUILD_LOCK
+        .get_or_init(|| Mutex::new(()))
+        .lock()
+        .expect("lock poisoned");
+
+    let manifest_dir = PathBuf::from(env!("CARGO_MANIFEST_DIR"));
+    let dist = manifest_dir.join("dist");
+    if dist.exists() {
+        let _ = fs::remove_dir_all(&dist);
+    }
+
+    let mut cmd = Command::new(env!("CARGO_BIN_EXE_voltts"));
+    cmd.current_dir(&manifest_dir)
+        .arg("run")
+        .arg("examples/numbers.vts");
+
+    cmd.assert()
+        .failure()
+        .stdout(predicate::str::contains(
+            "0.30000000000000004\n3\n2.6666666666666665\n3\n3.5\n15000000000\n",
+        ))
+        .stdout(predicate::str::contains(
+            "max=18000000000000000000 big=5000000000\n255\n3\n-3\n66\n2147483648\ntrue\n10000000000\n",
+        ))
+        .stderr(predicate::str::contains(
+            "examples/numbers.vts:41: u8 の演算 + がオーバーフローしました",
+        ));
+}
+
+#[test]
+fn closures_example_captures_variables() {
+    let _guard = BUILD_LOCK
+        .get_or_init(|| Mutex::new(()))
+        .lock()
+        .expect("lock poisoned");
+
+    let manifest_dir = PathBuf::from(env!("CARGO_MANIFEST_DIR"));
+    let dist = manifest_dir.join("dist");
+    if dist.exists() {
+        let _ = fs::remove_dir_all(&dist);
+    }
+
+    let mut cmd = Command::new(env!("CARGO_BIN_EXE_voltts"));
+    cmd.current_dir(&manifest_dir)
+        .arg("run")
+        .arg("examples/closures.vts");
+
+    cmd.assert().success().stdout(predicate::str::contains(
+        "5\n8\n2\n1\n2\n3\n4\n#1\n21\n81\ntrue\n3\n10\nhello volt\nclicked\nok addition\n",
+    ));
+}
+
+#[test]
+fn methods_example_calls_struct_methods() {
+    let _guard = BUILD_LOCK
+        .get_or_init(|| Mutex::new(()))
+        .lock()
+        .expect("lock poisoned");
+
+    let manifest_dir = PathBuf::from(env!("CARGO_MANIFEST_DIR"));
+    let dist = manifest_dir.join("dist");
+    if dist.exists() {
+        let _ = fs::remove_dir_all(&dist);
+    }
+
+    let mut cmd = Command::new(env!("CARGO_BIN_EXE_voltts"));
+    cmd.current_dir(&manifest_dir)
+        .arg("run")
+        .arg("examples/methods.vts");
+
+    cmd.assert()
+        .success()
+        .stdout(predicate::str::contains("25\n6\n8\n100\n15\n15 by 5\n3\n"));
+}
+
+#[test]
+fn interfaces_example_dispatches_per_struct() {
+    let _guard = BUILD_LOCK
+        .get_or_init(|| Mutex::new(()))
+        .lock()
+        .expect("lock poisoned");
+
+    let manifest_dir = PathBuf::from(env!("CARGO_MANIFEST_DIR"));
+    let dist = manifest_dir.join("dist");
+    if dist.exists() {
+        let _ = fs::remove_dir_all(&dist);
+    }
+
+    let mut cmd = Command::new(env!("CARGO_BIN_EXE_voltts"));
+    cmd.current_dir(&manifest_dir)
+        .arg("run")
+        .arg("examples/interfaces.vts");
+
+    cmd.assert().success().stdout(predicate::str::contains(
+        "> line 0\n> line 1\n3\nhello from console\n> ===\n> line 0\n",
+    ));
+}
+
+#[test]
+fn tuples_example_destructures_values() {
+    let _guard = BUILD_LOCK
+        .get_or_init(|| Mutex::new(()))
+        .lock()
+        .expect("lock poisoned");
+
+    let manifest_dir = PathBuf::from(env!("CARGO_MANIFEST_DIR"));
+    let dist = manifest_dir.join("dist");
+    if dist.exists() {
+        let _ = fs::remove_dir_all(&dist);
+    }
+
+    let mut cmd = Command::new(env!("CARGO_BIN_EXE_voltts"));
+    cmd.current_dir(&manifest_dir)
+        .arg("run")
+        .arg("examples/tuples.vts");
+
+    cmd.assert().success().stdout(predicate::str::contains(
+        "357\n6\n7\n-12\nac\nada is 36\nalan is 41\none=1\ntwo=2\n",
+    ));
+}